@@ -0,0 +1,163 @@
+//! Headless mode, so target lists can be scripted without opening the GUI:
+//!
+//! `turunmap query --server de99 --selections mine.tms --format csv`
+//!
+//! The data is loaded through the same `Presenter` as in the GUI, either live from the data
+//! source or from a saved `.apiresponse` file. The towns of all selections are printed to stdout.
+
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context};
+
+use crate::emptyselection::EmptyTownSelection;
+use crate::export::{self, ExportFormat};
+use crate::model::source::DataSourceConfig;
+use crate::model::APIResponse;
+use crate::presenter::{Presenter, PresenterReady};
+
+/// give up if the data is not loaded after this time
+const LOAD_TIMEOUT: Duration = Duration::from_secs(5 * 60);
+
+const USAGE: &str = "Usage: turunmap query (--server <id> | --file <path.apiresponse>) --selections <path.tms> [--selections <path.tms> ...] [--format csv|json] [--data-dir <dir>]
+
+  --server <id>          fetch the current data for this server, e.g. de99
+  --file <path>          use data saved by the app instead (.apiresponse)
+  --selections <path>    file with exported selections (.tms), can be given multiple times
+  --format <format>      csv (default) or json
+  --data-dir <dir>       read the .txt files of the world data from this directory instead of the internet";
+
+struct QueryArgs {
+    server: Option<String>,
+    file: Option<PathBuf>,
+    selections: Vec<PathBuf>,
+    format: ExportFormat,
+    data_dir: Option<PathBuf>,
+}
+
+impl QueryArgs {
+    fn parse(args: &[String]) -> anyhow::Result<Self> {
+        let mut re = Self {
+            server: None,
+            file: None,
+            selections: Vec::new(),
+            format: ExportFormat::Csv,
+            data_dir: None,
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .with_context(|| format!("Missing value for {arg}"))
+            };
+            match arg.as_str() {
+                "--server" => re.server = Some(value()?.clone()),
+                "--file" => re.file = Some(PathBuf::from(value()?)),
+                "--selections" => re.selections.push(PathBuf::from(value()?)),
+                "--format" => {
+                    let name = value()?;
+                    re.format = ExportFormat::from_name(name)
+                        .with_context(|| format!("Unknown format {name}"))?;
+                }
+                "--data-dir" => re.data_dir = Some(PathBuf::from(value()?)),
+                _ => bail!("Unknown argument {arg}"),
+            }
+        }
+        if re.server.is_some() == re.file.is_some() {
+            bail!("Exactly one of --server and --file is required");
+        }
+        if re.selections.is_empty() {
+            bail!("At least one --selections file is required");
+        }
+        return Ok(re);
+    }
+}
+
+/// run the command line interface with the arguments after the program name. Returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+    if args.first().map(String::as_str) != Some("query") {
+        eprintln!("{USAGE}");
+        return 2;
+    }
+    let query_args = match QueryArgs::parse(&args[1..]) {
+        Ok(query_args) => query_args,
+        Err(err) => {
+            eprintln!("{err:#}\n\n{USAGE}");
+            return 2;
+        }
+    };
+    match query(&query_args) {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("{err:#}");
+            1
+        }
+    }
+}
+
+fn query(args: &QueryArgs) -> anyhow::Result<()> {
+    let mut selections = Vec::new();
+    for result in EmptyTownSelection::try_from_path(&args.selections) {
+        selections.extend(result?);
+    }
+
+    let mut presenter = Presenter::default();
+    // the process exits right after printing, a save file written in the background would be cut off
+    presenter.set_save_api_responses(false);
+    if let Some(dir) = &args.data_dir {
+        presenter.set_data_source(DataSourceConfig::LocalDirectory(dir.clone()));
+    }
+    if let Some(server) = &args.server {
+        presenter.load_server(server.clone());
+    } else if let Some(file) = &args.file {
+        // read right away, a file that can not be read would otherwise only show up as a timeout
+        let api_response = APIResponse::read_from_file(&file.clone().into())?;
+        if !api_response.is_complete() {
+            bail!(
+                "{} does not contain all files of the world data",
+                file.display()
+            );
+        }
+        presenter.load_api_response(api_response);
+    }
+
+    // selections that compare with older snapshots have to wait until those are read as well
+    let needs_history = selections.iter().any(EmptyTownSelection::uses_history);
+    let start = Instant::now();
+    loop {
+        match presenter.ready_for_requests()? {
+            PresenterReady::HistoryLoaded => break,
+            PresenterReady::AlwaysHasBeen | PresenterReady::NewlyReady if !needs_history => break,
+            PresenterReady::NewlyReady => presenter.request_history(),
+            PresenterReady::AlwaysHasBeen | PresenterReady::WaitingForAPI => {
+                if start.elapsed() > LOAD_TIMEOUT {
+                    bail!("Loading the data did not finish within {LOAD_TIMEOUT:?}");
+                }
+                thread::sleep(Duration::from_millis(50));
+            }
+            PresenterReady::DownloadFailed(failures) => {
+                let reasons: Vec<String> = failures
+                    .iter()
+                    .map(|(file, reason)| format!("{}: {reason}", file.txt_stem()))
+                    .collect();
+                bail!("Failed to fetch the data:\n{}", reasons.join("\n"));
+            }
+        }
+    }
+
+    let report = presenter.get_parse_report();
+    if !report.bad_lines.is_empty() {
+        eprintln!("Skipped {} unparsable lines", report.bad_lines.len());
+    }
+
+    let mut towns = Vec::with_capacity(selections.len());
+    for selection in &selections {
+        let selection_towns = presenter
+            .towns_for_selection(selection, &selections)
+            .with_context(|| format!("Failed to evaluate selection {}", selection.name))?;
+        towns.push((selection.name.clone(), selection_towns));
+    }
+    print!("{}", export::export_towns(args.format, &towns)?);
+    return Ok(());
+}
@@ -1,5 +1,6 @@
 use crate::emptyconstraint::EmptyConstraint;
 use crate::emptyselection::EmptyTownSelection;
+use crate::model::history;
 use crate::presenter::Presenter;
 use crate::selection::AndOr;
 use crate::view::dropdownbox::DropDownBox;
@@ -49,6 +50,29 @@ impl fmt::Debug for Constraint {
 }
 
 impl Constraint {
+    /// The combo box to choose the comparator, with the comparators the constraint type allows.
+    /// Returns true if another comparator was chosen.
+    fn comparator_ui(&mut self, ui: &mut egui::Ui, id: String) -> bool {
+        let mut re = false;
+        let _inner_response = egui::ComboBox::from_id_source(id)
+            .width(ui.style().spacing.interact_size.x * 1.75)
+            .selected_text(self.comparator.to_string())
+            .show_ui(ui, |ui| {
+                for value in Comparator::iter()
+                    .filter(|value| self.constraint_type.allows_comparator(*value))
+                {
+                    let text = value.to_string();
+                    if ui
+                        .selectable_value(&mut self.comparator, value, text)
+                        .clicked()
+                    {
+                        re = true;
+                    }
+                }
+            });
+        return re;
+    }
+
     pub fn partial_clone(&self) -> EmptyConstraint {
         EmptyConstraint {
             constraint_type: self.constraint_type,
@@ -86,35 +110,27 @@ impl Constraint {
                         .selectable_value(&mut self.constraint_type, value, text)
                         .clicked()
                     {
+                        if !value.allows_comparator(self.comparator) {
+                            self.comparator = Comparator::Equal;
+                        }
                         re_edited = true;
                     }
                 }
             });
 
             // with which comparison method (<=, ==, >=, <>)?
-            let _inner_response = egui::ComboBox::from_id_source(format!(
-                "ComboxBox {selection_index}/{constraint_index} Comparator"
-            ))
-            .width(ui.style().spacing.interact_size.x * 1.75)
-            .selected_text(self.comparator.to_string())
-            .show_ui(ui, |ui| {
-                for value in Comparator::iter() {
-                    let text = value.to_string();
-                    if ui
-                        .selectable_value(&mut self.comparator, value, text)
-                        .clicked()
-                    {
-                        re_edited = true;
-                    }
-                }
-            });
+            let id = format!("ComboxBox {selection_index}/{constraint_index} Comparator");
+            if self.comparator_ui(ui, id) {
+                re_edited = true;
+            }
 
             // List of possible values
             let ddb = DropDownBox::from_iter(
                 self.drop_down_values.as_ref(),
                 format!("ComboBox {selection_index}/{constraint_index} Value"),
                 &mut self.value,
-            );
+            )
+            .with_labels(self.constraint_type.drop_down_label());
             let ddb_response = ui.add_sized(
                 [
                     ui.style().spacing.interact_size.x * 4.5,
@@ -155,6 +171,9 @@ impl Constraint {
             if ddb_response.changed() {
                 re_edited = true;
             };
+            if let Some(hint) = self.constraint_type.value_hint() {
+                let _response = ddb_response.on_hover_text(hint);
+            }
 
             // Buttons
             let first_item = constraint_index == 0;
@@ -202,21 +221,42 @@ pub enum ConstraintType {
     PlayerPoints,
     PlayerRank,
     PlayerTowns,
+    PlayerAttackPoints,
+    PlayerDefencePoints,
+    PlayerKillRank,
     AllianceName,
     AlliancePoints,
     AllianceTowns,
     AllianceMembers,
     AllianceRank,
+    AllianceAttackPoints,
+    AllianceDefencePoints,
+    AllianceKillRank,
     TownID,
     TownName,
     TownPoints,
     IslandID,
     IslandX,
     IslandY,
+    Ocean,
     IslandType,
     IslandTowns,
     IslandResMore,
     IslandResLess,
+    ConqueredWithinHours,
+    ConqueredByPlayer,
+    ConqueredByAlliance,
+    LostByPlayer,
+    LostByAlliance,
+    PlayerPointsGained,
+    TownOwnerChangedSince,
+    TownPointsUnchangedDays,
+    PlayerInactiveDays,
+    DistanceToTown,
+    DistanceToPoint,
+    DistanceToSelection,
+    TravelTimeToTown,
+    InArea,
 }
 
 impl ToString for ConstraintType {
@@ -227,21 +267,110 @@ impl ToString for ConstraintType {
             ConstraintType::PlayerPoints => t!("selection.constraint.player_points"),
             ConstraintType::PlayerRank => t!("selection.constraint.player_rank"),
             ConstraintType::PlayerTowns => t!("selection.constraint.player_towns"),
+            ConstraintType::PlayerAttackPoints => t!("selection.constraint.player_attack_points"),
+            ConstraintType::PlayerDefencePoints => {
+                t!("selection.constraint.player_defence_points")
+            }
+            ConstraintType::PlayerKillRank => t!("selection.constraint.player_kill_rank"),
             ConstraintType::AllianceName => t!("selection.constraint.alliance_name"),
             ConstraintType::AlliancePoints => t!("selection.constraint.alliance_points"),
             ConstraintType::AllianceTowns => t!("selection.constraint.alliance_towns"),
             ConstraintType::AllianceMembers => t!("selection.constraint.alliance_members"),
             ConstraintType::AllianceRank => t!("selection.constraint.alliance_rank"),
+            ConstraintType::AllianceAttackPoints => {
+                t!("selection.constraint.alliance_attack_points")
+            }
+            ConstraintType::AllianceDefencePoints => {
+                t!("selection.constraint.alliance_defence_points")
+            }
+            ConstraintType::AllianceKillRank => t!("selection.constraint.alliance_kill_rank"),
             ConstraintType::TownID => t!("selection.constraint.town_id"),
             ConstraintType::TownName => t!("selection.constraint.town_name"),
             ConstraintType::TownPoints => t!("selection.constraint.town_points"),
             ConstraintType::IslandID => t!("selection.constraint.island_id"),
             ConstraintType::IslandX => t!("selection.constraint.island_x"),
             ConstraintType::IslandY => t!("selection.constraint.island_y"),
+            ConstraintType::Ocean => t!("selection.constraint.ocean"),
             ConstraintType::IslandType => t!("selection.constraint.island_type"),
             ConstraintType::IslandTowns => t!("selection.constraint.island_towns"),
             ConstraintType::IslandResMore => t!("selection.constraint.island_resmore"),
             ConstraintType::IslandResLess => t!("selection.constraint.island_resless"),
+            ConstraintType::ConqueredWithinHours => {
+                t!("selection.constraint.conquered_within_hours")
+            }
+            ConstraintType::ConqueredByPlayer => t!("selection.constraint.conquered_by_player"),
+            ConstraintType::ConqueredByAlliance => {
+                t!("selection.constraint.conquered_by_alliance")
+            }
+            ConstraintType::LostByPlayer => t!("selection.constraint.lost_by_player"),
+            ConstraintType::LostByAlliance => t!("selection.constraint.lost_by_alliance"),
+            ConstraintType::PlayerPointsGained => t!("selection.constraint.player_points_gained"),
+            ConstraintType::TownOwnerChangedSince => {
+                t!("selection.constraint.town_owner_changed_since")
+            }
+            ConstraintType::TownPointsUnchangedDays => {
+                t!("selection.constraint.town_points_unchanged_days")
+            }
+            ConstraintType::PlayerInactiveDays => t!("selection.constraint.player_inactive_days"),
+            ConstraintType::DistanceToTown => t!("selection.constraint.distance_to_town"),
+            ConstraintType::DistanceToPoint => t!("selection.constraint.distance_to_point"),
+            ConstraintType::DistanceToSelection => {
+                t!("selection.constraint.distance_to_selection")
+            }
+            ConstraintType::TravelTimeToTown => t!("selection.constraint.travel_time_to_town"),
+            ConstraintType::InArea => t!("selection.constraint.in_area"),
+        }
+    }
+}
+
+impl ConstraintType {
+    /// explains the expected input, for constraint types where that is not obvious
+    pub fn value_hint(self) -> Option<String> {
+        match self {
+            ConstraintType::DistanceToTown => Some(t!("selection.hint.distance_to_town")),
+            ConstraintType::DistanceToPoint => Some(t!("selection.hint.distance_to_point")),
+            ConstraintType::DistanceToSelection => Some(t!("selection.hint.distance_to_selection")),
+            ConstraintType::TravelTimeToTown => Some(t!("selection.hint.travel_time_to_town")),
+            ConstraintType::InArea => Some(t!("selection.hint.in_area")),
+            ConstraintType::PlayerPointsGained => Some(t!("selection.hint.player_points_gained")),
+            ConstraintType::TownOwnerChangedSince => {
+                Some(t!("selection.hint.town_owner_changed_since"))
+            }
+            ConstraintType::TownPointsUnchangedDays => {
+                Some(t!("selection.hint.town_points_unchanged_days"))
+            }
+            ConstraintType::PlayerInactiveDays => Some(t!("selection.hint.player_inactive_days")),
+            _ => None,
+        }
+    }
+
+    /// Can the constraint be used with `comparator`? Constraints that only tell whether a town
+    /// has some property do not know `<=` and `>=`.
+    pub fn allows_comparator(self, comparator: Comparator) -> bool {
+        match self {
+            ConstraintType::InArea | ConstraintType::TownOwnerChangedSince => {
+                comparator != Comparator::LessThan && comparator != Comparator::GreaterThan
+            }
+            _ => true,
+        }
+    }
+
+    /// does the constraint compare with the saved snapshots of the world?
+    pub fn uses_history(self) -> bool {
+        matches!(
+            self,
+            ConstraintType::PlayerPointsGained
+                | ConstraintType::TownOwnerChangedSince
+                | ConstraintType::TownPointsUnchangedDays
+                | ConstraintType::PlayerInactiveDays
+        )
+    }
+
+    /// how the drop down lists the values, for constraint types where the value is hard to read
+    pub fn drop_down_label(self) -> Option<fn(&str) -> String> {
+        match self {
+            ConstraintType::TownOwnerChangedSince => Some(history::time_label),
+            _ => None,
         }
     }
 }
@@ -295,6 +424,14 @@ pub enum ConstraintTypeType {
     Number,
     IslandRessource,
     Selection,
+    /// a radius (or travel time) followed by what it is measured from, e.g. `15 512|473`
+    Distance,
+    /// the corners of a polygon, e.g. `500|500 510|500 510|510`
+    Area,
+    /// an amount followed by a number of days, e.g. `100 7`
+    Period,
+    /// the date of a saved snapshot, as listed in the drop down
+    Snapshot,
 }
 
 impl From<&EmptyConstraint> for ConstraintTypeType {
@@ -312,21 +449,46 @@ impl From<&EmptyConstraint> for ConstraintTypeType {
                 | ConstraintType::IslandID
                 | ConstraintType::IslandX
                 | ConstraintType::IslandY
+                | ConstraintType::Ocean
                 | ConstraintType::IslandType
                 | ConstraintType::IslandTowns
                 | ConstraintType::AllianceTowns
                 | ConstraintType::AllianceMembers
                 | ConstraintType::AllianceRank
                 | ConstraintType::TownID
-                | ConstraintType::PlayerPoints => Self::Number,
+                | ConstraintType::PlayerPoints
+                | ConstraintType::PlayerAttackPoints
+                | ConstraintType::PlayerDefencePoints
+                | ConstraintType::PlayerKillRank
+                | ConstraintType::AllianceAttackPoints
+                | ConstraintType::AllianceDefencePoints
+                | ConstraintType::AllianceKillRank
+                | ConstraintType::ConqueredWithinHours
+                | ConstraintType::TownPointsUnchangedDays
+                | ConstraintType::PlayerInactiveDays => Self::Number,
 
                 ConstraintType::AllianceName
                 | ConstraintType::TownName
-                | ConstraintType::PlayerName => Self::StringLike,
+                | ConstraintType::PlayerName
+                | ConstraintType::ConqueredByPlayer
+                | ConstraintType::ConqueredByAlliance
+                | ConstraintType::LostByPlayer
+                | ConstraintType::LostByAlliance => Self::StringLike,
 
                 ConstraintType::IslandResMore | ConstraintType::IslandResLess => {
                     Self::IslandRessource
                 }
+
+                ConstraintType::DistanceToTown
+                | ConstraintType::DistanceToPoint
+                | ConstraintType::DistanceToSelection
+                | ConstraintType::TravelTimeToTown => Self::Distance,
+
+                ConstraintType::InArea => Self::Area,
+
+                ConstraintType::PlayerPointsGained => Self::Period,
+
+                ConstraintType::TownOwnerChangedSince => Self::Snapshot,
             },
             Comparator::InSelection | Comparator::NotInSelection => return Self::Selection,
         }
@@ -1,10 +1,26 @@
 use crate::{
     constraint::{Comparator, Constraint, ConstraintType, ConstraintTypeType},
     emptyselection::EmptyTownSelection,
-    model::database::{self, BackendTown},
+    model::database::{self, BackendTown, Conquest, DataTable},
+    model::history,
     selection::AndOr,
+    spatial,
 };
-use std::{collections::HashSet, fmt, hash::Hash, rc::Rc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    hash::Hash,
+    sync::Arc,
+};
+
+/// what the distance constraints measure from
+#[derive(Debug, Clone, PartialEq)]
+pub enum DistanceReference {
+    Town(u32),
+    Point(f32, f32),
+    /// the closest town of that selection
+    Selection(String),
+}
 
 #[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct EmptyConstraint {
@@ -40,20 +56,87 @@ impl EmptyConstraint {
             Comparator::LessThan
             | Comparator::Equal
             | Comparator::GreaterThan
-            | Comparator::NotEqual => None,
+            | Comparator::NotEqual => match self.distance_value() {
+                Some((_radius, DistanceReference::Selection(name))) => Some(name),
+                _ => None,
+            },
             Comparator::InSelection | Comparator::NotInSelection => Some(self.value.clone()),
         }
     }
 
+    /// Split the value of a distance constraint into the radius and the reference, e.g. `15 12345`
+    /// for town 12345, `15 512|473` for a point or `15 Enemies` for the selection named Enemies.
+    /// None if the value does not have this form or the constraint is no distance constraint.
+    pub fn distance_value(&self) -> Option<(f64, DistanceReference)> {
+        let (radius, reference) = self.value.trim().split_once(' ')?;
+        let radius = radius.parse::<f64>().ok()?;
+        let reference = reference.trim();
+        let re = match self.constraint_type {
+            ConstraintType::DistanceToTown | ConstraintType::TravelTimeToTown => {
+                DistanceReference::Town(reference.parse().ok()?)
+            }
+            ConstraintType::DistanceToPoint => {
+                let (x, y) = reference.split_once('|')?;
+                DistanceReference::Point(x.trim().parse().ok()?, y.trim().parse().ok()?)
+            }
+            ConstraintType::DistanceToSelection => {
+                DistanceReference::Selection(reference.to_string())
+            }
+            _ => return None,
+        };
+        return Some((radius, re));
+    }
+
+    /// a constraint for all towns inside the polygon with the given corners (in world coordinates)
+    pub fn in_area(corners: &[egui::Pos2]) -> Self {
+        let value = corners
+            .iter()
+            .map(|corner| format!("{:.1}|{:.1}", corner.x, corner.y))
+            .collect::<Vec<String>>()
+            .join(" ");
+        Self {
+            constraint_type: ConstraintType::InArea,
+            comparator: Comparator::Equal,
+            value,
+        }
+    }
+
+    /// Parse the corners of an area constraint, e.g. `500|500 510|500 510|510`. None if the value
+    /// does not have this form, has less than three corners or the constraint is no area constraint.
+    pub fn area_value(&self) -> Option<Vec<egui::Pos2>> {
+        if self.constraint_type != ConstraintType::InArea {
+            return None;
+        }
+        let mut corners = Vec::new();
+        for corner in self.value.split_whitespace() {
+            let (x, y) = corner.split_once('|')?;
+            corners.push(egui::pos2(x.parse().ok()?, y.parse().ok()?));
+        }
+        return (corners.len() >= 3).then_some(corners);
+    }
+
+    /// Split the value of a constraint that looks back in time into the amount and the number of
+    /// days, e.g. `100 7`. None if the value does not have this form.
+    pub fn period_value(&self) -> Option<(f64, f64)> {
+        let (amount, days) = self.value.trim().split_once(' ')?;
+        let amount = amount.parse::<f64>().ok()?;
+        let days = days.trim().parse::<f64>().ok()?;
+        return (days >= 0.0).then_some((amount, days));
+    }
+
     /// checks if the constraint has input that can be considered "valid". That means that number
     ///constraints can parse their userinput as numbers, in/notin constraints have input that is a
     ///name of another selection and for ressource constraints the strings match exactly to one of
     ///the options (ignoring case). For other string like constraints we always return true.
     pub fn has_valid_input(&self, all_selections: &[EmptyTownSelection]) -> bool {
         // TODO: do this check in the frontend and highlight invalid input
+        if !self.constraint_type.allows_comparator(self.comparator) {
+            return false;
+        }
         let constraint_type_type: ConstraintTypeType = self.into();
         match constraint_type_type {
             ConstraintTypeType::StringLike => !self.value.is_empty(),
+            ConstraintTypeType::Snapshot => history::parse_time(&self.value).is_some(),
             ConstraintTypeType::Number => self.value.parse::<f64>().is_ok(),
             ConstraintTypeType::IslandRessource => {
                 matches!(
@@ -62,6 +145,15 @@ impl EmptyConstraint {
                 )
             }
             ConstraintTypeType::Selection => all_selections.iter().any(|s| s.name == self.value),
+            ConstraintTypeType::Distance => match self.distance_value() {
+                Some((_radius, DistanceReference::Selection(name))) => {
+                    all_selections.iter().any(|s| s.name == name)
+                }
+                Some(_) => true,
+                None => false,
+            },
+            ConstraintTypeType::Area => self.area_value().is_some(),
+            ConstraintTypeType::Period => self.period_value().is_some(),
         }
     }
 
@@ -69,7 +161,8 @@ impl EmptyConstraint {
     #[allow(clippy::too_many_lines)]
     pub fn matching_towns(
         &self,
-        towns: &mut HashSet<Rc<BackendTown>>,
+        db: &DataTable,
+        towns: &mut HashSet<Arc<BackendTown>>,
         all_selections: &[EmptyTownSelection],
         join_mode: AndOr, // NOTE: this could be dropped, since database.rs ensures this method is never called for constraints that do not have a valid input.
     ) {
@@ -92,8 +185,16 @@ impl EmptyConstraint {
             ConstraintTypeType::Number => {
                         self.value.parse().expect("we ran EmptyConstraint::has_valid_input just before this. So unwrap _must_ be fine here!")
             },
+            ConstraintTypeType::Distance => {
+                self.distance_value().expect("we ran EmptyConstraint::has_valid_input just before this. So unwrap _must_ be fine here!").0
+            },
+            ConstraintTypeType::Period => {
+                self.period_value().expect("we ran EmptyConstraint::has_valid_input just before this. So unwrap _must_ be fine here!").0
+            },
             ConstraintTypeType::StringLike |
             ConstraintTypeType::IslandRessource |
+            ConstraintTypeType::Area |
+            ConstraintTypeType::Snapshot |
             ConstraintTypeType::Selection => {
                 0f64 // should not matter at all
             }
@@ -154,6 +255,38 @@ impl EmptyConstraint {
                         }
                     });
                 }
+                ConstraintType::PlayerAttackPoints => {
+                    towns.retain(|t| {
+                        if let Some(kills) = t.player.as_ref().map(|(_id, player)| player.kills) {
+                            // players that are not part of the ranking have no points
+                            let points = kills.attack.map_or(0, |score| score.points);
+                            self.comparator.compare(f64::from(points), value_f64)
+                        } else {
+                            false
+                        }
+                    });
+                }
+                ConstraintType::PlayerDefencePoints => {
+                    towns.retain(|t| {
+                        if let Some(kills) = t.player.as_ref().map(|(_id, player)| player.kills) {
+                            let points = kills.defence.map_or(0, |score| score.points);
+                            self.comparator.compare(f64::from(points), value_f64)
+                        } else {
+                            false
+                        }
+                    });
+                }
+                ConstraintType::PlayerKillRank => {
+                    towns.retain(|t| {
+                        if let Some(score) =
+                            t.player.as_ref().and_then(|(_id, player)| player.kills.all)
+                        {
+                            self.comparator.compare(f64::from(score.rank), value_f64)
+                        } else {
+                            false
+                        }
+                    });
+                }
                 ConstraintType::AllianceName => {
                     towns.retain(|t| {
                         if let Some(name) = t
@@ -224,6 +357,50 @@ impl EmptyConstraint {
                         }
                     });
                 }
+                ConstraintType::AllianceAttackPoints => {
+                    towns.retain(|t| {
+                        if let Some(kills) = t
+                            .player
+                            .as_ref()
+                            .and_then(|(_id, player)| player.alliance.clone())
+                            .map(|(_id, alliance)| alliance.kills)
+                        {
+                            let points = kills.attack.map_or(0, |score| score.points);
+                            self.comparator.compare(f64::from(points), value_f64)
+                        } else {
+                            false
+                        }
+                    });
+                }
+                ConstraintType::AllianceDefencePoints => {
+                    towns.retain(|t| {
+                        if let Some(kills) = t
+                            .player
+                            .as_ref()
+                            .and_then(|(_id, player)| player.alliance.clone())
+                            .map(|(_id, alliance)| alliance.kills)
+                        {
+                            let points = kills.defence.map_or(0, |score| score.points);
+                            self.comparator.compare(f64::from(points), value_f64)
+                        } else {
+                            false
+                        }
+                    });
+                }
+                ConstraintType::AllianceKillRank => {
+                    towns.retain(|t| {
+                        if let Some(score) = t
+                            .player
+                            .as_ref()
+                            .and_then(|(_id, player)| player.alliance.clone())
+                            .and_then(|(_id, alliance)| alliance.kills.all)
+                        {
+                            self.comparator.compare(f64::from(score.rank), value_f64)
+                        } else {
+                            false
+                        }
+                    });
+                }
                 ConstraintType::TownID => {
                     towns.retain(|t| self.comparator.compare(f64::from(t.id), value_f64));
                 }
@@ -235,45 +412,185 @@ impl EmptyConstraint {
                 }
                 ConstraintType::IslandID => {
                     towns.retain(|t| {
-                        let (_x, _y, island) = &t.island;
-                        self.comparator.compare(f64::from(island.id), value_f64)
+                        let (_x, _y, opt_island) = &t.island;
+                        opt_island.as_ref().is_some_and(|island| {
+                            self.comparator.compare(f64::from(island.id), value_f64)
+                        })
                     });
                 }
                 ConstraintType::IslandX => {
                     towns.retain(|t| {
                         let (x, _y, _island) = &t.island;
-                        self.comparator.compare(f64::from(*x), value_f64)
+                        db.position_is_usable(t)
+                            && self.comparator.compare(f64::from(*x), value_f64)
                     });
                 }
                 ConstraintType::IslandY => {
                     towns.retain(|t| {
                         let (_x, y, _island) = &t.island;
-                        self.comparator.compare(f64::from(*y), value_f64)
+                        db.position_is_usable(t)
+                            && self.comparator.compare(f64::from(*y), value_f64)
+                    });
+                }
+                ConstraintType::Ocean => {
+                    towns.retain(|t| {
+                        db.position_is_usable(t)
+                            && self.comparator.compare(f64::from(t.ocean), value_f64)
                     });
                 }
                 ConstraintType::IslandType => {
                     towns.retain(|t| {
-                        let (_x, _y, island) = &t.island;
-                        self.comparator.compare(f64::from(island.typ), value_f64)
+                        let (_x, _y, opt_island) = &t.island;
+                        opt_island.as_ref().is_some_and(|island| {
+                            self.comparator.compare(f64::from(island.typ), value_f64)
+                        })
                     });
                 }
                 ConstraintType::IslandTowns => {
                     towns.retain(|t| {
-                        let (_x, _y, island) = &t.island;
-                        self.comparator.compare(f64::from(island.towns), value_f64)
+                        let (_x, _y, opt_island) = &t.island;
+                        opt_island.as_ref().is_some_and(|island| {
+                            self.comparator.compare(f64::from(island.towns), value_f64)
+                        })
                     });
                 }
                 ConstraintType::IslandResMore => {
                     towns.retain(|t| {
-                        let (_x, _y, island) = &t.island;
-                        self.comparator.compare(&island.ressource_plus, &self.value)
+                        let (_x, _y, opt_island) = &t.island;
+                        opt_island.as_ref().is_some_and(|island| {
+                            self.comparator.compare(&island.ressource_plus, &self.value)
+                        })
                     });
                 }
                 ConstraintType::IslandResLess => {
                     towns.retain(|t| {
-                        let (_x, _y, island) = &t.island;
-                        self.comparator
-                            .compare(&island.ressource_minus, &self.value)
+                        let (_x, _y, opt_island) = &t.island;
+                        opt_island.as_ref().is_some_and(|island| {
+                            self.comparator
+                                .compare(&island.ressource_minus, &self.value)
+                        })
+                    });
+                }
+                ConstraintType::ConqueredWithinHours => {
+                    towns.retain(|t| {
+                        if let Some(hours) = t.hours_since_last_conquest(db.reference_time) {
+                            #[allow(clippy::cast_precision_loss)] // hours will never be that large
+                            self.comparator.compare(hours as f64, value_f64)
+                        } else {
+                            false
+                        }
+                    });
+                }
+                ConstraintType::ConqueredByPlayer => {
+                    towns.retain(|t| {
+                        self.any_conquest_matches(&t.conquests, |c| {
+                            c.new_player.as_ref().map(|(_id, player)| &player.name)
+                        })
+                    });
+                }
+                ConstraintType::ConqueredByAlliance => {
+                    towns.retain(|t| {
+                        self.any_conquest_matches(&t.conquests, |c| {
+                            c.new_alliance
+                                .as_ref()
+                                .map(|(_id, alliance)| &alliance.name)
+                        })
+                    });
+                }
+                ConstraintType::LostByPlayer => {
+                    towns.retain(|t| {
+                        self.any_conquest_matches(&t.conquests, |c| {
+                            c.old_player.as_ref().map(|(_id, player)| &player.name)
+                        })
+                    });
+                }
+                ConstraintType::LostByAlliance => {
+                    towns.retain(|t| {
+                        self.any_conquest_matches(&t.conquests, |c| {
+                            c.old_alliance
+                                .as_ref()
+                                .map(|(_id, alliance)| &alliance.name)
+                        })
+                    });
+                }
+                ConstraintType::PlayerPointsGained => {
+                    let (_amount, days) = self.period_value().expect("we ran EmptyConstraint::has_valid_input just before this. So unwrap _must_ be fine here!");
+                    // without a snapshot that old we can not tell how many points were gained
+                    let Some(snapshot) = db.history.days_before(db.reference_time, days) else {
+                        towns.clear();
+                        return;
+                    };
+                    towns.retain(|t| {
+                        let Some((id, player)) = t.player.as_ref() else {
+                            return false;
+                        };
+                        snapshot
+                            .players
+                            .get(id)
+                            .is_some_and(|(old_points, _towns)| {
+                                let gained = f64::from(player.points) - f64::from(*old_points);
+                                self.comparator.compare(gained, value_f64)
+                            })
+                    });
+                }
+                ConstraintType::TownOwnerChangedSince => {
+                    let time = history::parse_time(&self.value).expect("we ran EmptyConstraint::has_valid_input just before this. So unwrap _must_ be fine here!");
+                    let Some(snapshot) = db.history.at_or_before(time) else {
+                        towns.clear();
+                        return;
+                    };
+                    // `=` matches the towns with another owner than back then, `!=` the ones with
+                    // the same owner. Towns that did not exist back then never match.
+                    towns.retain(|t| {
+                        snapshot
+                            .towns
+                            .get(&t.id)
+                            .is_some_and(|(old_owner, _points)| {
+                                let owner = t.player.as_ref().map(|(id, _player)| *id);
+                                self.comparator.compare(owner != *old_owner, true)
+                            })
+                    });
+                }
+                ConstraintType::TownPointsUnchangedDays => {
+                    towns.retain(|t| {
+                        let days = t.days_without_point_change(&db.history, db.reference_time);
+                        self.comparator.compare(days, value_f64)
+                    });
+                }
+                ConstraintType::PlayerInactiveDays => {
+                    let inactivity = db.inactivity_by_player();
+                    towns.retain(|t| {
+                        t.player
+                            .as_ref()
+                            .and_then(|(id, _player)| inactivity.get(id))
+                            .is_some_and(|inactivity| {
+                                self.comparator.compare(inactivity.days, value_f64)
+                            })
+                    });
+                }
+                ConstraintType::DistanceToTown
+                | ConstraintType::DistanceToPoint
+                | ConstraintType::DistanceToSelection => {
+                    #[allow(clippy::cast_possible_truncation)] // the world is 1000 fields wide
+                    self.retain_by_distance(db, towns, all_selections, value_f64 as f32);
+                }
+                ConstraintType::TravelTimeToTown => {
+                    // the value is given in minutes, the travel time grows with the distance
+                    #[allow(clippy::cast_possible_truncation)] // minutes easily fit into an f32
+                    let seconds = value_f64 as f32 * 60.0;
+                    let Some(max_distance) = db.travel.travel_distance(seconds) else {
+                        towns.clear();
+                        return;
+                    };
+                    self.retain_by_distance(db, towns, all_selections, max_distance);
+                }
+                ConstraintType::InArea => {
+                    let corners = self.area_value().expect("we ran EmptyConstraint::has_valid_input just before this. So unwrap _must_ be fine here!");
+                    // `=` matches the towns inside the area, `!=` the ones outside of it
+                    towns.retain(|t| {
+                        let inside =
+                            spatial::polygon_contains(&corners, egui::pos2(t.actual_x, t.actual_y));
+                        db.position_is_usable(t) && self.comparator.compare(inside, true)
                     });
                 }
             },
@@ -281,20 +598,105 @@ impl EmptyConstraint {
                 let opt_selection = all_selections.iter().find(|s| s.name == self.value);
                 let selection = opt_selection.expect("we ran EmptyConstraint::has_valid_input just before this. So unwrap _must_ be fine here!");
                 let towns_in_referenced_selection =
-                    database::matching_towns_for_selection(towns, selection, all_selections);
+                    database::matching_towns_for_selection(db, towns, selection, all_selections);
                 towns.retain(|t| towns_in_referenced_selection.contains(t));
             }
             Comparator::NotInSelection => {
                 let opt_selection = all_selections.iter().find(|s| s.name == self.value);
                 let selection = opt_selection.expect("we ran EmptyConstraint::has_valid_input just before this. So unwrap _must_ be fine here!");
                 let towns_in_referenced_selection =
-                    database::matching_towns_for_selection(towns, selection, all_selections);
+                    database::matching_towns_for_selection(db, towns, selection, all_selections);
                 towns.retain(|t| !towns_in_referenced_selection.contains(t));
             }
         };
     }
 }
 
+impl EmptyConstraint {
+    /// Keep the towns whose distance to the reference of this constraint compares to
+    /// `max_distance`. If the reference is a selection, the distance to the closest town of that
+    /// selection counts.
+    fn retain_by_distance(
+        &self,
+        db: &DataTable,
+        towns: &mut HashSet<Arc<BackendTown>>,
+        all_selections: &[EmptyTownSelection],
+        max_distance: f32,
+    ) {
+        let reference_points: Vec<egui::Pos2> = match self.distance_value() {
+            Some((_radius, DistanceReference::Town(id))) => db
+                .towns
+                .iter()
+                .filter(|t| t.id == id && db.position_is_usable(t))
+                .map(|t| egui::pos2(t.actual_x, t.actual_y))
+                .collect(),
+            Some((_radius, DistanceReference::Point(x, y))) => vec![egui::pos2(x, y)],
+            Some((_radius, DistanceReference::Selection(name))) => {
+                let opt_selection = all_selections.iter().find(|s| s.name == name);
+                let selection = opt_selection.expect("we ran EmptyConstraint::has_valid_input just before this. So unwrap _must_ be fine here!");
+                database::matching_towns_for_selection(
+                    db,
+                    &HashSet::from_iter(db.towns.clone()),
+                    selection,
+                    all_selections,
+                )
+                .iter()
+                .filter(|t| db.position_is_usable(t))
+                .map(|t| egui::pos2(t.actual_x, t.actual_y))
+                .collect()
+            }
+            None => Vec::new(),
+        };
+
+        // without a reference there is nothing to be close to or far away from
+        if reference_points.is_empty() {
+            towns.clear();
+            return;
+        }
+
+        // Only measure the towns within `max_distance` of a reference point, the index of all
+        // towns finds them without looking at every town. Everything else is farther away.
+        let mut closest: HashMap<u32, f32> = HashMap::new();
+        for point in reference_points {
+            let around = egui::Rect::from_center_size(point, egui::Vec2::splat(2.0 * max_distance));
+            for index in db.index.in_rect(around) {
+                let town = &db.towns[index];
+                let distance = egui::pos2(town.actual_x, town.actual_y).distance(point);
+                if distance <= max_distance {
+                    closest
+                        .entry(town.id)
+                        .and_modify(|closest| *closest = closest.min(distance))
+                        .or_insert(distance);
+                }
+            }
+        }
+        towns.retain(|t| {
+            let distance = closest.get(&t.id).copied().unwrap_or(f32::INFINITY);
+            db.position_is_usable(t) && self.comparator.compare(distance, max_distance)
+        });
+    }
+
+    /// A town has a whole list of conquests, so the conquest constraints match if any one of them
+    /// matches. `NotEqual` is the exception: it matches towns that were conquered, but where none
+    /// of the conquests involved the given name.
+    fn any_conquest_matches<'a, F>(&self, conquests: &'a [Arc<Conquest>], name_of: F) -> bool
+    where
+        F: Fn(&'a Conquest) -> Option<&'a String>,
+    {
+        if self.comparator == Comparator::NotEqual {
+            return !conquests.is_empty()
+                && !conquests
+                    .iter()
+                    .filter_map(|c| name_of(c))
+                    .any(|name| name == &self.value);
+        }
+        conquests
+            .iter()
+            .filter_map(|c| name_of(c))
+            .any(|name| self.comparator.compare(name, &self.value))
+    }
+}
+
 impl Default for EmptyConstraint {
     fn default() -> Self {
         Self {
@@ -445,3 +847,433 @@ pub mod short_serialization {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::model::database::{fixtures, Player};
+    use crate::model::history::{History, Snapshot};
+
+    const DAY: i64 = 86400;
+
+    fn constraint(constraint_type: ConstraintType, value: &str) -> EmptyConstraint {
+        EmptyConstraint {
+            constraint_type,
+            comparator: Comparator::LessThan,
+            value: value.to_string(),
+        }
+    }
+
+    fn town(id: u32, x: f32, y: f32) -> Arc<BackendTown> {
+        Arc::new(BackendTown {
+            actual_x: x,
+            actual_y: y,
+            ..fixtures::town(id)
+        })
+    }
+
+    fn table(towns: Vec<Arc<BackendTown>>) -> DataTable {
+        fixtures::table(towns, Vec::new())
+    }
+
+    fn player(id: u32, points: u32) -> Arc<Player> {
+        Arc::new(Player {
+            points,
+            ..fixtures::player(id)
+        })
+    }
+
+    fn owned_town(id: u32, owner: Option<&Arc<Player>>, points: u16) -> Arc<BackendTown> {
+        Arc::new(BackendTown {
+            points,
+            player: owner.map(|player| (player.id, Arc::clone(player))),
+            ..fixtures::town(id)
+        })
+    }
+
+    /// a snapshot taken at the start of `day`, with owner and points by town id and points by
+    /// player id
+    fn snapshot(day: i64, towns: &[(u32, Option<u32>, u16)], players: &[(u32, u32)]) -> Snapshot {
+        Snapshot {
+            time: day * DAY,
+            path: std::path::PathBuf::new(),
+            towns: towns
+                .iter()
+                .map(|(id, owner, points)| (*id, (*owner, *points)))
+                .collect(),
+            players: players
+                .iter()
+                .map(|(id, points)| (*id, (*points, 1)))
+                .collect(),
+        }
+    }
+
+    /// the data of day 10 with the given saved snapshots before it
+    fn table_with_history(towns: Vec<Arc<BackendTown>>, snapshots: Vec<Snapshot>) -> DataTable {
+        let mut db = table(towns);
+        db.reference_time = 10 * DAY;
+        db.history = Arc::new(History::from_snapshots("de99", snapshots));
+        return db;
+    }
+
+    fn delta(
+        constraint_type: ConstraintType,
+        comparator: Comparator,
+        value: &str,
+    ) -> EmptyConstraint {
+        EmptyConstraint {
+            constraint_type,
+            comparator,
+            value: value.to_string(),
+        }
+    }
+
+    /// the ids of the towns that match the constraint, sorted
+    fn matching_ids(
+        db: &DataTable,
+        constraint: &EmptyConstraint,
+        all_selections: &[EmptyTownSelection],
+    ) -> Vec<u32> {
+        let mut towns = HashSet::from_iter(db.towns.clone());
+        constraint.matching_towns(db, &mut towns, all_selections, AndOr::And);
+        let mut ids: Vec<u32> = towns.iter().map(|t| t.id).collect();
+        ids.sort_unstable();
+        return ids;
+    }
+
+    /// a selection named with digits only, that contains town 3
+    fn selection_named_with_digits() -> EmptyTownSelection {
+        EmptyTownSelection {
+            name: String::from("12345"),
+            constraints: vec![EmptyConstraint {
+                constraint_type: ConstraintType::TownID,
+                comparator: Comparator::Equal,
+                value: String::from("3"),
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn distance_value_parses_town_point_and_selection() {
+        assert_eq!(
+            constraint(ConstraintType::DistanceToTown, "15 12345").distance_value(),
+            Some((15.0, DistanceReference::Town(12345)))
+        );
+        assert_eq!(
+            constraint(ConstraintType::TravelTimeToTown, " 90  12345 ").distance_value(),
+            Some((90.0, DistanceReference::Town(12345)))
+        );
+        assert_eq!(
+            constraint(ConstraintType::DistanceToPoint, "15 512|473").distance_value(),
+            Some((15.0, DistanceReference::Point(512.0, 473.0)))
+        );
+        assert_eq!(
+            constraint(ConstraintType::DistanceToSelection, "15 My Enemies").distance_value(),
+            Some((
+                15.0,
+                DistanceReference::Selection(String::from("My Enemies"))
+            ))
+        );
+    }
+
+    #[test]
+    fn distance_value_rejects_malformed_input() {
+        // a name is no town id and a town id is no point
+        assert_eq!(
+            constraint(ConstraintType::DistanceToTown, "15 Name").distance_value(),
+            None
+        );
+        assert_eq!(
+            constraint(ConstraintType::DistanceToPoint, "15 12345").distance_value(),
+            None
+        );
+        assert_eq!(
+            constraint(ConstraintType::DistanceToTown, "far 12345").distance_value(),
+            None
+        );
+        assert_eq!(
+            constraint(ConstraintType::DistanceToTown, "15").distance_value(),
+            None
+        );
+        assert_eq!(
+            constraint(ConstraintType::TownPoints, "15 12345").distance_value(),
+            None
+        );
+    }
+
+    #[test]
+    fn distance_value_of_digits_depends_on_the_constraint_type() {
+        // the same value is a town id for the town constraint and a selection name otherwise
+        assert_eq!(
+            constraint(ConstraintType::DistanceToTown, "15 12345").distance_value(),
+            Some((15.0, DistanceReference::Town(12345)))
+        );
+        assert_eq!(
+            constraint(ConstraintType::DistanceToSelection, "15 12345").distance_value(),
+            Some((15.0, DistanceReference::Selection(String::from("12345"))))
+        );
+
+        // a selection constraint is only valid if that selection exists
+        let selection = constraint(ConstraintType::DistanceToSelection, "15 12345");
+        assert!(!selection.has_valid_input(&[]));
+        assert!(selection.has_valid_input(&[selection_named_with_digits()]));
+        assert_eq!(
+            selection.referenced_selection(),
+            Some(String::from("12345"))
+        );
+        assert_eq!(
+            constraint(ConstraintType::DistanceToTown, "15 12345").referenced_selection(),
+            None
+        );
+    }
+
+    #[test]
+    fn retain_by_distance_keeps_the_towns_close_to_the_reference() {
+        let db = table(vec![
+            town(1, 500.0, 500.0),
+            town(2, 505.0, 500.0),
+            town(3, 530.0, 500.0),
+            town(12345, 560.0, 500.0),
+        ]);
+        let selections = [selection_named_with_digits()];
+
+        assert_eq!(
+            matching_ids(
+                &db,
+                &constraint(ConstraintType::DistanceToTown, "10 1"),
+                &selections
+            ),
+            vec![1, 2]
+        );
+        assert_eq!(
+            matching_ids(
+                &db,
+                &constraint(ConstraintType::DistanceToPoint, "10 531|500"),
+                &selections
+            ),
+            vec![3]
+        );
+
+        // town 12345 and the selection named 12345 (which contains town 3) are different references
+        assert_eq!(
+            matching_ids(
+                &db,
+                &constraint(ConstraintType::DistanceToTown, "10 12345"),
+                &selections
+            ),
+            vec![12345]
+        );
+        assert_eq!(
+            matching_ids(
+                &db,
+                &constraint(ConstraintType::DistanceToSelection, "10 12345"),
+                &selections
+            ),
+            vec![3]
+        );
+
+        let mut farther = constraint(ConstraintType::DistanceToTown, "10 1");
+        farther.comparator = Comparator::GreaterThan;
+        assert_eq!(matching_ids(&db, &farther, &selections), vec![3, 12345]);
+
+        // a bireme needs about 7 minutes per field, so an hour gets it about 8 fields far
+        assert_eq!(
+            matching_ids(
+                &db,
+                &constraint(ConstraintType::TravelTimeToTown, "60 1"),
+                &selections
+            ),
+            vec![1, 2]
+        );
+    }
+
+    #[test]
+    fn in_area_tells_inside_from_outside_and_knows_no_order() {
+        let db = table(vec![town(1, 500.0, 500.0), town(2, 520.0, 500.0)]);
+        let square = [
+            egui::pos2(490.0, 490.0),
+            egui::pos2(510.0, 490.0),
+            egui::pos2(510.0, 510.0),
+            egui::pos2(490.0, 510.0),
+        ];
+        let mut in_area = EmptyConstraint::in_area(&square);
+        assert_eq!(matching_ids(&db, &in_area, &[]), vec![1]);
+
+        in_area.comparator = Comparator::NotEqual;
+        assert_eq!(matching_ids(&db, &in_area, &[]), vec![2]);
+
+        for comparator in [Comparator::LessThan, Comparator::GreaterThan] {
+            in_area.comparator = comparator;
+            assert!(!in_area.has_valid_input(&[]));
+        }
+    }
+
+    #[test]
+    fn retain_by_distance_without_a_usable_reference_matches_nothing() {
+        let mut approximate = town(2, 505.0, 500.0);
+        Arc::get_mut(&mut approximate).unwrap().approximate = true;
+        let mut db = table(vec![town(1, 500.0, 500.0), approximate]);
+
+        // there is no town 999
+        assert!(matching_ids(
+            &db,
+            &constraint(ConstraintType::DistanceToTown, "10 999"),
+            &[]
+        )
+        .is_empty());
+
+        // an approximate position is neither a reference nor a match, if excluded
+        db.exclude_approximate = true;
+        assert!(matching_ids(
+            &db,
+            &constraint(ConstraintType::DistanceToTown, "10 2"),
+            &[]
+        )
+        .is_empty());
+        assert_eq!(
+            matching_ids(
+                &db,
+                &constraint(ConstraintType::DistanceToTown, "10 1"),
+                &[]
+            ),
+            vec![1]
+        );
+    }
+
+    #[test]
+    fn player_points_gained_compares_with_a_snapshot_old_enough() {
+        let (grown, same, new) = (player(1, 1000), player(2, 1000), player(3, 1000));
+        let db = table_with_history(
+            vec![
+                owned_town(1, Some(&grown), 100),
+                owned_town(2, Some(&same), 100),
+                owned_town(3, Some(&new), 100),
+                owned_town(4, None, 100),
+            ],
+            vec![snapshot(8, &[], &[(1, 900), (2, 1000)])],
+        );
+
+        // one day back is the snapshot of day 8. The player that was not in it and the ghost town
+        // never match.
+        let gained = delta(
+            ConstraintType::PlayerPointsGained,
+            Comparator::GreaterThan,
+            "50 1",
+        );
+        assert_eq!(matching_ids(&db, &gained, &[]), vec![1]);
+        let not_gained = delta(
+            ConstraintType::PlayerPointsGained,
+            Comparator::LessThan,
+            "0 2",
+        );
+        assert_eq!(matching_ids(&db, &not_gained, &[]), vec![2]);
+
+        // the history does not reach back three days
+        let too_old = delta(
+            ConstraintType::PlayerPointsGained,
+            Comparator::LessThan,
+            "0 3",
+        );
+        assert!(matching_ids(&db, &too_old, &[]).is_empty());
+    }
+
+    #[test]
+    fn town_owner_changed_since_matches_other_or_same_owner() {
+        let (old_owner, new_owner) = (player(1, 1000), player(2, 1000));
+        let db = table_with_history(
+            vec![
+                owned_town(1, Some(&new_owner), 100),
+                owned_town(2, Some(&old_owner), 100),
+                owned_town(3, None, 100),
+                // founded after the snapshot
+                owned_town(4, Some(&old_owner), 100),
+            ],
+            vec![
+                snapshot(
+                    5,
+                    &[(1, Some(1), 100), (2, Some(1), 100), (3, Some(1), 100)],
+                    &[],
+                ),
+                snapshot(
+                    9,
+                    &[(1, Some(2), 100), (2, Some(1), 100), (3, None, 100)],
+                    &[],
+                ),
+            ],
+        );
+
+        // the moment is between the snapshots, so it compares with the one of day 5
+        let since = history::format_time(7 * DAY);
+        let changed = delta(
+            ConstraintType::TownOwnerChangedSince,
+            Comparator::Equal,
+            &since,
+        );
+        assert_eq!(matching_ids(&db, &changed, &[]), vec![1, 3]);
+        let unchanged = delta(
+            ConstraintType::TownOwnerChangedSince,
+            Comparator::NotEqual,
+            &since,
+        );
+        assert_eq!(matching_ids(&db, &unchanged, &[]), vec![2]);
+
+        // the snapshot of day 9 itself
+        let since = history::format_time(9 * DAY);
+        let changed = delta(
+            ConstraintType::TownOwnerChangedSince,
+            Comparator::Equal,
+            &since,
+        );
+        assert!(matching_ids(&db, &changed, &[]).is_empty());
+
+        // there is no snapshot that old
+        let since = history::format_time(4 * DAY);
+        let unchanged = delta(
+            ConstraintType::TownOwnerChangedSince,
+            Comparator::NotEqual,
+            &since,
+        );
+        assert!(matching_ids(&db, &unchanged, &[]).is_empty());
+
+        // an owner is either another one or the same, it is not more or less
+        for comparator in [Comparator::LessThan, Comparator::GreaterThan] {
+            let ordered = delta(ConstraintType::TownOwnerChangedSince, comparator, &since);
+            assert!(!ordered.has_valid_input(&[]));
+        }
+    }
+
+    #[test]
+    fn town_points_unchanged_days_counts_back_to_the_last_change() {
+        let db = table_with_history(
+            vec![
+                owned_town(1, None, 100),
+                owned_town(2, None, 100),
+                // not in any snapshot
+                owned_town(3, None, 100),
+            ],
+            vec![
+                snapshot(7, &[(1, None, 100), (2, None, 90)], &[]),
+                snapshot(8, &[(1, None, 100), (2, None, 100)], &[]),
+            ],
+        );
+
+        let unchanged = |comparator, days| {
+            let constraint = delta(ConstraintType::TownPointsUnchangedDays, comparator, days);
+            return matching_ids(&db, &constraint, &[]);
+        };
+        assert_eq!(unchanged(Comparator::GreaterThan, "3"), vec![1]);
+        assert_eq!(unchanged(Comparator::Equal, "2"), vec![2]);
+        assert_eq!(unchanged(Comparator::LessThan, "0"), vec![3]);
+
+        // without any snapshot nothing is known to be unchanged
+        let db = table_with_history(vec![owned_town(1, None, 100)], Vec::new());
+        let constraint = delta(
+            ConstraintType::TownPointsUnchangedDays,
+            Comparator::GreaterThan,
+            "1",
+        );
+        assert!(matching_ids(&db, &constraint, &[]).is_empty());
+    }
+}
@@ -9,7 +9,7 @@ use std::path::PathBuf;
 use std::sync::Arc;
 
 use crate::emptyconstraint::EmptyConstraint;
-use crate::selection::{AndOr, TownSelection};
+use crate::selection::{AndOr, MarkerStyle, TownSelection};
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct HiddenId(String);
@@ -37,6 +37,10 @@ pub struct EmptyTownSelection {
 
     #[serde(default)]
     pub color: egui::Color32,
+
+    /// how the towns are drawn on the map, not part of the comparison or the hash
+    #[serde(default)]
+    pub marker: MarkerStyle,
 }
 
 impl Default for EmptyTownSelection {
@@ -47,6 +51,7 @@ impl Default for EmptyTownSelection {
             constraints: vec![EmptyConstraint::default()],
             constraint_join_mode: AndOr::default(),
             color: egui::Color32::GREEN,
+            marker: MarkerStyle::default(),
         }
     }
 }
@@ -120,6 +125,7 @@ impl EmptyTownSelection {
             constraints: self.constraints.iter().map(EmptyConstraint::fill).collect(),
             constraint_join_mode: self.constraint_join_mode,
             color: self.color,
+            marker: self.marker,
             towns: Arc::new(Vec::new()),
         }
     }
@@ -140,6 +146,13 @@ impl EmptyTownSelection {
             .collect()
     }
 
+    /// does any constraint compare with the saved snapshots of the world?
+    pub fn uses_history(&self) -> bool {
+        self.constraints
+            .iter()
+            .any(|constraint| constraint.constraint_type.uses_history())
+    }
+
     /// Starting from self, create the tree of selection references.
     /// If a reference cycle is detected, return an error. If not,
     /// return the list of referenced `EmptyTownSelections`.
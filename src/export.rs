@@ -0,0 +1,489 @@
+//! Convert the towns of selections into formats that other tools can read
+
+use std::sync::Arc;
+
+use rust_i18n::t;
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use crate::town::Town;
+use crate::travel::{self, TravelSettings};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, EnumIter)]
+pub enum ExportFormat {
+    Csv,
+    Json,
+}
+
+impl ExportFormat {
+    /// the usual file extension for this format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Json => "json",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "csv" => Some(ExportFormat::Csv),
+            "json" => Some(ExportFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+/// one line of the exported table
+#[derive(Serialize)]
+struct TownRow<'a> {
+    selection: &'a str,
+    id: u32,
+    name: &'a str,
+    player: &'a str,
+    alliance: &'a str,
+    x: f32,
+    y: f32,
+    points: u16,
+    island_x: u16,
+    island_y: u16,
+    island_id: Option<u32>,
+    ocean: u8,
+}
+
+impl<'a> TownRow<'a> {
+    const HEADER: [&'static str; 12] = [
+        "selection",
+        "id",
+        "name",
+        "player",
+        "alliance",
+        "x",
+        "y",
+        "points",
+        "island_x",
+        "island_y",
+        "island_id",
+        "ocean",
+    ];
+
+    fn new(selection: &'a str, town: &'a Town) -> Self {
+        Self {
+            selection,
+            id: town.id,
+            name: &town.name,
+            player: town.player_name.as_deref().unwrap_or(""),
+            alliance: town.alliance_name.as_deref().unwrap_or(""),
+            // more digits are just noise
+            x: (town.x * 1000.0).round() / 1000.0,
+            y: (town.y * 1000.0).round() / 1000.0,
+            points: town.points,
+            island_x: town.island_x,
+            island_y: town.island_y,
+            island_id: town.island_id,
+            ocean: town.ocean,
+        }
+    }
+
+    fn to_csv_fields(&self) -> Vec<String> {
+        vec![
+            self.selection.to_string(),
+            self.id.to_string(),
+            self.name.to_string(),
+            self.player.to_string(),
+            self.alliance.to_string(),
+            self.x.to_string(),
+            self.y.to_string(),
+            self.points.to_string(),
+            self.island_x.to_string(),
+            self.island_y.to_string(),
+            self.island_id.map(|id| id.to_string()).unwrap_or_default(),
+            self.ocean.to_string(),
+        ]
+    }
+}
+
+/// the towns of each selection, sorted by id so the output is the same every time
+fn rows(selections: &[(String, Arc<Vec<Town>>)]) -> Vec<TownRow<'_>> {
+    let mut re = Vec::new();
+    for (selection, towns) in selections {
+        let mut selection_rows: Vec<TownRow> = towns
+            .iter()
+            .map(|town| TownRow::new(selection, town))
+            .collect();
+        selection_rows.sort_by_key(|row| row.id);
+        re.extend(selection_rows);
+    }
+    return re;
+}
+
+/// quote a csv field if it contains anything that would break the line apart
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// all towns of the given selections, one town per line
+pub fn towns_to_csv(selections: &[(String, Arc<Vec<Town>>)]) -> String {
+    let mut lines = vec![TownRow::HEADER.join(",")];
+    for row in rows(selections) {
+        let fields: Vec<String> = row.to_csv_fields().iter().map(|f| csv_field(f)).collect();
+        lines.push(fields.join(","));
+    }
+    let mut re = lines.join("\n");
+    re.push('\n');
+    return re;
+}
+
+/// all towns of the given selections as a json list of objects
+pub fn towns_to_json(selections: &[(String, Arc<Vec<Town>>)]) -> anyhow::Result<String> {
+    let re = serde_json::to_string_pretty(&rows(selections))?;
+    return Ok(re);
+}
+
+pub fn export_towns(
+    format: ExportFormat,
+    selections: &[(String, Arc<Vec<Town>>)],
+) -> anyhow::Result<String> {
+    match format {
+        ExportFormat::Csv => Ok(towns_to_csv(selections)),
+        ExportFormat::Json => towns_to_json(selections),
+    }
+}
+
+/// which towns end up under the same heading in the `BBCode` export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumIter)]
+pub enum BBCodeGrouping {
+    #[default]
+    Player,
+    Alliance,
+}
+
+/// order of the groups and of the towns within each group in the `BBCode` export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumIter)]
+pub enum BBCodeSorting {
+    /// most points first
+    #[default]
+    Points,
+    /// closest to the reference point first
+    Distance,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct BBCodeOptions {
+    #[serde(default)]
+    pub group_by: BBCodeGrouping,
+    #[serde(default)]
+    pub sort_by: BBCodeSorting,
+}
+
+/// the towns of one player or alliance
+struct BBCodeGroup<'a> {
+    name: Option<&'a str>,
+    /// each town with its distance to the reference point
+    towns: Vec<(&'a Town, f32)>,
+}
+
+impl BBCodeGroup<'_> {
+    fn total_points(&self) -> u32 {
+        self.towns
+            .iter()
+            .map(|(town, _)| u32::from(town.points))
+            .sum()
+    }
+
+    fn min_distance(&self) -> f32 {
+        self.towns
+            .iter()
+            .map(|(_, distance)| *distance)
+            .fold(f32::INFINITY, f32::min)
+    }
+}
+
+/// The towns of a selection as `BBCode` that can be pasted into Grepolis messages and the
+/// alliance forum. The game turns the tags into links to the towns, players and alliances.
+/// `reference` is the town the distances are measured from, it is only shown and used for
+/// sorting with `BBCodeSorting::Distance`. Without it the towns are sorted by points. Next to the
+/// distances the travel times of the unit selected in `travel` are shown.
+pub fn towns_to_bbcode(
+    selection_name: &str,
+    towns: &[Town],
+    options: BBCodeOptions,
+    reference: Option<&Town>,
+    travel: &TravelSettings,
+) -> String {
+    let sort_by = if reference.is_some() {
+        options.sort_by
+    } else {
+        BBCodeSorting::Points
+    };
+    let mut groups: Vec<BBCodeGroup> = Vec::new();
+    for town in towns {
+        let name = match options.group_by {
+            BBCodeGrouping::Player => town.player_name.as_deref(),
+            BBCodeGrouping::Alliance => town.alliance_name.as_deref(),
+        };
+        let distance = reference.map_or(0.0, |reference| {
+            (town.x - reference.x).hypot(town.y - reference.y)
+        });
+        if let Some(group) = groups.iter_mut().find(|group| group.name == name) {
+            group.towns.push((town, distance));
+        } else {
+            groups.push(BBCodeGroup {
+                name,
+                towns: vec![(town, distance)],
+            });
+        }
+    }
+
+    for group in &mut groups {
+        match sort_by {
+            BBCodeSorting::Points => group
+                .towns
+                .sort_by_key(|(town, _)| (std::cmp::Reverse(town.points), town.id)),
+            BBCodeSorting::Distance => group
+                .towns
+                .sort_by(|(a, da), (b, db)| da.total_cmp(db).then(a.id.cmp(&b.id))),
+        }
+    }
+    match sort_by {
+        BBCodeSorting::Points => {
+            groups.sort_by_key(|group| (std::cmp::Reverse(group.total_points()), group.name));
+        }
+        BBCodeSorting::Distance => groups.sort_by(|a, b| {
+            a.min_distance()
+                .total_cmp(&b.min_distance())
+                .then(a.name.cmp(&b.name))
+        }),
+    }
+
+    let mut lines = vec![format!("[b]{selection_name}[/b]")];
+    if let (BBCodeSorting::Distance, Some(reference)) = (sort_by, reference) {
+        let town = format!("[town]{}[/town]", reference.id);
+        // travel times are only listed if the selected unit can travel at all
+        lines.push(match (travel.selected_unit(), travel.travel_seconds(0.0)) {
+            (Some(unit), Some(_)) => t!(
+                "menu.bbcode.distances_and_travel_times_from",
+                unit = unit.name,
+                town = town
+            ),
+            _ => t!("menu.bbcode.distances_from", town = town),
+        });
+    }
+    for group in &groups {
+        lines.push(String::new());
+        lines.push(match (options.group_by, group.name) {
+            (BBCodeGrouping::Player, Some(name)) => format!("[player]{name}[/player]"),
+            (BBCodeGrouping::Alliance, Some(name)) => format!("[ally]{name}[/ally]"),
+            (BBCodeGrouping::Player, None) => format!("[i]{}[/i]", t!("menu.bbcode.no_player")),
+            (BBCodeGrouping::Alliance, None) => {
+                format!("[i]{}[/i]", t!("menu.bbcode.no_alliance"))
+            }
+        });
+        for (town, distance) in &group.towns {
+            let mut line = format!("[town]{}[/town]", town.id);
+            // with alliance headings it is not obvious whose town it is
+            if let (BBCodeGrouping::Alliance, Some(player)) = (options.group_by, &town.player_name)
+            {
+                line = format!("{line} [player]{player}[/player]");
+            }
+            line.push(' ');
+            line.push_str(&match sort_by {
+                BBCodeSorting::Points => t!("menu.bbcode.points", points = town.points),
+                BBCodeSorting::Distance => match travel.travel_seconds(*distance) {
+                    Some(seconds) => t!(
+                        "menu.bbcode.points_distance_and_travel_time",
+                        points = town.points,
+                        distance = format!("{distance:.1}"),
+                        time = travel::format_duration(seconds)
+                    ),
+                    None => t!(
+                        "menu.bbcode.points_and_distance",
+                        points = town.points,
+                        distance = format!("{distance:.1}")
+                    ),
+                },
+            });
+            lines.push(line);
+        }
+    }
+    let mut re = lines.join("\n");
+    re.push('\n');
+    return re;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn town(id: u32, name: &str, player: Option<&str>) -> Town {
+        Town {
+            id,
+            player_id: player.map(|_| 7),
+            player_name: player.map(ToString::to_string),
+            alliance_name: None,
+            player_points: None,
+            alliance_points: None,
+            name: name.to_string(),
+            x: 512.1234,
+            y: 473.5,
+            island_x: 512,
+            island_y: 473,
+            island_id: Some(3),
+            ocean: 54,
+            slot_number: 2,
+            points: 1000,
+            approximate: false,
+        }
+    }
+
+    #[test]
+    fn csv_field_quotes_only_when_needed() {
+        assert_eq!(csv_field("plain name"), "plain name");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn towns_to_csv_sorts_by_id_and_quotes_names() {
+        let towns = Arc::new(vec![
+            town(20, "Second, \"the\" town", None),
+            town(10, "First", Some("Alice")),
+        ]);
+        let csv = towns_to_csv(&[("targets".to_string(), towns)]);
+        assert_eq!(
+            csv,
+            "selection,id,name,player,alliance,x,y,points,island_x,island_y,island_id,ocean\n\
+             targets,10,First,Alice,,512.123,473.5,1000,512,473,3,54\n\
+             targets,20,\"Second, \"\"the\"\" town\",,,512.123,473.5,1000,512,473,3,54\n"
+        );
+    }
+
+    #[test]
+    fn towns_to_json_keeps_the_selection_of_each_town() {
+        let selections = [
+            ("a".to_string(), Arc::new(vec![town(1, "x", None)])),
+            ("b".to_string(), Arc::new(vec![town(1, "x", None)])),
+        ];
+        let json: serde_json::Value =
+            serde_json::from_str(&towns_to_json(&selections).unwrap()).unwrap();
+        let rows = json.as_array().unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0]["selection"], "a");
+        assert_eq!(rows[1]["selection"], "b");
+        assert_eq!(rows[1]["player"], "");
+        assert_eq!(rows[1]["ocean"], 54);
+    }
+
+    #[test]
+    fn towns_to_bbcode_groups_by_player_with_most_points_first() {
+        let towns = [
+            Town {
+                points: 500,
+                ..town(1, "a", Some("Alice"))
+            },
+            Town {
+                points: 1500,
+                ..town(2, "b", Some("Alice"))
+            },
+            Town {
+                points: 3000,
+                ..town(3, "c", Some("Bob"))
+            },
+            Town {
+                points: 100,
+                ..town(4, "d", None)
+            },
+        ];
+        let options = BBCodeOptions::default();
+        let bbcode = towns_to_bbcode("targets", &towns, options, None, &TravelSettings::default());
+        assert_eq!(
+            bbcode,
+            "[b]targets[/b]\n\
+             \n\
+             [player]Bob[/player]\n\
+             [town]3[/town] 3000 points\n\
+             \n\
+             [player]Alice[/player]\n\
+             [town]2[/town] 1500 points\n\
+             [town]1[/town] 500 points\n\
+             \n\
+             [i]Ghost towns[/i]\n\
+             [town]4[/town] 100 points\n"
+        );
+
+        // without a town to measure from, sorting by distance falls back to points
+        let by_distance = BBCodeOptions {
+            sort_by: BBCodeSorting::Distance,
+            ..options
+        };
+        assert_eq!(
+            towns_to_bbcode(
+                "targets",
+                &towns,
+                by_distance,
+                None,
+                &TravelSettings::default()
+            ),
+            bbcode
+        );
+    }
+
+    #[test]
+    fn towns_to_bbcode_groups_by_alliance_closest_first() {
+        let in_alliance = |id, alliance: &str, x, y| Town {
+            alliance_name: Some(alliance.to_string()),
+            x,
+            y,
+            ..town(id, "t", Some("Alice"))
+        };
+        let towns = [
+            in_alliance(3, "X", 500.0, 520.0),
+            in_alliance(2, "Y", 500.0, 510.0),
+            in_alliance(1, "X", 503.0, 504.0),
+        ];
+        let reference = Town {
+            x: 500.0,
+            y: 500.0,
+            ..town(9, "home", Some("Carol"))
+        };
+        let options = BBCodeOptions {
+            group_by: BBCodeGrouping::Alliance,
+            sort_by: BBCodeSorting::Distance,
+        };
+        let unknown_unit = TravelSettings {
+            unit: String::from("unknown"),
+            ..Default::default()
+        };
+        assert_eq!(
+            towns_to_bbcode("targets", &towns, options, Some(&reference), &unknown_unit),
+            "[b]targets[/b]\n\
+             Distances from [town]9[/town]\n\
+             \n\
+             [ally]X[/ally]\n\
+             [town]1[/town] [player]Alice[/player] 1000 points, 5.0 fields away\n\
+             [town]3[/town] [player]Alice[/player] 1000 points, 20.0 fields away\n\
+             \n\
+             [ally]Y[/ally]\n\
+             [town]2[/town] [player]Alice[/player] 1000 points, 10.0 fields away\n"
+        );
+
+        // with a known unit the travel times from the reference town are listed as well
+        let bireme = TravelSettings::default();
+        assert_eq!(
+            towns_to_bbcode("targets", &towns, options, Some(&reference), &bireme),
+            "[b]targets[/b]\n\
+             Distances and travel times of a Bireme from [town]9[/town]\n\
+             \n\
+             [ally]X[/ally]\n\
+             [town]1[/town] [player]Alice[/player] 1000 points, 5.0 fields away, 0:35:33 travel time\n\
+             [town]3[/town] [player]Alice[/player] 1000 points, 20.0 fields away, 2:22:13 travel time\n\
+             \n\
+             [ally]Y[/ally]\n\
+             [town]2[/town] [player]Alice[/player] 1000 points, 10.0 fields away, 1:11:07 travel time\n"
+        );
+    }
+}
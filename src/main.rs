@@ -8,14 +8,22 @@
 extern crate rust_i18n;
 i18n!("locales", fallback = "en");
 
+#[cfg(not(target_arch = "wasm32"))]
+mod cli;
 mod constraint;
 mod emptyconstraint;
 mod emptyselection;
+mod export;
 mod model;
 mod presenter;
+#[cfg(not(target_arch = "wasm32"))]
+mod render;
 mod selection;
+mod spatial;
 mod telemetry;
+mod territory;
 mod town;
+mod travel;
 mod view;
 
 #[cfg(not(target_arch = "wasm32"))]
@@ -30,5 +38,35 @@ use view::View;
 const APP_KEY: &str = eframe::APP_KEY;
 
 fn main() {
+    // `turunmap query ...` is used from the command line, anything else starts the GUI. The OS may
+    // pass other arguments, e.g. a file that was opened with us.
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let args: Vec<String> = std::env::args().skip(1).collect();
+        if args.first().map(String::as_str) == Some("query") {
+            #[cfg(windows)]
+            attach_console();
+            std::process::exit(cli::run(&args));
+        }
+    }
+
     View::new_and_start();
 }
+
+/// We are built for the windows subsystem, so windows does not give us a console. Without one,
+/// `turunmap query` would print nothing. Write to the console we were started from instead. Output
+/// that is redirected to a file keeps going there.
+#[cfg(windows)]
+fn attach_console() {
+    const ATTACH_PARENT_PROCESS: u32 = u32::MAX;
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn AttachConsole(process_id: u32) -> i32;
+    }
+    // fails if we were not started from a console, e.g. from the explorer. Then there is nobody
+    // to print to anyway.
+    // SAFETY: AttachConsole takes no pointers and only changes the console of this process
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
@@ -1,12 +1,17 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
 use std::ops::Deref;
-use std::rc::Rc;
+use std::sync::Arc;
 
 use crate::emptyselection::EmptyTownSelection;
+use crate::model::history::{self, History, Inactivity, SECONDS_PER_DAY};
 use crate::model::ConstraintType;
 use crate::selection::AndOr;
-use crate::town::Town;
+use crate::spatial::SpatialIndex;
+use crate::town::{
+    AllianceDetails, InactivePlayer, IslandDetails, PlayerDetails, Town, TownDetails,
+};
+use crate::travel::TravelSettings;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Offset {
@@ -27,6 +32,21 @@ pub struct Island {
     pub ressource_minus: String,
 }
 
+/// rank and points in one of the kill rankings
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KillScore {
+    pub rank: u32,
+    pub points: u32,
+}
+
+/// Players and alliances only show up in a kill ranking once they have any points in it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Kills {
+    pub all: Option<KillScore>,
+    pub attack: Option<KillScore>,
+    pub defence: Option<KillScore>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Alliance {
     pub id: u32,
@@ -35,16 +55,64 @@ pub struct Alliance {
     pub towns: u32,
     pub members: u16,
     pub rank: u16,
+    pub kills: Kills,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Player {
     pub id: u32,
     pub name: String,
-    pub alliance: Option<(u32, Rc<Alliance>)>, // link player.alliance_id == alliance.id
+    pub alliance: Option<(u32, Arc<Alliance>)>, // link player.alliance_id == alliance.id
     pub points: u32,
     pub rank: u16,
     pub towns: u16,
+    pub kills: Kills,
+}
+
+impl Player {
+    /// For how long the player has not grown, as far as the saved snapshots before
+    /// `reference_time` tell. Going back one snapshot at a time, the player counts as inactive as
+    /// long as their points did not grow and their number of towns stayed the same. A conquest of
+    /// theirs ends the inactivity, even if the town was lost again in between.
+    pub fn inactivity(
+        &self,
+        history: &History,
+        last_conquest: Option<i64>,
+        reference_time: i64,
+    ) -> Inactivity {
+        let (mut points, mut towns) = (self.points, self.towns);
+        let mut since = reference_time;
+        let mut snapshots = 0;
+        for snapshot in history.before(reference_time).iter().rev() {
+            if last_conquest.is_some_and(|time| time > snapshot.time) {
+                break;
+            }
+            match snapshot.players.get(&self.id) {
+                Some((old_points, old_towns)) if *old_points >= points && *old_towns == towns => {
+                    (points, towns) = (*old_points, *old_towns);
+                    since = snapshot.time;
+                    snapshots += 1;
+                }
+                _ => break,
+            }
+        }
+        #[allow(clippy::cast_precision_loss)] // the history is never that long
+        return Inactivity {
+            days: (reference_time - since) as f64 / SECONDS_PER_DAY,
+            snapshots,
+        };
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Conquest {
+    pub town_id: u32,
+    pub time: i64,                                  // unix timestamp
+    pub new_player: Option<(u32, Arc<Player>)>,     // link conquest.new_player_id == player.id
+    pub old_player: Option<(u32, Arc<Player>)>, // NULL if the town was a ghost town or newly founded
+    pub new_alliance: Option<(u32, Arc<Alliance>)>, // link conquest.new_ally_id == alliance.id
+    pub old_alliance: Option<(u32, Arc<Alliance>)>,
+    pub town_points: u16,
 }
 
 // TODO: Merge BackendTown and Town as it is used for the frontend into one struct
@@ -54,11 +122,14 @@ pub struct BackendTown {
     pub id: u32,
     pub name: String,
     pub points: u16, // had a bug where a city actually had negative points in the game
-    pub player: Option<(u32, Rc<Player>)>, // link town.player_id == player.id
-    pub island: (u16, u16, Rc<Island>), // link town.x = island.x && town.y == island.y
-    pub offset: (u8, Rc<Offset>), // link town.slot_number = offset.slot_number && offset.type == island.type
+    pub player: Option<(u32, Arc<Player>)>, // link town.player_id == player.id
+    pub island: (u16, u16, Option<Arc<Island>>), // link town.x = island.x && town.y == island.y
+    pub ocean: u8,   // computed from the island coordinates
+    pub offset: (u8, Option<Arc<Offset>>), // link town.slot_number = offset.slot_number && offset.type == island.type
     pub actual_x: f32,
-    pub actual_y: f32, // computed from the linked island and offset
+    pub actual_y: f32,                 // computed from the linked island and offset
+    pub approximate: bool, // true if the island or offset is unknown and actual_x/y is just the centre of the island
+    pub conquests: Vec<Arc<Conquest>>, // link town.id == conquest.town_id, sorted oldest first
 }
 impl Eq for BackendTown {}
 impl PartialEq for BackendTown {
@@ -72,6 +143,30 @@ impl std::hash::Hash for BackendTown {
     }
 }
 
+impl BackendTown {
+    /// For how many days the points of the town did not change, as far as the saved snapshots
+    /// before `reference_time` tell. 0 if they changed since the last snapshot or there is none.
+    pub fn days_without_point_change(&self, history: &History, reference_time: i64) -> f64 {
+        let mut since = reference_time;
+        for snapshot in history.before(reference_time).iter().rev() {
+            match snapshot.towns.get(&self.id) {
+                Some((_owner, points)) if *points == self.points => since = snapshot.time,
+                _ => break,
+            }
+        }
+        #[allow(clippy::cast_precision_loss)] // the history is never that long
+        return (reference_time - since) as f64 / SECONDS_PER_DAY;
+    }
+
+    /// full hours between the most recent conquest of this town and `reference_time`. None if the
+    /// town was never conquered.
+    pub fn hours_since_last_conquest(&self, reference_time: i64) -> Option<i64> {
+        self.conquests
+            .last()
+            .map(|conquest| (reference_time - conquest.time) / 3600)
+    }
+}
+
 impl From<&BackendTown> for Town {
     fn from(value: &BackendTown) -> Self {
         Self {
@@ -83,20 +178,81 @@ impl From<&BackendTown> for Town {
                 .as_ref()
                 .and_then(|(_, p)| p.alliance.as_ref())
                 .map(|(_, a)| a.name.clone()),
+            player_points: value.player.as_ref().map(|(_, p)| p.points),
+            alliance_points: value
+                .player
+                .as_ref()
+                .and_then(|(_, p)| p.alliance.as_ref())
+                .map(|(_, a)| a.points),
             name: value.name.clone(),
             x: value.actual_x,
             y: value.actual_y,
-            slot_number: value.offset.1.slot_number,
+            island_x: value.island.0,
+            island_y: value.island.1,
+            island_id: value.island.2.as_ref().map(|island| island.id),
+            ocean: value.ocean,
+            slot_number: value.offset.0,
             points: value.points,
+            approximate: value.approximate,
+        }
+    }
+}
+
+impl From<&BackendTown> for TownDetails {
+    fn from(value: &BackendTown) -> Self {
+        let player = value.player.as_ref().map(|(_, p)| p);
+        Self {
+            town: value.into(),
+            island: value.island.2.as_ref().map(|island| IslandDetails {
+                typ: island.typ,
+                towns: island.towns,
+                ressource_plus: island.ressource_plus.clone(),
+                ressource_minus: island.ressource_minus.clone(),
+            }),
+            player: player.map(|p| PlayerDetails {
+                name: p.name.clone(),
+                points: p.points,
+                rank: p.rank,
+                towns: p.towns,
+            }),
+            alliance: player
+                .and_then(|p| p.alliance.as_ref())
+                .map(|(_, a)| AllianceDetails {
+                    name: a.name.clone(),
+                    points: a.points,
+                    rank: a.rank,
+                    towns: a.towns,
+                    members: a.members,
+                }),
+            conquests: value.conquests.len(),
         }
     }
 }
 
 pub struct DataTable {
-    pub towns: Vec<Rc<BackendTown>>,
+    pub towns: Vec<Arc<BackendTown>>,
+    /// every player of the world, including those without towns
+    pub players: Vec<Arc<Player>>,
+    /// positions of `towns`, in the same order. Shared with the view, where it refers to the
+    /// towns of `get_all_towns`.
+    pub index: Arc<SpatialIndex>,
+    pub conquests: Vec<Arc<Conquest>>,
+    /// unix timestamp of the moment the data was fetched. Time based constraints are relative to this.
+    pub reference_time: i64,
+    /// if true, towns with an approximate position never match coordinate based constraints
+    pub exclude_approximate: bool,
+    /// unit and speed modifiers for travel time constraints
+    pub travel: Arc<TravelSettings>,
+    /// the saved snapshots of this world, for constraints that compare with the past
+    pub history: Arc<History>,
 }
 
 impl DataTable {
+    /// may coordinate based constraints use the position of this town?
+    pub fn position_is_usable(&self, town: &BackendTown) -> bool {
+        !(self.exclude_approximate && town.approximate)
+    }
+
     pub fn get_all_towns(&self) -> Vec<Town> {
         self.towns.iter().map(|t| t.deref().into()).collect()
     }
@@ -110,8 +266,69 @@ impl DataTable {
             .collect()
     }
 
+    pub fn get_town_details(&self, town_id: u32) -> Option<TownDetails> {
+        self.towns
+            .iter()
+            .find(|t| t.id == town_id)
+            .map(|t| t.deref().into())
+    }
+
+    /// How long each player with at least one town has been inactive, by player id. See
+    /// `Player::inactivity`.
+    pub fn inactivity_by_player(&self) -> HashMap<u32, Inactivity> {
+        let mut last_conquests: HashMap<u32, i64> = HashMap::new();
+        for conquest in &self.conquests {
+            if let Some((id, _player)) = &conquest.new_player {
+                let time = last_conquests.entry(*id).or_insert(conquest.time);
+                *time = (*time).max(conquest.time);
+            }
+        }
+        return self
+            .players
+            .iter()
+            .filter(|player| player.towns > 0)
+            .map(|player| {
+                let last_conquest = last_conquests.get(&player.id).copied();
+                let inactivity =
+                    player.inactivity(&self.history, last_conquest, self.reference_time);
+                (player.id, inactivity)
+            })
+            .collect();
+    }
+
+    /// every player with at least one town and how long they have been inactive, in no
+    /// particular order
+    pub fn get_inactive_players(&self) -> Vec<InactivePlayer> {
+        let mut first_towns: HashMap<u32, &BackendTown> = HashMap::new();
+        for town in &self.towns {
+            if let Some((id, _player)) = &town.player {
+                first_towns.entry(*id).or_insert(town);
+            }
+        }
+        let inactivity = self.inactivity_by_player();
+        return self
+            .players
+            .iter()
+            .filter_map(|player| {
+                let inactivity = inactivity.get(&player.id)?;
+                Some(InactivePlayer {
+                    name: player.name.clone(),
+                    alliance_name: player
+                        .alliance
+                        .as_ref()
+                        .map(|(_, alliance)| alliance.name.clone()),
+                    points: player.points,
+                    towns: player.towns,
+                    inactive_days: inactivity.days,
+                    snapshots: inactivity.snapshots,
+                    first_town: first_towns.get(&player.id).map(|town| (*town).into()),
+                })
+            })
+            .collect();
+    }
+
     pub fn get_names_for_constraint_type(&self, constraint_type: ConstraintType) -> Vec<String> {
-        return get_names_for_constraint_type_in_town_list(&self.towns, constraint_type);
+        return get_names_for_constraint_type_in_town_list(self, &self.towns, constraint_type);
     }
 
     pub fn get_names_for_constraint_type_in_constraints(
@@ -125,7 +342,7 @@ impl DataTable {
         }
 
         let towns = self.get_backendtowns_for_constraints(selection, all_selections);
-        return get_names_for_constraint_type_in_town_list(&towns, constraint_type);
+        return get_names_for_constraint_type_in_town_list(self, &towns, constraint_type);
     }
 
     pub fn get_towns_for_constraints(
@@ -149,12 +366,13 @@ impl DataTable {
         &self,
         selection: &EmptyTownSelection,
         all_selections: &[EmptyTownSelection],
-    ) -> Vec<Rc<BackendTown>> {
+    ) -> Vec<Arc<BackendTown>> {
         if selection.constraints.is_empty() {
             return Vec::new();
         }
 
         return matching_towns_for_selection(
+            self,
             &HashSet::from_iter(self.towns.clone()),
             selection,
             all_selections,
@@ -165,10 +383,11 @@ impl DataTable {
 }
 
 pub fn matching_towns_for_selection(
-    towns: &HashSet<Rc<BackendTown>>,
+    db: &DataTable,
+    towns: &HashSet<Arc<BackendTown>>,
     selection: &EmptyTownSelection,
     all_selections: &[EmptyTownSelection],
-) -> HashSet<Rc<BackendTown>> {
+) -> HashSet<Arc<BackendTown>> {
     // short circuit useless selections.
     // useless selection in this case means that for all constraints where a value is provided by the user the input must be valid
     if !selection
@@ -196,6 +415,7 @@ pub fn matching_towns_for_selection(
             AndOr::And => {
                 // shortcut dataprocessing. AND join means that we can never reintroduce towns that were already excluded by another constraint
                 constraint.matching_towns(
+                    db,
                     &mut local_towns,
                     all_selections,
                     selection.constraint_join_mode,
@@ -206,6 +426,7 @@ pub fn matching_towns_for_selection(
                 let mut these_towns = towns.clone();
 
                 constraint.matching_towns(
+                    db,
                     &mut these_towns,
                     all_selections,
                     selection.constraint_join_mode,
@@ -221,7 +442,8 @@ pub fn matching_towns_for_selection(
 
 #[allow(clippy::too_many_lines)]
 pub fn get_names_for_constraint_type_in_town_list(
-    towns: &[Rc<BackendTown>],
+    db: &DataTable,
+    towns: &[Arc<BackendTown>],
     constraint_type: ConstraintType,
 ) -> Vec<String> {
     // This is a big chunk of the actual work the program is doing. If we want to speed it up, we could
@@ -282,6 +504,37 @@ pub fn get_names_for_constraint_type_in_town_list(
             values.dedup();
             values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
         }
+        ConstraintType::PlayerAttackPoints => {
+            let mut values = towns
+                .iter()
+                .filter_map(|t| t.player.as_ref())
+                .map(|(_id, player)| player.kills.attack.map_or(0, |score| score.points))
+                .collect::<Vec<_>>();
+            values.sort_unstable();
+            values.dedup();
+            values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
+        }
+        ConstraintType::PlayerDefencePoints => {
+            let mut values = towns
+                .iter()
+                .filter_map(|t| t.player.as_ref())
+                .map(|(_id, player)| player.kills.defence.map_or(0, |score| score.points))
+                .collect::<Vec<_>>();
+            values.sort_unstable();
+            values.dedup();
+            values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
+        }
+        ConstraintType::PlayerKillRank => {
+            let mut values = towns
+                .iter()
+                .filter_map(|t| t.player.as_ref())
+                .filter_map(|(_id, player)| player.kills.all)
+                .map(|score| score.rank)
+                .collect::<Vec<_>>();
+            values.sort_unstable();
+            values.dedup();
+            values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
+        }
         ConstraintType::AllianceName => {
             let mut values = towns
                 .iter()
@@ -337,6 +590,40 @@ pub fn get_names_for_constraint_type_in_town_list(
             values.dedup();
             values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
         }
+        ConstraintType::AllianceAttackPoints => {
+            let mut values = towns
+                .iter()
+                .filter_map(|t| t.player.as_ref())
+                .filter_map(|(_id, player)| player.alliance.as_ref())
+                .map(|(_id, ally)| ally.kills.attack.map_or(0, |score| score.points))
+                .collect::<Vec<_>>();
+            values.sort_unstable();
+            values.dedup();
+            values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
+        }
+        ConstraintType::AllianceDefencePoints => {
+            let mut values = towns
+                .iter()
+                .filter_map(|t| t.player.as_ref())
+                .filter_map(|(_id, player)| player.alliance.as_ref())
+                .map(|(_id, ally)| ally.kills.defence.map_or(0, |score| score.points))
+                .collect::<Vec<_>>();
+            values.sort_unstable();
+            values.dedup();
+            values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
+        }
+        ConstraintType::AllianceKillRank => {
+            let mut values = towns
+                .iter()
+                .filter_map(|t| t.player.as_ref())
+                .filter_map(|(_id, player)| player.alliance.as_ref())
+                .filter_map(|(_id, ally)| ally.kills.all)
+                .map(|score| score.rank)
+                .collect::<Vec<_>>();
+            values.sort_unstable();
+            values.dedup();
+            values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
+        }
         ConstraintType::TownID => {
             let mut values = towns.iter().map(|t| t.id).collect::<Vec<_>>();
             values.sort_unstable();
@@ -358,8 +645,8 @@ pub fn get_names_for_constraint_type_in_town_list(
         ConstraintType::IslandID => {
             let mut values = towns
                 .iter()
-                .map(|t| t.island.clone())
-                .map(|(_x, _y, island)| island.id)
+                .filter_map(|t| t.island.2.clone())
+                .map(|island| island.id)
                 .collect::<Vec<_>>();
             values.sort_unstable();
             values.dedup();
@@ -385,11 +672,35 @@ pub fn get_names_for_constraint_type_in_town_list(
             values.dedup();
             values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
         }
+        ConstraintType::Ocean => {
+            let mut values = towns.iter().map(|t| t.ocean).collect::<Vec<_>>();
+            values.sort_unstable();
+            values.dedup();
+            values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
+        }
+        // the reference is typed in by the user, there is nothing sensible to suggest
+        ConstraintType::DistanceToTown
+        | ConstraintType::DistanceToPoint
+        | ConstraintType::DistanceToSelection
+        | ConstraintType::TravelTimeToTown
+        | ConstraintType::InArea
+        | ConstraintType::PlayerPointsGained
+        | ConstraintType::TownPointsUnchangedDays
+        | ConstraintType::PlayerInactiveDays => Vec::new(),
+        ConstraintType::TownOwnerChangedSince => {
+            // the newest snapshot first, that is the one most likely compared with
+            db.history
+                .before(db.reference_time)
+                .iter()
+                .rev()
+                .map(|snapshot| history::format_time(snapshot.time))
+                .collect()
+        }
         ConstraintType::IslandType => {
             let mut values = towns
                 .iter()
-                .map(|t| t.island.clone())
-                .map(|(_x, _y, island)| island.typ)
+                .filter_map(|t| t.island.2.clone())
+                .map(|island| island.typ)
                 .collect::<Vec<_>>();
             values.sort_unstable();
             values.dedup();
@@ -398,8 +709,8 @@ pub fn get_names_for_constraint_type_in_town_list(
         ConstraintType::IslandTowns => {
             let mut values = towns
                 .iter()
-                .map(|t| t.island.clone())
-                .map(|(_x, _y, island)| island.towns)
+                .filter_map(|t| t.island.2.clone())
+                .map(|island| island.towns)
                 .collect::<Vec<_>>();
             values.sort_unstable();
             values.dedup();
@@ -409,8 +720,8 @@ pub fn get_names_for_constraint_type_in_town_list(
             // todo
             let mut values = towns
                 .iter()
-                .map(|t| t.island.clone())
-                .map(|(_x, _y, island)| island.ressource_plus.clone())
+                .filter_map(|t| t.island.2.clone())
+                .map(|island| island.ressource_plus.clone())
                 .collect::<Vec<_>>();
             values.sort_unstable_by_key(|s| s.to_lowercase());
             values.dedup();
@@ -420,12 +731,137 @@ pub fn get_names_for_constraint_type_in_town_list(
             // todo
             let mut values = towns
                 .iter()
-                .map(|t| t.island.clone())
-                .map(|(_x, _y, island)| island.ressource_minus.clone())
+                .filter_map(|t| t.island.2.clone())
+                .map(|island| island.ressource_minus.clone())
                 .collect::<Vec<_>>();
             values.sort_unstable_by_key(|s| s.to_lowercase());
             values.dedup();
             values
         }
+        ConstraintType::ConqueredWithinHours => {
+            let mut values = towns
+                .iter()
+                .filter_map(|t| t.hours_since_last_conquest(db.reference_time))
+                .collect::<Vec<_>>();
+            values.sort_unstable();
+            values.dedup();
+            values.iter().map(|x| format!("{x}")).collect::<Vec<_>>()
+        }
+        ConstraintType::ConqueredByPlayer => {
+            let mut values = towns
+                .iter()
+                .flat_map(|t| t.conquests.iter())
+                .filter_map(|c| c.new_player.as_ref())
+                .map(|(_id, player)| player.name.clone())
+                .collect::<Vec<_>>();
+            values.sort_unstable_by_key(|k| k.to_lowercase());
+            values.dedup();
+            values
+        }
+        ConstraintType::ConqueredByAlliance => {
+            let mut values = towns
+                .iter()
+                .flat_map(|t| t.conquests.iter())
+                .filter_map(|c| c.new_alliance.as_ref())
+                .map(|(_id, ally)| ally.name.clone())
+                .collect::<Vec<_>>();
+            values.sort_unstable_by_key(|k| k.to_lowercase());
+            values.dedup();
+            values
+        }
+        ConstraintType::LostByPlayer => {
+            let mut values = towns
+                .iter()
+                .flat_map(|t| t.conquests.iter())
+                .filter_map(|c| c.old_player.as_ref())
+                .map(|(_id, player)| player.name.clone())
+                .collect::<Vec<_>>();
+            values.sort_unstable_by_key(|k| k.to_lowercase());
+            values.dedup();
+            values
+        }
+        ConstraintType::LostByAlliance => {
+            let mut values = towns
+                .iter()
+                .flat_map(|t| t.conquests.iter())
+                .filter_map(|c| c.old_alliance.as_ref())
+                .map(|(_id, ally)| ally.name.clone())
+                .collect::<Vec<_>>();
+            values.sort_unstable_by_key(|k| k.to_lowercase());
+            values.dedup();
+            values
+        }
     };
 }
+
+/// Small worlds for tests. Each value has plain defaults, tests change what they look at with
+/// the struct update syntax, e.g. `Player { points: 500, ..fixtures::player(1) }`.
+#[cfg(test)]
+pub mod fixtures {
+    use std::sync::Arc;
+
+    use super::{Alliance, BackendTown, DataTable, Kills, Player};
+    use crate::model::history::History;
+    use crate::spatial::SpatialIndex;
+    use crate::travel::TravelSettings;
+
+    pub fn alliance(id: u32) -> Alliance {
+        Alliance {
+            id,
+            name: format!("alliance{id}"),
+            points: 1000,
+            towns: 10,
+            members: 5,
+            rank: 1,
+            kills: Kills::default(),
+        }
+    }
+
+    pub fn player(id: u32) -> Player {
+        Player {
+            id,
+            name: format!("player{id}"),
+            alliance: None,
+            points: 100,
+            rank: 1,
+            towns: 1,
+            kills: Kills::default(),
+        }
+    }
+
+    /// a town without owner in the middle of the island at 500|500
+    pub fn town(id: u32) -> BackendTown {
+        BackendTown {
+            id,
+            name: format!("town{id}"),
+            points: 1000,
+            player: None,
+            island: (500, 500, None),
+            ocean: 55,
+            offset: (0, None),
+            actual_x: 500.5,
+            actual_y: 500.5,
+            approximate: false,
+            conquests: Vec::new(),
+        }
+    }
+
+    /// the data fetched at unix time 0, with the index built from the towns
+    pub fn table(towns: Vec<Arc<BackendTown>>, players: Vec<Arc<Player>>) -> DataTable {
+        DataTable {
+            index: Arc::new(SpatialIndex::new(
+                towns
+                    .iter()
+                    .map(|town| egui::pos2(town.actual_x, town.actual_y))
+                    .collect(),
+            )),
+            towns,
+            players,
+            conquests: Vec::new(),
+            reference_time: 0,
+            exclude_approximate: false,
+            travel: Arc::new(TravelSettings::default()),
+            history: Arc::new(History::default()),
+        }
+    }
+}
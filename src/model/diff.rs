@@ -0,0 +1,392 @@
+//! What happened on a world between two snapshots of it.
+//!
+//! Towns and players are matched by their id, names may change in between. Players are only
+//! compared if they own at least one town in the older snapshot, everybody else is invisible on
+//! the map anyway.
+
+use std::collections::{HashMap, HashSet};
+use std::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
+
+use super::database::DataTable;
+use crate::town::Town;
+
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, EnumIter,
+)]
+pub enum ChangeKind {
+    /// the town belongs to someone else, or to nobody
+    OwnerChanged,
+    PointsGained,
+    PointsLost,
+    /// the town did not exist in the older snapshot
+    TownFounded,
+    /// the player owned towns in the older snapshot and is gone in the newer one
+    PlayerDeleted,
+    /// the player joined, left or changed their alliance
+    AllianceSwitched,
+}
+
+impl ToString for ChangeKind {
+    fn to_string(&self) -> String {
+        match self {
+            ChangeKind::OwnerChanged => t!("map.changes.kind.owner_changed"),
+            ChangeKind::PointsGained => t!("map.changes.kind.points_gained"),
+            ChangeKind::PointsLost => t!("map.changes.kind.points_lost"),
+            ChangeKind::TownFounded => t!("map.changes.kind.town_founded"),
+            ChangeKind::PlayerDeleted => t!("map.changes.kind.player_deleted"),
+            ChangeKind::AllianceSwitched => t!("map.changes.kind.alliance_switched"),
+        }
+    }
+}
+
+impl ChangeKind {
+    /// the colour the change is marked with on the map
+    pub fn color(self) -> egui::Color32 {
+        match self {
+            ChangeKind::OwnerChanged => egui::Color32::from_rgb(230, 25, 75),
+            ChangeKind::PointsGained => egui::Color32::from_rgb(60, 180, 75),
+            ChangeKind::PointsLost => egui::Color32::from_rgb(245, 130, 48),
+            ChangeKind::TownFounded => egui::Color32::from_rgb(0, 130, 200),
+            ChangeKind::PlayerDeleted => egui::Color32::from_rgb(145, 30, 180),
+            ChangeKind::AllianceSwitched => egui::Color32::from_rgb(255, 225, 25),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ChangeRecord {
+    pub kind: ChangeKind,
+    /// name of the town or player the change is about, as of the newer snapshot if possible
+    pub subject: String,
+    /// e.g. the previous owner or the previous points. None if there was no owner or alliance.
+    pub before: Option<String>,
+    pub after: Option<String>,
+    /// where the change is marked on the map. For changes of a player these are all their towns.
+    pub towns: Vec<Town>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ChangeSet {
+    /// unix timestamps of the two snapshots
+    pub older_time: i64,
+    pub newer_time: i64,
+    pub records: Vec<ChangeRecord>,
+}
+
+impl ChangeSet {
+    pub fn count(&self, kind: ChangeKind) -> usize {
+        self.records
+            .iter()
+            .filter(|record| record.kind == kind)
+            .count()
+    }
+}
+
+/// a player that owns at least one town, with all of their towns
+#[derive(Debug, Clone)]
+struct PlayerState {
+    name: String,
+    /// id and name of the alliance
+    alliance: Option<(u32, String)>,
+    towns: Vec<Town>,
+}
+
+/// The parts of a snapshot that are compared, with towns and players by id. Much smaller than a
+/// `DataTable`, so the snapshot to compare with does not have to be kept around as a whole.
+#[derive(Debug, Clone, Default)]
+pub struct WorldState {
+    /// unix timestamp of the snapshot
+    time: i64,
+    towns: HashMap<u32, Town>,
+    /// the players that own at least one town
+    players: HashMap<u32, PlayerState>,
+    /// every player of the world, including those without towns
+    all_players: HashSet<u32>,
+}
+
+impl From<&DataTable> for WorldState {
+    fn from(db: &DataTable) -> Self {
+        let mut players: HashMap<u32, PlayerState> = HashMap::new();
+        for town in &db.towns {
+            if let Some((id, player)) = &town.player {
+                players
+                    .entry(*id)
+                    .or_insert_with(|| PlayerState {
+                        name: player.name.clone(),
+                        alliance: player
+                            .alliance
+                            .as_ref()
+                            .map(|(id, alliance)| (*id, alliance.name.clone())),
+                        towns: Vec::new(),
+                    })
+                    .towns
+                    .push(town.deref().into());
+            }
+        }
+        return Self {
+            time: db.reference_time,
+            towns: db
+                .towns
+                .iter()
+                .map(|town| (town.id, town.deref().into()))
+                .collect(),
+            players,
+            all_players: db.players.iter().map(|player| player.id).collect(),
+        };
+    }
+}
+
+fn alliance_name(player: &PlayerState) -> Option<String> {
+    player.alliance.as_ref().map(|(_, name)| name.clone())
+}
+
+/// Compare two snapshots of the same world. The order of the arguments does not matter, the
+/// changes always lead from the older snapshot to the newer one.
+pub fn changes_between(one: &WorldState, two: &WorldState) -> ChangeSet {
+    let (older, newer) = if one.time <= two.time {
+        (one, two)
+    } else {
+        (two, one)
+    };
+    let mut records = Vec::new();
+
+    // TOWNS
+    for (id, town) in &newer.towns {
+        let Some(old_town) = older.towns.get(id) else {
+            records.push(ChangeRecord {
+                kind: ChangeKind::TownFounded,
+                subject: town.name.clone(),
+                before: None,
+                after: town.player_name.clone(),
+                towns: vec![town.clone()],
+            });
+            continue;
+        };
+
+        if old_town.player_id != town.player_id {
+            records.push(ChangeRecord {
+                kind: ChangeKind::OwnerChanged,
+                subject: town.name.clone(),
+                before: old_town.player_name.clone(),
+                after: town.player_name.clone(),
+                towns: vec![town.clone()],
+            });
+        }
+
+        if town.points != old_town.points {
+            let kind = if town.points > old_town.points {
+                ChangeKind::PointsGained
+            } else {
+                ChangeKind::PointsLost
+            };
+            records.push(ChangeRecord {
+                kind,
+                subject: town.name.clone(),
+                before: Some(old_town.points.to_string()),
+                after: Some(town.points.to_string()),
+                towns: vec![town.clone()],
+            });
+        }
+    }
+
+    // PLAYERS
+    for (id, old_player) in &older.players {
+        // the player may have lost all of their towns without being deleted
+        let Some(player) = newer.players.get(id) else {
+            if !newer.all_players.contains(id) {
+                records.push(ChangeRecord {
+                    kind: ChangeKind::PlayerDeleted,
+                    subject: old_player.name.clone(),
+                    before: alliance_name(old_player),
+                    after: None,
+                    towns: old_player.towns.clone(),
+                });
+            }
+            continue;
+        };
+
+        let old_alliance = old_player.alliance.as_ref().map(|(id, _)| *id);
+        let new_alliance = player.alliance.as_ref().map(|(id, _)| *id);
+        if old_alliance != new_alliance {
+            records.push(ChangeRecord {
+                kind: ChangeKind::AllianceSwitched,
+                subject: player.name.clone(),
+                before: alliance_name(old_player),
+                after: alliance_name(player),
+                towns: player.towns.clone(),
+            });
+        }
+    }
+
+    // group the list by kind, the towns and players come in no particular order
+    records.sort_by(|a, b| a.kind.cmp(&b.kind).then_with(|| a.subject.cmp(&b.subject)));
+    return ChangeSet {
+        older_time: older.time,
+        newer_time: newer.time,
+        records,
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::model::database::{fixtures, BackendTown, Player};
+
+    fn player(id: u32, alliance: Option<u32>) -> Arc<Player> {
+        Arc::new(Player {
+            alliance: alliance.map(|id| (id, Arc::new(fixtures::alliance(id)))),
+            ..fixtures::player(id)
+        })
+    }
+
+    fn town(id: u32, owner: Option<&Arc<Player>>, points: u16) -> Arc<BackendTown> {
+        Arc::new(BackendTown {
+            points,
+            player: owner.map(|player| (player.id, Arc::clone(player))),
+            ..fixtures::town(id)
+        })
+    }
+
+    fn snapshot(time: i64, towns: Vec<Arc<BackendTown>>, players: Vec<Arc<Player>>) -> DataTable {
+        DataTable {
+            reference_time: time,
+            ..fixtures::table(towns, players)
+        }
+    }
+
+    fn summary(changes: &ChangeSet) -> Vec<(ChangeKind, &str, Option<&str>, Option<&str>)> {
+        changes
+            .records
+            .iter()
+            .map(|record| {
+                (
+                    record.kind,
+                    record.subject.as_str(),
+                    record.before.as_deref(),
+                    record.after.as_deref(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn changes_between_finds_every_kind_of_change() {
+        let (stays, switches, leaves, deleted) = (
+            player(1, Some(10)),
+            player(2, Some(10)),
+            player(3, None),
+            player(4, None),
+        );
+        let older = snapshot(
+            1000,
+            vec![
+                town(1, Some(&stays), 200),
+                town(2, Some(&switches), 300),
+                town(3, Some(&leaves), 400),
+                town(4, Some(&deleted), 500),
+                town(5, None, 600),
+            ],
+            vec![
+                Arc::clone(&stays),
+                Arc::clone(&switches),
+                Arc::clone(&leaves),
+                Arc::clone(&deleted),
+            ],
+        );
+
+        let in_new_alliance = player(2, Some(20));
+        let newer = snapshot(
+            2000,
+            vec![
+                town(1, Some(&stays), 250),
+                town(2, Some(&in_new_alliance), 300),
+                town(3, Some(&stays), 350),
+                town(4, None, 500),
+                town(5, None, 600),
+                town(6, Some(&stays), 100),
+            ],
+            // the player that lost their only town still exists
+            vec![Arc::clone(&stays), in_new_alliance, Arc::clone(&leaves)],
+        );
+
+        let changes = changes_between(&(&older).into(), &(&newer).into());
+
+        assert_eq!(changes.older_time, 1000);
+        assert_eq!(changes.newer_time, 2000);
+        assert_eq!(
+            summary(&changes),
+            vec![
+                (
+                    ChangeKind::OwnerChanged,
+                    "town3",
+                    Some("player3"),
+                    Some("player1")
+                ),
+                (ChangeKind::OwnerChanged, "town4", Some("player4"), None),
+                (ChangeKind::PointsGained, "town1", Some("200"), Some("250")),
+                (ChangeKind::PointsLost, "town3", Some("400"), Some("350")),
+                (ChangeKind::TownFounded, "town6", None, Some("player1")),
+                (ChangeKind::PlayerDeleted, "player4", None, None),
+                (
+                    ChangeKind::AllianceSwitched,
+                    "player2",
+                    Some("alliance10"),
+                    Some("alliance20")
+                ),
+            ]
+        );
+        assert_eq!(changes.count(ChangeKind::OwnerChanged), 2);
+        assert_eq!(changes.count(ChangeKind::PlayerDeleted), 1);
+
+        // the towns of a deleted player are marked where they were in the older snapshot
+        let deleted_record = &changes.records[5];
+        assert_eq!(deleted_record.towns.len(), 1);
+        assert_eq!(deleted_record.towns[0].id, 4);
+    }
+
+    #[test]
+    fn changes_between_does_not_depend_on_the_order_of_the_arguments() {
+        let owner = player(1, None);
+        let older = snapshot(
+            1000,
+            vec![town(1, Some(&owner), 100)],
+            vec![Arc::clone(&owner)],
+        );
+        let newer = snapshot(
+            2000,
+            vec![town(1, Some(&owner), 150), town(2, None, 50)],
+            vec![Arc::clone(&owner)],
+        );
+
+        let forward = changes_between(&(&older).into(), &(&newer).into());
+        let backward = changes_between(&(&newer).into(), &(&older).into());
+
+        assert_eq!(summary(&forward), summary(&backward));
+        assert_eq!(backward.older_time, 1000);
+        assert_eq!(backward.newer_time, 2000);
+        assert_eq!(
+            summary(&forward),
+            vec![
+                (ChangeKind::PointsGained, "town1", Some("100"), Some("150")),
+                (ChangeKind::TownFounded, "town2", None, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn changes_between_identical_snapshots_is_empty() {
+        let owner = player(1, Some(10));
+        let towns = vec![town(1, Some(&owner), 100), town(2, None, 50)];
+        let one = snapshot(1000, towns.clone(), vec![Arc::clone(&owner)]);
+        let two = snapshot(2000, towns, vec![owner]);
+
+        assert!(changes_between(&(&one).into(), &(&two).into())
+            .records
+            .is_empty());
+    }
+}
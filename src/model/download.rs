@@ -1,9 +1,20 @@
-use super::database::{Alliance, BackendTown, DataTable, Island, Offset, Player};
+use super::database::{
+    Alliance, BackendTown, Conquest, DataTable, Island, KillScore, Kills, Offset, Player,
+};
+use super::history::History;
+use super::report::{Fallback, FallbackReason, ParseReport};
+use super::source::{WorldDataFile, WorldDataSource};
 use super::{offset_data, APIResponse};
+use crate::spatial::SpatialIndex;
+use crate::town::ocean_number;
+use crate::travel::TravelSettings;
 use anyhow::Context;
 use std::collections::HashMap;
-use std::rc::Rc;
 use std::sync::{Arc, Mutex};
+use strum::IntoEnumIterator;
+
+/// how often we try to fetch a file from the data source before giving up
+const MAX_FETCH_ATTEMPTS: u32 = 4;
 
 /*
 Forum threads:
@@ -114,63 +125,183 @@ Building, unit and research information
 */
 
 impl DataTable {
-    pub fn get_api_results(api_results: &Arc<Mutex<APIResponse>>) {
+    /// fetch all files of the world data api from the given `source` and put them into `api_results`
+    /// as they come in. This is done asynchronously so the UI doesn't hang.
+    pub fn get_api_results(
+        api_results: &Arc<Mutex<APIResponse>>,
+        source: &Arc<dyn WorldDataSource>,
+    ) {
         let server_id = api_results.lock().unwrap().for_server.clone();
 
-        #[cfg(target_arch = "wasm32")]
-        let base_url = format!("https://reflector.gmap.turun.de/{server_id}/");
-        #[cfg(not(target_arch = "wasm32"))]
-        let base_url = format!("https://{server_id}.grepolis.com/data/");
-
-        let req_players = ehttp::Request::get(base_url.clone() + "players.txt");
-        let these_api_results = Arc::clone(api_results);
-        ehttp::fetch(req_players, move |response| {
-            let text = String::from_utf8(response.unwrap().bytes).unwrap();
-            these_api_results.lock().unwrap().players = Some(text);
-        });
-
-        let req_players = ehttp::Request::get(base_url.clone() + "alliances.txt");
-        let these_api_results = Arc::clone(api_results);
-        ehttp::fetch(req_players, move |response| {
-            let text = String::from_utf8(response.unwrap().bytes).unwrap();
-            these_api_results.lock().unwrap().alliances = Some(text);
-        });
-
-        let req_players = ehttp::Request::get(base_url.clone() + "towns.txt");
-        let these_api_results = Arc::clone(api_results);
-        ehttp::fetch(req_players, move |response| {
-            let text = String::from_utf8(response.unwrap().bytes).unwrap();
-            these_api_results.lock().unwrap().towns = Some(text);
-        });
+        for file in WorldDataFile::iter() {
+            Self::fetch_with_retry(source, &server_id, file, 1, Arc::clone(api_results));
+        }
+    }
 
-        let req_players = ehttp::Request::get(base_url.clone() + "islands.txt");
-        let these_api_results = Arc::clone(api_results);
-        ehttp::fetch(req_players, move |response| {
-            let text = String::from_utf8(response.unwrap().bytes).unwrap();
-            these_api_results.lock().unwrap().islands = Some(text);
-        });
+    /// fetch one file and store it in `api_results`. Transient errors (timeouts, server errors) are
+    /// retried with an exponential backoff, if we give up the file is marked as failed instead.
+    fn fetch_with_retry(
+        source: &Arc<dyn WorldDataSource>,
+        server_id: &str,
+        file: WorldDataFile,
+        attempt: u32,
+        api_results: Arc<Mutex<APIResponse>>,
+    ) {
+        let this_source = Arc::clone(source);
+        let this_server_id = server_id.to_string();
+        source.fetch(
+            server_id,
+            file,
+            Box::new(move |result| match result {
+                Ok(text) => api_results.lock().unwrap().set_file(file, text),
+                Err(err) if err.is_transient() && attempt < MAX_FETCH_ATTEMPTS => {
+                    eprintln!(
+                        "Attempt {attempt} to fetch {} failed, trying again: {err}",
+                        file.txt_stem()
+                    );
+                    // wait 1s, 2s, 4s, ... before trying again
+                    let delay = std::time::Duration::from_secs(1 << (attempt - 1));
+                    let retry = move || {
+                        Self::fetch_with_retry(
+                            &this_source,
+                            &this_server_id,
+                            file,
+                            attempt + 1,
+                            api_results,
+                        );
+                    };
+                    #[cfg(not(target_arch = "wasm32"))]
+                    {
+                        std::thread::sleep(delay);
+                        retry();
+                    }
+                    // the browser does not let us block the thread, so we set a timer instead
+                    #[cfg(target_arch = "wasm32")]
+                    crate::wasm_utils::run_after(delay, retry);
+                }
+                Err(err) => {
+                    eprintln!("Giving up on fetching {}: {err}", file.txt_stem());
+                    api_results
+                        .lock()
+                        .unwrap()
+                        .set_failed(file, err.to_string());
+                }
+            }),
+        );
     }
 
-    pub fn create_for_world(api_response: APIResponse) -> Self {
-        // TODO: we need to massively improve the way we handle errors here. Crashing the entire backend if one line in
-        // one input file is unexpected is not a good solution. We need more fine grained error handling.
+    /// build the `DataTable` from a complete api response. Lines that can not be parsed are skipped,
+    /// they and any other irregularities are collected in the returned `ParseReport`.
+    #[allow(clippy::too_many_lines)] // one step per api file
+    pub fn create_for_world(api_response: APIResponse) -> (Self, ParseReport) {
+        let mut report = ParseReport::default();
         let offsets = Self::make_offsets();
-        let (bl_alliances, alliances) = Self::parse_alliances(&api_response.alliances.unwrap());
-        let (bl_islands, islands) = Self::parse_islands(&api_response.islands.unwrap());
-        let (bl_players, players) = Self::parse_players(&api_response.players.unwrap(), &alliances);
-        let (bl_towns, towns) =
-            Self::parse_towns(&api_response.towns.unwrap(), &players, &islands, &offsets);
-        let towns = towns.into_values().collect();
-
-        // TODO: do something with the bad lines information
-        let total_bad_lines = bl_alliances + bl_islands + bl_players + bl_towns;
-        if total_bad_lines > 0 {
-            eprintln!("Got {total_bad_lines} bad lines in api response.");
+        let alliance_kills = Self::parse_kills(
+            [
+                (
+                    WorldDataFile::AllianceKillsAll,
+                    &api_response.alliance_kills_all.unwrap(),
+                ),
+                (
+                    WorldDataFile::AllianceKillsAtt,
+                    &api_response.alliance_kills_att.unwrap(),
+                ),
+                (
+                    WorldDataFile::AllianceKillsDef,
+                    &api_response.alliance_kills_def.unwrap(),
+                ),
+            ],
+            &mut report,
+        );
+        let player_kills = Self::parse_kills(
+            [
+                (
+                    WorldDataFile::PlayerKillsAll,
+                    &api_response.player_kills_all.unwrap(),
+                ),
+                (
+                    WorldDataFile::PlayerKillsAtt,
+                    &api_response.player_kills_att.unwrap(),
+                ),
+                (
+                    WorldDataFile::PlayerKillsDef,
+                    &api_response.player_kills_def.unwrap(),
+                ),
+            ],
+            &mut report,
+        );
+        let alliances = Self::parse_alliances(
+            &api_response.alliances.unwrap(),
+            &alliance_kills,
+            &mut report,
+        );
+        let islands = Self::parse_islands(&api_response.islands.unwrap(), &mut report);
+        let players = Self::parse_players(
+            &api_response.players.unwrap(),
+            &alliances,
+            &player_kills,
+            &mut report,
+        );
+        let conquests = Self::parse_conquests(
+            &api_response.conquers.unwrap(),
+            &players,
+            &alliances,
+            &mut report,
+        );
+        let mut conquests_by_town: HashMap<u32, Vec<Arc<Conquest>>> = HashMap::new();
+        for conquest in &conquests {
+            conquests_by_town
+                .entry(conquest.town_id)
+                .or_default()
+                .push(Arc::clone(conquest));
         }
-        Self { towns }
+        for town_conquests in conquests_by_town.values_mut() {
+            town_conquests.sort_by_key(|conquest| conquest.time);
+        }
+        let towns = Self::parse_towns(
+            &api_response.towns.unwrap(),
+            &players,
+            &islands,
+            &offsets,
+            &conquests_by_town,
+            &mut report,
+        );
+        let towns: Vec<Arc<BackendTown>> = towns.into_values().collect();
+        let index = SpatialIndex::new(
+            towns
+                .iter()
+                .map(|town| egui::pos2(town.actual_x, town.actual_y))
+                .collect(),
+        );
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let reference_time = api_response.timestamp.unix_timestamp();
+        // on wasm the data is fetched right before it is parsed, so now is close enough
+        #[cfg(target_arch = "wasm32")]
+        #[allow(clippy::cast_possible_truncation)] // seconds since 1970 fit into an i64
+        let reference_time = (js_sys::Date::now() / 1000.0) as i64;
+
+        if !report.is_empty() {
+            eprintln!(
+                "Got {} bad lines and {} misplaced towns in api response.",
+                report.bad_lines.len(),
+                report.fallbacks.len()
+            );
+        }
+        let db = Self {
+            towns,
+            players: players.into_values().collect(),
+            index: Arc::new(index),
+            conquests,
+            reference_time,
+            exclude_approximate: false,
+            travel: Arc::new(TravelSettings::default()),
+            history: Arc::new(History::default()),
+        };
+        return (db, report);
     }
 
-    fn make_offsets() -> HashMap<(u8, u8), Rc<Offset>> {
+    fn make_offsets() -> HashMap<(u8, u8), Arc<Offset>> {
         let lines: Vec<&str> = offset_data::OFFSET_DATA.lines().collect();
         let mut re = HashMap::with_capacity(lines.len());
         for line in lines {
@@ -181,7 +312,7 @@ impl DataTable {
             let slot_number: u8 = values.next().unwrap().parse().unwrap();
             let _duplicate = re.insert(
                 (typ, slot_number),
-                Rc::new(Offset {
+                Arc::new(Offset {
                     typ,
                     x,
                     y,
@@ -192,8 +323,70 @@ impl DataTable {
         return re;
     }
 
-    fn parse_alliances(data: &str) -> (u32, HashMap<u32, Rc<Alliance>>) {
-        fn parse_line(line: &str) -> anyhow::Result<(u32, Alliance)> {
+    /// parse the three kill rankings (overall, as attacker, as defender) of either players or
+    /// alliances and merge them into one `Kills` entry per player/alliance id.
+    fn parse_kills(
+        files: [(WorldDataFile, &str); 3],
+        report: &mut ParseReport,
+    ) -> HashMap<u32, Kills> {
+        fn parse_line(line: &str) -> anyhow::Result<(u32, KillScore)> {
+            let mut values = line.split(',');
+
+            let rank = values
+                .next()
+                .with_context(|| format!("No kills rank in {line}"))?
+                .parse()
+                .with_context(|| format!("No kills rank in {line} that can be parsed as int"))?;
+            let id = values
+                .next()
+                .with_context(|| format!("No kills id in {line}"))?
+                .parse()
+                .with_context(|| format!("No kills id in {line} that can be parsed as int"))?;
+            let points = values
+                .next()
+                .with_context(|| format!("No kills points in {line}"))?
+                .parse()
+                .with_context(|| format!("No kills points in {line} that can be parsed as int"))?;
+            return Ok((id, KillScore { rank, points }));
+        }
+
+        let mut parse_ranking = |(file, data): (WorldDataFile, &str)| {
+            let lines: Vec<&str> = data.lines().collect();
+            let mut ranking = HashMap::with_capacity(lines.len());
+            for (index, line) in lines.into_iter().enumerate() {
+                match parse_line(line) {
+                    Ok((id, score)) => {
+                        let _duplicate = ranking.insert(id, score);
+                    }
+                    Err(err) => report.add_bad_line(file, index, line, &err),
+                }
+            }
+            ranking
+        };
+        let [file_all, file_att, file_def] = files;
+        let ranking_all = parse_ranking(file_all);
+        let ranking_att = parse_ranking(file_att);
+        let ranking_def = parse_ranking(file_def);
+
+        let mut re: HashMap<u32, Kills> = HashMap::with_capacity(ranking_all.len());
+        for (id, score) in ranking_all {
+            re.entry(id).or_default().all = Some(score);
+        }
+        for (id, score) in ranking_att {
+            re.entry(id).or_default().attack = Some(score);
+        }
+        for (id, score) in ranking_def {
+            re.entry(id).or_default().defence = Some(score);
+        }
+        return re;
+    }
+
+    fn parse_alliances(
+        data: &str,
+        kills: &HashMap<u32, Kills>,
+        report: &mut ParseReport,
+    ) -> HashMap<u32, Arc<Alliance>> {
+        fn parse_line(line: &str, kills: &HashMap<u32, Kills>) -> anyhow::Result<(u32, Alliance)> {
             let mut values = line.split(',');
 
             let id = values
@@ -239,24 +432,25 @@ impl DataTable {
                     towns,
                     members,
                     rank,
+                    kills: kills.get(&id).copied().unwrap_or_default(),
                 },
             ));
         }
 
-        let mut bad_lines = 0;
         let lines: Vec<&str> = data.lines().collect();
         let mut re = HashMap::with_capacity(lines.len());
-        for line in lines {
-            if let Ok((id, alliance)) = parse_line(line) {
-                let _duplicate = re.insert(id, Rc::new(alliance));
-            } else {
-                bad_lines += 1;
+        for (index, line) in lines.into_iter().enumerate() {
+            match parse_line(line, kills) {
+                Ok((id, alliance)) => {
+                    let _duplicate = re.insert(id, Arc::new(alliance));
+                }
+                Err(err) => report.add_bad_line(WorldDataFile::Alliances, index, line, &err),
             }
         }
-        return (bad_lines, re);
+        return re;
     }
 
-    fn parse_islands(data: &str) -> (u32, HashMap<(u16, u16), Rc<Island>>) {
+    fn parse_islands(data: &str, report: &mut ParseReport) -> HashMap<(u16, u16), Arc<Island>> {
         fn parse_line(line: &str) -> anyhow::Result<(u16, u16, Island)> {
             let mut values = line.split(',');
 
@@ -308,26 +502,29 @@ impl DataTable {
             ));
         }
 
-        let mut bad_lines = 0;
         let lines: Vec<&str> = data.lines().collect();
         let mut re = HashMap::with_capacity(lines.len());
-        for line in lines {
-            if let Ok((x, y, island)) = parse_line(line) {
-                let _duplicate = re.insert((x, y), Rc::new(island));
-            } else {
-                bad_lines += 1;
+        for (index, line) in lines.into_iter().enumerate() {
+            match parse_line(line) {
+                Ok((x, y, island)) => {
+                    let _duplicate = re.insert((x, y), Arc::new(island));
+                }
+                Err(err) => report.add_bad_line(WorldDataFile::Islands, index, line, &err),
             }
         }
-        return (bad_lines, re);
+        return re;
     }
 
     fn parse_players(
         data: &str,
-        alliances: &HashMap<u32, Rc<Alliance>>,
-    ) -> (u32, HashMap<u32, Rc<Player>>) {
+        alliances: &HashMap<u32, Arc<Alliance>>,
+        kills: &HashMap<u32, Kills>,
+        report: &mut ParseReport,
+    ) -> HashMap<u32, Arc<Player>> {
         fn parse_line(
             line: &str,
-            alliances: &HashMap<u32, Rc<Alliance>>,
+            alliances: &HashMap<u32, Arc<Alliance>>,
+            kills: &HashMap<u32, Kills>,
         ) -> anyhow::Result<(u32, Player)> {
             let mut values = line.split(',');
 
@@ -375,7 +572,7 @@ impl DataTable {
 
             let alliance_tuple = if let Some(alliance_id) = opt_alliance_id {
                 let opt_alliance = alliances.get(&alliance_id);
-                opt_alliance.map(|alliance| (alliance_id, Rc::clone(alliance)))
+                opt_alliance.map(|alliance| (alliance_id, Arc::clone(alliance)))
             } else {
                 None
             };
@@ -389,34 +586,145 @@ impl DataTable {
                     points,
                     rank,
                     towns,
+                    kills: kills.get(&id).copied().unwrap_or_default(),
                 },
             ));
         }
 
-        let mut bad_lines = 0;
         let lines: Vec<&str> = data.lines().collect();
         let mut re = HashMap::with_capacity(lines.len());
-        for line in lines {
-            if let Ok((id, player)) = parse_line(line, alliances) {
-                let _duplicate = re.insert(id, Rc::new(player));
+        for (index, line) in lines.into_iter().enumerate() {
+            match parse_line(line, alliances, kills) {
+                Ok((id, player)) => {
+                    let _duplicate = re.insert(id, Arc::new(player));
+                }
+                Err(err) => report.add_bad_line(WorldDataFile::Players, index, line, &err),
+            }
+        }
+        return re;
+    }
+
+    fn parse_conquests(
+        data: &str,
+        players: &HashMap<u32, Arc<Player>>,
+        alliances: &HashMap<u32, Arc<Alliance>>,
+        report: &mut ParseReport,
+    ) -> Vec<Arc<Conquest>> {
+        fn parse_optional_id(
+            text: Option<&str>,
+            what: &str,
+            line: &str,
+        ) -> anyhow::Result<Option<u32>> {
+            let text = text.with_context(|| format!("No conquest {what} in {line}"))?;
+            if text.is_empty() {
+                Ok(None)
             } else {
-                bad_lines += 1;
+                Ok(Some(text.parse().with_context(|| {
+                    format!("No conquest {what} in {line} that can be parsed as int")
+                })?))
+            }
+        }
+
+        fn parse_line(
+            line: &str,
+            players: &HashMap<u32, Arc<Player>>,
+            alliances: &HashMap<u32, Arc<Alliance>>,
+        ) -> anyhow::Result<Conquest> {
+            let mut values = line.split(',');
+
+            let town_id = values
+                .next()
+                .with_context(|| format!("No conquest town id in {line}"))?
+                .parse()
+                .with_context(|| {
+                    format!("No conquest town id in {line} that can be parsed as int")
+                })?;
+            let time = values
+                .next()
+                .with_context(|| format!("No conquest time in {line}"))?
+                .parse()
+                .with_context(|| format!("No conquest time in {line} that can be parsed as int"))?;
+            let opt_new_player_id = parse_optional_id(values.next(), "new player id", line)?;
+            let opt_old_player_id = parse_optional_id(values.next(), "old player id", line)?;
+            let opt_new_alliance_id = parse_optional_id(values.next(), "new alliance id", line)?;
+            let opt_old_alliance_id = parse_optional_id(values.next(), "old alliance id", line)?;
+            let town_points = values
+                .next()
+                .with_context(|| format!("No conquest town points in {line}"))?
+                .parse()
+                .with_context(|| {
+                    format!("No conquest town points in {line} that can be parsed as int")
+                })?;
+
+            // players and alliances that no longer exist can not be linked
+            let link_player = |opt_id: Option<u32>| {
+                opt_id.and_then(|id| players.get(&id).map(|player| (id, Arc::clone(player))))
+            };
+            let link_alliance = |opt_id: Option<u32>| {
+                opt_id.and_then(|id| {
+                    alliances
+                        .get(&id)
+                        .map(|alliance| (id, Arc::clone(alliance)))
+                })
+            };
+
+            return Ok(Conquest {
+                town_id,
+                time,
+                new_player: link_player(opt_new_player_id),
+                old_player: link_player(opt_old_player_id),
+                new_alliance: link_alliance(opt_new_alliance_id),
+                old_alliance: link_alliance(opt_old_alliance_id),
+                town_points,
+            });
+        }
+
+        let lines: Vec<&str> = data.lines().collect();
+        let mut re = Vec::with_capacity(lines.len());
+        for (index, line) in lines.into_iter().enumerate() {
+            match parse_line(line, players, alliances) {
+                Ok(conquest) => {
+                    re.push(Arc::new(conquest));
+                }
+                Err(err) => report.add_bad_line(WorldDataFile::Conquers, index, line, &err),
             }
         }
-        return (bad_lines, re);
+        return re;
     }
 
+    #[allow(clippy::too_many_lines)]
     fn parse_towns(
         data: &str,
-        players: &HashMap<u32, Rc<Player>>,
-        islands: &HashMap<(u16, u16), Rc<Island>>,
-        offsets: &HashMap<(u8, u8), Rc<Offset>>,
-    ) -> (u32, HashMap<u32, Rc<BackendTown>>) {
+        players: &HashMap<u32, Arc<Player>>,
+        islands: &HashMap<(u16, u16), Arc<Island>>,
+        offsets: &HashMap<(u8, u8), Arc<Offset>>,
+        conquests: &HashMap<u32, Vec<Arc<Conquest>>>,
+        report: &mut ParseReport,
+    ) -> HashMap<u32, Arc<BackendTown>> {
+        /// the average offset of all slots on an island of the given type, or of all islands if the
+        /// type is unknown
+        fn island_centre(typ: Option<u8>, offsets: &HashMap<(u8, u8), Arc<Offset>>) -> (f32, f32) {
+            let mut matching: Vec<&Arc<Offset>> = offsets
+                .values()
+                .filter(|offset| typ.is_none_or(|typ| offset.typ == typ))
+                .collect();
+            if matching.is_empty() {
+                matching = offsets.values().collect();
+            }
+            #[allow(clippy::cast_precision_loss)] // there are only a few hundred offsets
+            let count = matching.len().max(1) as f32;
+            let sum_x: f32 = matching.iter().map(|offset| f32::from(offset.x)).sum();
+            let sum_y: f32 = matching.iter().map(|offset| f32::from(offset.y)).sum();
+            return (sum_x / count, sum_y / count);
+        }
+
         fn parse_line(
             line: &str,
-            players: &HashMap<u32, Rc<Player>>,
-            islands: &HashMap<(u16, u16), Rc<Island>>,
-            offsets: &HashMap<(u8, u8), Rc<Offset>>,
+            players: &HashMap<u32, Arc<Player>>,
+            islands: &HashMap<(u16, u16), Arc<Island>>,
+            offsets: &HashMap<(u8, u8), Arc<Offset>>,
+            conquests: &HashMap<u32, Vec<Arc<Conquest>>>,
+            report: &mut ParseReport,
         ) -> anyhow::Result<(u32, BackendTown)> {
             let mut values = line.split(',');
 
@@ -472,38 +780,48 @@ impl DataTable {
             // get actual player from the player id
             let player_tuple = if let Some(player_id) = opt_player_id {
                 let opt_player = players.get(&player_id);
-                opt_player.map(|player| (player_id, Rc::clone(player)))
+                opt_player.map(|player| (player_id, Arc::clone(player)))
             } else {
                 None
             };
 
             // get actual island from x and y
-            let island_tuple = (x, y, {
-                let opt_island = islands.get(&(x, y));
-                if let Some(island) = opt_island {
-                    Rc::clone(island)
-                } else {
-                    // every town _needs_ a corresponding island. So if there is no matching one found we take the first we get.
-                    let (_key, value) = islands.iter().next().unwrap();
-                    Rc::clone(value)
-                }
-            });
+            let opt_island = islands.get(&(x, y)).map(Arc::clone);
+            if opt_island.is_none() {
+                report.fallbacks.push(Fallback {
+                    town_id: id,
+                    town_name: name.clone(),
+                    reason: FallbackReason::MissingIsland { x, y },
+                });
+            }
 
-            // get the offset from the offset list from slot_number
-            let offset_tuple = (slot_number, {
-                let opt_offset = offsets.get(&(island_tuple.2.typ, slot_number));
-                if let Some(offset) = opt_offset {
-                    Rc::clone(offset)
-                } else {
-                    // correspondingly, every town also _needs_ an offset tuple.
-                    let (_key, value) = offsets.iter().next().unwrap();
-                    Rc::clone(value)
+            // get the offset from the offset list from slot_number. Without an island we don't
+            // know the island type, so there is no way to find the offset.
+            let opt_offset = opt_island.as_ref().and_then(|island| {
+                let opt_offset = offsets.get(&(island.typ, slot_number)).map(Arc::clone);
+                if opt_offset.is_none() {
+                    report.fallbacks.push(Fallback {
+                        town_id: id,
+                        town_name: name.clone(),
+                        reason: FallbackReason::MissingOffset {
+                            island_type: island.typ,
+                            slot_number,
+                        },
+                    });
                 }
+                opt_offset
             });
 
-            // compute actual x
-            let actual_x = f32::from(x) + f32::from(offset_tuple.1.x) / 125f32;
-            let actual_y = f32::from(y) + f32::from(offset_tuple.1.y) / 125f32;
+            // compute actual x. If we don't know where exactly on the island the town is, we put
+            // it in the middle of the island and mark the position as approximate
+            let approximate = opt_offset.is_none();
+            let (offset_x, offset_y) = if let Some(offset) = &opt_offset {
+                (f32::from(offset.x), f32::from(offset.y))
+            } else {
+                island_centre(opt_island.as_ref().map(|island| island.typ), offsets)
+            };
+            let actual_x = f32::from(x) + offset_x / 125f32;
+            let actual_y = f32::from(y) + offset_y / 125f32;
 
             return Ok((
                 id,
@@ -512,25 +830,158 @@ impl DataTable {
                     name,
                     points,
                     player: player_tuple,
-                    island: island_tuple,
-                    offset: offset_tuple,
+                    island: (x, y, opt_island),
+                    ocean: ocean_number(x, y),
+                    offset: (slot_number, opt_offset),
                     actual_x,
                     actual_y,
+                    approximate,
+                    conquests: conquests.get(&id).cloned().unwrap_or_default(),
                 },
             ));
         }
 
-        let mut bad_lines = 0;
         let lines: Vec<&str> = data.lines().collect();
         let mut re = HashMap::with_capacity(lines.len());
-        for line in lines {
-            if let Ok((id, town)) = parse_line(line, players, islands, offsets) {
-                let _duplicate = re.insert(id, Rc::new(town));
-            } else {
-                // TODO: dont use a counter, use a list that contains the lines themselves
-                bad_lines += 1;
+        for (index, line) in lines.into_iter().enumerate() {
+            match parse_line(line, players, islands, offsets, conquests, report) {
+                Ok((id, town)) => {
+                    let _duplicate = re.insert(id, Arc::new(town));
+                }
+                Err(err) => report.add_bad_line(WorldDataFile::Towns, index, line, &err),
             }
         }
-        return (bad_lines, re);
+        return re;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::database::fixtures;
+
+    #[test]
+    fn parse_conquests_links_known_players_and_skips_bad_lines() {
+        let players = HashMap::from([(7, Arc::new(fixtures::player(7)))]);
+        let data = "1,1700000000,7,8,,,1234\n2,1700000100,,,,,55\nnot,a,conquest\n3,1700000200,7";
+        let mut report = ParseReport::default();
+
+        let conquests = DataTable::parse_conquests(data, &players, &HashMap::new(), &mut report);
+
+        assert_eq!(conquests.len(), 2);
+        assert_eq!(conquests[0].town_id, 1);
+        assert_eq!(conquests[0].time, 1_700_000_000);
+        assert_eq!(conquests[0].town_points, 1234);
+        assert_eq!(conquests[0].new_player.as_ref().map(|(id, _)| *id), Some(7));
+        // player 8 is not in the player list anymore
+        assert!(conquests[0].old_player.is_none());
+        assert!(conquests[1].new_player.is_none());
+        assert_eq!(conquests[1].town_points, 55);
+
+        let line_numbers: Vec<usize> = report.bad_lines.iter().map(|bad| bad.line_number).collect();
+        assert_eq!(line_numbers, vec![3, 4]);
+        assert!(report
+            .bad_lines
+            .iter()
+            .all(|bad| bad.file == WorldDataFile::Conquers));
+    }
+
+    #[test]
+    fn parse_kills_merges_the_three_rankings() {
+        let mut report = ParseReport::default();
+        let kills = DataTable::parse_kills(
+            [
+                (WorldDataFile::PlayerKillsAll, "1,7,500\n2,8,300"),
+                (WorldDataFile::PlayerKillsAtt, "1,7,400\nbad line"),
+                (WorldDataFile::PlayerKillsDef, "1,8,300"),
+            ],
+            &mut report,
+        );
+
+        assert_eq!(kills.len(), 2);
+        assert_eq!(
+            kills[&7].all,
+            Some(KillScore {
+                rank: 1,
+                points: 500
+            })
+        );
+        assert_eq!(
+            kills[&7].attack,
+            Some(KillScore {
+                rank: 1,
+                points: 400
+            })
+        );
+        assert_eq!(kills[&7].defence, None);
+        assert_eq!(kills[&8].attack, None);
+        assert_eq!(
+            kills[&8].defence,
+            Some(KillScore {
+                rank: 1,
+                points: 300
+            })
+        );
+
+        assert_eq!(report.bad_lines.len(), 1);
+        assert_eq!(report.bad_lines[0].file, WorldDataFile::PlayerKillsAtt);
+        assert_eq!(report.bad_lines[0].line_number, 2);
+    }
+
+    #[test]
+    fn parse_towns_keeps_towns_without_island_or_slot_at_an_approximate_position() {
+        let offsets = DataTable::make_offsets();
+        let island = Arc::new(Island {
+            id: 1,
+            x: 500,
+            y: 500,
+            typ: 1,
+            towns: 20,
+            ressource_plus: String::new(),
+            ressource_minus: String::new(),
+        });
+        let islands = HashMap::from([((500, 500), island)]);
+        let data = "1,,Exact,500,500,0,100\n2,,Lost,600,600,0,100\n3,,Odd+Slot,500,500,99,100";
+        let mut report = ParseReport::default();
+
+        let towns = DataTable::parse_towns(
+            data,
+            &HashMap::new(),
+            &islands,
+            &offsets,
+            &HashMap::new(),
+            &mut report,
+        );
+
+        assert_eq!(towns.len(), 3);
+        assert!(report.bad_lines.is_empty());
+        let exact = &towns[&1];
+        assert!(!exact.approximate);
+        assert!((exact.actual_x - (500.0 + 451.0 / 125.0)).abs() < 1e-3);
+        assert!((exact.actual_y - (500.0 + 130.0 / 125.0)).abs() < 1e-3);
+
+        let lost = &towns[&2];
+        assert!(lost.approximate);
+        assert!(lost.island.2.is_none());
+        // somewhere on the island, not at its corner
+        assert!(lost.actual_x > 600.0 && lost.actual_x < 605.0);
+        assert!(lost.actual_y > 600.0 && lost.actual_y < 605.0);
+        assert_eq!(towns[&3].name, "Odd Slot");
+        assert!(towns[&3].approximate);
+
+        assert_eq!(report.fallbacks.len(), 2);
+        assert_eq!(report.fallbacks[0].town_id, 2);
+        assert!(matches!(
+            report.fallbacks[0].reason,
+            FallbackReason::MissingIsland { x: 600, y: 600 }
+        ));
+        assert_eq!(report.fallbacks[1].town_id, 3);
+        assert!(matches!(
+            report.fallbacks[1].reason,
+            FallbackReason::MissingOffset {
+                island_type: 1,
+                slot_number: 99
+            }
+        ));
     }
 }
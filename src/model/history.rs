@@ -0,0 +1,362 @@
+//! The saved snapshots of a world, to see how towns and players developed over time.
+//!
+//! Only the values that are compared over time are kept, a few numbers per town and player. That
+//! way dozens of snapshots fit into memory next to the data that is shown.
+
+use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::sync::Arc;
+
+#[cfg(not(target_arch = "wasm32"))]
+use time::format_description::well_known::Rfc3339;
+#[cfg(not(target_arch = "wasm32"))]
+use time::OffsetDateTime;
+
+#[cfg(not(target_arch = "wasm32"))]
+use super::APIResponse;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::storage::{self, SavedDB};
+
+pub const SECONDS_PER_DAY: f64 = 86400.0;
+
+/// at most this many saved snapshots are read, the newest ones. Months of daily snapshots.
+#[cfg(not(target_arch = "wasm32"))]
+const MAX_SNAPSHOTS: usize = 120;
+
+/// A moment as it is stored in the value of a constraint, e.g. `2024-04-05T12:30:00Z`. It is in
+/// UTC, so the constraint means the same moment in every time zone. On wasm, where there are no
+/// saved snapshots to compare with, it is the plain unix timestamp.
+pub fn format_time(time: i64) -> String {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(text) = OffsetDateTime::from_unix_timestamp(time)
+        .ok()
+        .and_then(|date| date.format(&Rfc3339).ok())
+    {
+        return text;
+    }
+    return time.to_string();
+}
+
+/// the unix timestamp of a moment given by `format_time`, or given as a unix timestamp
+pub fn parse_time(value: &str) -> Option<i64> {
+    let value = value.trim();
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Ok(date) = OffsetDateTime::parse(value, &Rfc3339) {
+        return Some(date.unix_timestamp());
+    }
+    return value.parse().ok();
+}
+
+/// how a moment given by `format_time` is shown to the user, in the local time zone
+pub fn time_label(value: &str) -> String {
+    #[cfg(not(target_arch = "wasm32"))]
+    if let Some(time) = parse_time(value) {
+        return storage::format_timestamp(time);
+    }
+    return value.to_string();
+}
+
+#[derive(Debug)]
+pub struct Snapshot {
+    /// unix timestamp of the moment the data was fetched
+    pub time: i64,
+    #[cfg(not(target_arch = "wasm32"))]
+    pub path: PathBuf,
+    /// owner and points by town id
+    pub towns: HashMap<u32, (Option<u32>, u16)>,
+    /// points and number of towns by player id
+    pub players: HashMap<u32, (u32, u16)>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Snapshot {
+    /// Pick the values we compare from the api response. Lines that can not be parsed are skipped,
+    /// they were already reported when the snapshot was loaded as a whole.
+    fn new(saved_db: &SavedDB, api_response: &APIResponse) -> Self {
+        let mut towns = HashMap::new();
+        // id, player id, name, island x, island y, slot number, points
+        for line in api_response.towns.as_deref().unwrap_or_default().lines() {
+            let values: Vec<&str> = line.split(',').collect();
+            let (Some(id), Some(points)) = (
+                values.first().and_then(|v| v.parse().ok()),
+                values.get(6).and_then(|v| v.parse().ok()),
+            ) else {
+                continue;
+            };
+            let owner = values.get(1).and_then(|v| v.parse().ok());
+            towns.insert(id, (owner, points));
+        }
+
+        let mut players = HashMap::new();
+        // id, name, alliance id, points, rank, towns
+        for line in api_response.players.as_deref().unwrap_or_default().lines() {
+            let values: Vec<&str> = line.split(',').collect();
+            let (Some(id), Some(points), Some(town_count)) = (
+                values.first().and_then(|v| v.parse().ok()),
+                values.get(3).and_then(|v| v.parse().ok()),
+                values.get(5).and_then(|v| v.parse().ok()),
+            ) else {
+                continue;
+            };
+            players.insert(id, (points, town_count));
+        }
+
+        Self {
+            time: saved_db.date.unix_timestamp(),
+            path: saved_db.path.clone(),
+            towns,
+            players,
+        }
+    }
+}
+
+/// how long a player has not grown, see `Player::inactivity`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Inactivity {
+    pub days: f64,
+    /// number of snapshots the inactivity is based on, the more the more reliable it is
+    pub snapshots: usize,
+}
+
+/// the saved snapshots of one server, oldest first
+#[derive(Debug, Default)]
+pub struct History {
+    pub server: String,
+    snapshots: Vec<Arc<Snapshot>>,
+}
+
+impl History {
+    /// Read the saved snapshots of `server`, the newest of each day and at most `MAX_SNAPSHOTS`.
+    /// The snapshots of `known` are reused instead of read again, if it belongs to the same
+    /// server. Files that can not be read are skipped.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load(server: &str, known: &History) -> Self {
+        let mut saved_dbs = storage::get_list_of_saved_dbs()
+            .remove(server)
+            .unwrap_or_default();
+        saved_dbs.sort_by_key(|saved_db| saved_db.date);
+        let saved_dbs = newest_per_day(saved_dbs, |saved_db| saved_db.date.unix_timestamp());
+
+        let known_snapshots: &[Arc<Snapshot>] = if known.server == server {
+            &known.snapshots
+        } else {
+            &[]
+        };
+        let mut snapshots = Vec::with_capacity(saved_dbs.len());
+        for saved_db in saved_dbs {
+            let opt_known = known_snapshots
+                .iter()
+                .find(|snapshot| snapshot.path == saved_db.path);
+            if let Some(snapshot) = opt_known {
+                snapshots.push(Arc::clone(snapshot));
+                continue;
+            }
+            match APIResponse::read_from_file(&saved_db) {
+                Ok(api_response) => {
+                    snapshots.push(Arc::new(Snapshot::new(&saved_db, &api_response)));
+                }
+                Err(err) => {
+                    eprintln!("Skipping {saved_db} in the history of {server}: {err:?}");
+                }
+            }
+        }
+        Self {
+            server: server.to_string(),
+            snapshots,
+        }
+    }
+
+    /// a history of the given snapshots, oldest first
+    #[cfg(test)]
+    pub fn from_snapshots(server: &str, snapshots: Vec<Snapshot>) -> Self {
+        Self {
+            server: server.to_string(),
+            snapshots: snapshots.into_iter().map(Arc::new).collect(),
+        }
+    }
+
+    /// are both made of the same snapshots?
+    pub fn same_snapshots(&self, other: &History) -> bool {
+        self.server == other.server
+            && self.snapshots.len() == other.snapshots.len()
+            && self
+                .snapshots
+                .iter()
+                .zip(&other.snapshots)
+                .all(|(a, b)| Arc::ptr_eq(a, b))
+    }
+
+    /// the snapshots taken before `time`, oldest first
+    pub fn before(&self, time: i64) -> &[Arc<Snapshot>] {
+        let end = self
+            .snapshots
+            .partition_point(|snapshot| snapshot.time < time);
+        return &self.snapshots[..end];
+    }
+
+    /// The newest snapshot that is at least `days` older than `time`. None if the history does not
+    /// reach back that far.
+    pub fn days_before(&self, time: i64, days: f64) -> Option<&Snapshot> {
+        #[allow(clippy::cast_possible_truncation)] // the history is never that long
+        let latest = time - (days * SECONDS_PER_DAY).ceil() as i64;
+        return self.at_or_before(latest);
+    }
+
+    /// The newest snapshot taken at `time` or before. None if the history does not reach back that
+    /// far.
+    pub fn at_or_before(&self, time: i64) -> Option<&Snapshot> {
+        self.before(time.saturating_add(1))
+            .last()
+            .map(std::convert::AsRef::as_ref)
+    }
+}
+
+/// Of `items` sorted oldest first, keep the newest one of each day (in UTC), and of those only the
+/// newest `MAX_SNAPSHOTS`. Saving every few hours adds little over one snapshot per day.
+#[cfg(not(target_arch = "wasm32"))]
+fn newest_per_day<T>(items: Vec<T>, time: impl Fn(&T) -> i64) -> Vec<T> {
+    let day = |item: &T| time(item).div_euclid(86400);
+    let mut items = items.into_iter().peekable();
+    let mut re = Vec::new();
+    while let Some(item) = items.next() {
+        let newer_same_day = items.peek().is_some_and(|next| day(next) == day(&item));
+        if !newer_same_day {
+            re.push(item);
+        }
+    }
+    let skip = re.len().saturating_sub(MAX_SNAPSHOTS);
+    return re.split_off(skip);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::database::{fixtures, Player};
+
+    const DAY: i64 = 86400;
+
+    fn player(points: u32, towns: u16) -> Player {
+        Player {
+            points,
+            towns,
+            ..fixtures::player(1)
+        }
+    }
+
+    /// one snapshot per day, with the points and towns of player 1 in each of them
+    fn history(days: &[(i64, Option<(u32, u16)>)]) -> History {
+        History {
+            server: String::from("de99"),
+            snapshots: days
+                .iter()
+                .map(|(day, values)| {
+                    Arc::new(Snapshot {
+                        time: day * DAY,
+                        path: PathBuf::new(),
+                        towns: HashMap::new(),
+                        players: values.iter().map(|values| (1, *values)).collect(),
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn inactivity_reaches_back_while_the_player_did_not_grow() {
+        let history = history(&[
+            (1, Some((900, 3))),
+            (2, Some((1000, 3))),
+            (3, Some((1000, 3))),
+            (4, Some((1000, 3))),
+        ]);
+
+        let inactivity = player(1000, 3).inactivity(&history, None, 5 * DAY);
+
+        assert_eq!(inactivity.snapshots, 3);
+        assert!((inactivity.days - 3.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn inactivity_counts_lost_points_as_inactive() {
+        let history = history(&[(1, Some((1200, 3))), (2, Some((1100, 3)))]);
+
+        let inactivity = player(1000, 3).inactivity(&history, None, 3 * DAY);
+
+        assert_eq!(inactivity.snapshots, 2);
+        assert!((inactivity.days - 2.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn inactivity_ends_at_a_change_of_the_number_of_towns() {
+        let history = history(&[(1, Some((1000, 2))), (2, Some((1000, 3)))]);
+
+        let inactivity = player(1000, 3).inactivity(&history, None, 3 * DAY);
+
+        assert_eq!(inactivity.snapshots, 1);
+        assert!((inactivity.days - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn inactivity_ends_at_a_conquest() {
+        // the conquered town was lost again, so points and towns look the same
+        let history = history(&[(1, Some((1000, 3))), (2, Some((1000, 3)))]);
+
+        let inactivity = player(1000, 3).inactivity(&history, Some(DAY + DAY / 2), 3 * DAY);
+
+        assert_eq!(inactivity.snapshots, 1);
+        assert!((inactivity.days - 1.0).abs() < f64::EPSILON);
+    }
+
+    #[test]
+    fn inactivity_is_zero_without_usable_snapshots() {
+        let active = player(1000, 3);
+        let grown = history(&[(1, Some((900, 3)))]);
+        let missing = history(&[(1, None)]);
+        let later = history(&[(4, Some((1000, 3)))]);
+
+        for history in [&grown, &missing, &later, &History::default()] {
+            let inactivity = active.inactivity(history, None, 3 * DAY);
+            assert_eq!(inactivity.snapshots, 0);
+            assert!(inactivity.days.abs() < f64::EPSILON);
+        }
+    }
+
+    #[test]
+    fn time_round_trips_through_the_constraint_value() {
+        let time = 1_712_320_200;
+        assert_eq!(format_time(time), "2024-04-05T12:30:00Z");
+        assert_eq!(parse_time("2024-04-05T12:30:00Z"), Some(time));
+        assert_eq!(parse_time("2024-04-05T14:30:00+02:00"), Some(time));
+        assert_eq!(parse_time(" 1712320200 "), Some(time));
+        assert_eq!(parse_time("2024-04-05 12:30:00"), None);
+    }
+
+    #[test]
+    fn at_or_before_picks_the_newest_snapshot_up_to_the_time() {
+        let history = history(&[(1, None), (3, None)]);
+
+        assert_eq!(history.at_or_before(DAY - 1).map(|s| s.time), None);
+        assert_eq!(history.at_or_before(DAY).map(|s| s.time), Some(DAY));
+        assert_eq!(history.at_or_before(2 * DAY).map(|s| s.time), Some(DAY));
+        assert_eq!(history.at_or_before(3 * DAY).map(|s| s.time), Some(3 * DAY));
+        assert_eq!(
+            history.at_or_before(i64::MAX).map(|s| s.time),
+            Some(3 * DAY)
+        );
+    }
+
+    #[test]
+    fn only_the_newest_snapshot_of_each_day_is_kept() {
+        let times = vec![DAY + 10, DAY + 20, 2 * DAY, 2 * DAY + DAY - 1, 4 * DAY];
+        assert_eq!(
+            newest_per_day(times, |time| *time),
+            vec![DAY + 20, 2 * DAY + DAY - 1, 4 * DAY]
+        );
+
+        let days: Vec<i64> = (0..500).map(|day| day * DAY).collect();
+        let kept = newest_per_day(days, |time| *time);
+        assert_eq!(kept.len(), MAX_SNAPSHOTS);
+        assert_eq!(kept.last(), Some(&(499 * DAY)));
+    }
+}
@@ -2,26 +2,41 @@ use crate::constraint::ConstraintType;
 use crate::emptyconstraint::EmptyConstraint;
 use crate::emptyselection::EmptyTownSelection;
 use crate::selection::AndOr;
+use crate::spatial::SpatialIndex;
 
 #[cfg(not(target_arch = "wasm32"))]
 use crate::storage::{self, SavedDB};
-use crate::town::Town;
+use crate::town::{InactivePlayer, Town, TownDetails};
+use crate::travel::TravelSettings;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::view::preferences::AutoDeletePref;
 use anyhow::Context;
 use eframe::epaint::ahash::HashMap;
+use report::ParseReport;
 use serde::{Deserialize, Serialize};
+use source::WorldDataFile;
 use std::collections::hash_map::Entry;
 use std::collections::BTreeSet;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::{fs, thread};
+use strum::{EnumCount, IntoEnumIterator};
 #[cfg(not(target_arch = "wasm32"))]
 use time::{OffsetDateTime, UtcOffset};
 
 pub(crate) mod database;
+pub mod diff;
 pub mod download;
+pub mod history;
 mod offset_data;
 #[cfg(not(target_arch = "wasm32"))]
 mod parse_sqlite;
+pub mod report;
+pub mod source;
+
+/// the number of files we need to fetch from the API before we can construct the `DataTable`
+#[allow(clippy::cast_possible_truncation)]
+pub const API_FILE_COUNT: u8 = WorldDataFile::COUNT as u8;
 
 const DECAY: f32 = 0.9;
 const MIN_AGE: f32 = 0.1; // anything that was not touched `DECAY.powi(20)` times in a row should be removed from cache
@@ -46,6 +61,31 @@ pub struct APIResponse {
     alliances: Option<String>,
     towns: Option<String>,
     islands: Option<String>,
+    #[serde(default = "missing_in_old_save_files")]
+    conquers: Option<String>,
+    #[serde(default = "missing_in_old_save_files")]
+    player_kills_all: Option<String>,
+    #[serde(default = "missing_in_old_save_files")]
+    player_kills_att: Option<String>,
+    #[serde(default = "missing_in_old_save_files")]
+    player_kills_def: Option<String>,
+    #[serde(default = "missing_in_old_save_files")]
+    alliance_kills_all: Option<String>,
+    #[serde(default = "missing_in_old_save_files")]
+    alliance_kills_att: Option<String>,
+    #[serde(default = "missing_in_old_save_files")]
+    alliance_kills_def: Option<String>,
+
+    /// the files we gave up on fetching, and why
+    #[serde(skip)]
+    failed: Vec<(WorldDataFile, String)>,
+}
+
+/// Api responses saved by older versions do not contain every file we fetch today. We treat these
+/// files as present but empty, otherwise the old save files could never be completely loaded.
+#[allow(clippy::unnecessary_wraps)]
+fn missing_in_old_save_files() -> Option<String> {
+    Some(String::new())
 }
 
 impl APIResponse {
@@ -63,6 +103,14 @@ impl APIResponse {
                 alliances: None,
                 towns: None,
                 islands: None,
+                conquers: None,
+                player_kills_all: None,
+                player_kills_att: None,
+                player_kills_def: None,
+                alliance_kills_all: None,
+                alliance_kills_att: None,
+                alliance_kills_def: None,
+                failed: Vec::new(),
             }
         }
         #[cfg(target_arch = "wasm32")]
@@ -73,67 +121,128 @@ impl APIResponse {
                 alliances: None,
                 towns: None,
                 islands: None,
+                conquers: None,
+                player_kills_all: None,
+                player_kills_att: None,
+                player_kills_def: None,
+                alliance_kills_all: None,
+                alliance_kills_att: None,
+                alliance_kills_def: None,
+                failed: Vec::new(),
             }
         }
     }
 
+    fn file(&self, file: WorldDataFile) -> Option<&String> {
+        match file {
+            WorldDataFile::Players => self.players.as_ref(),
+            WorldDataFile::Alliances => self.alliances.as_ref(),
+            WorldDataFile::Towns => self.towns.as_ref(),
+            WorldDataFile::Islands => self.islands.as_ref(),
+            WorldDataFile::Conquers => self.conquers.as_ref(),
+            WorldDataFile::PlayerKillsAll => self.player_kills_all.as_ref(),
+            WorldDataFile::PlayerKillsAtt => self.player_kills_att.as_ref(),
+            WorldDataFile::PlayerKillsDef => self.player_kills_def.as_ref(),
+            WorldDataFile::AllianceKillsAll => self.alliance_kills_all.as_ref(),
+            WorldDataFile::AllianceKillsAtt => self.alliance_kills_att.as_ref(),
+            WorldDataFile::AllianceKillsDef => self.alliance_kills_def.as_ref(),
+        }
+    }
+
+    fn file_mut(&mut self, file: WorldDataFile) -> &mut Option<String> {
+        match file {
+            WorldDataFile::Players => &mut self.players,
+            WorldDataFile::Alliances => &mut self.alliances,
+            WorldDataFile::Towns => &mut self.towns,
+            WorldDataFile::Islands => &mut self.islands,
+            WorldDataFile::Conquers => &mut self.conquers,
+            WorldDataFile::PlayerKillsAll => &mut self.player_kills_all,
+            WorldDataFile::PlayerKillsAtt => &mut self.player_kills_att,
+            WorldDataFile::PlayerKillsDef => &mut self.player_kills_def,
+            WorldDataFile::AllianceKillsAll => &mut self.alliance_kills_all,
+            WorldDataFile::AllianceKillsAtt => &mut self.alliance_kills_att,
+            WorldDataFile::AllianceKillsDef => &mut self.alliance_kills_def,
+        }
+    }
+
+    /// store the content of a fetched file
+    pub fn set_file(&mut self, file: WorldDataFile, text: String) {
+        *self.file_mut(file) = Some(text);
+    }
+
+    /// remember that we gave up on fetching `file`
+    pub fn set_failed(&mut self, file: WorldDataFile, reason: String) {
+        self.failed.push((file, reason));
+    }
+
+    /// the files we gave up on fetching, and why
+    pub fn failures(&self) -> &[(WorldDataFile, String)] {
+        &self.failed
+    }
+
     /// how many of the fields are already populated/loaded?
     pub fn count_completed(&self) -> u8 {
         let mut re = 0;
-        if self.players.is_some() {
-            re += 1;
-        }
-        if self.alliances.is_some() {
-            re += 1;
-        }
-        if self.towns.is_some() {
-            re += 1;
-        }
-        if self.islands.is_some() {
-            re += 1;
+        for file in WorldDataFile::iter() {
+            if self.file(file).is_some() {
+                re += 1;
+            }
         }
         return re;
     }
 
     pub fn is_complete(&self) -> bool {
-        return self.count_completed() == 4;
+        return self.count_completed() == API_FILE_COUNT;
+    }
+
+    /// read a previously fetched API Response from disk
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn read_from_file(saved_db: &SavedDB) -> anyhow::Result<APIResponse> {
+        match saved_db.path.extension().and_then(|ext| ext.to_str()) {
+            Some("sqlite") => {
+                let path = saved_db.path.clone();
+                return parse_sqlite::sqlite_to_apiresponse(saved_db.clone()).with_context(|| {
+                    format!(
+                        "failed to parse the api response from the sqlite file saved at {path:?}"
+                    )
+                });
+            }
+            Some("apiresponse") => {
+                // read file content
+                let s = fs::read_to_string(&saved_db.path)
+                    .with_context(|| format!("failed to read {}", saved_db.path.display()))?;
+                // convert to api response
+                return serde_json::from_str(&s).with_context(|| {
+                    format!(
+                        "failes to parse api response from json saved at {:?}",
+                        saved_db.path
+                    )
+                });
+            }
+            Some(_) | None => {
+                anyhow::bail!("Can not load data from file {:?}", saved_db.path);
+            }
+        }
     }
 
     /// given a filepath, load the previously fetched API Response and put it into the `api_results` out variable. This is done so the UI doesn't hang.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn load_from_file(saved_db: SavedDB, api_results: Arc<Mutex<APIResponse>>) {
-        thread::spawn(move || {
-            // TODO: improve error handling
-            match saved_db.path.extension().and_then(|ext| ext.to_str()) {
-                Some("sqlite") => {
-                    let api_response = parse_sqlite::sqlite_to_apiresponse(saved_db).context("failed to parse the api response from the sqlite file saved at {path:?}")
-                        .unwrap();
-                    let mut guard = api_results.lock().unwrap();
-                    *guard = api_response;
-                }
-                Some("apiresponse") => {
-                    // read file content
-                    let s = fs::read_to_string(saved_db.path.clone()).unwrap();
-                    // convert to api response
-                    let api_response = serde_json::from_str(&s)
-                        .context(format!(
-                            "failes to parse api response from json saved at {:?}",
-                            saved_db.path
-                        ))
-                        .unwrap();
-                    let mut guard = api_results.lock().unwrap();
-                    *guard = api_response;
-                }
-                Some(_) | None => {
-                    eprintln!("Can not load data from file {:?}", saved_db.path);
-                }
+        thread::spawn(move || match Self::read_from_file(&saved_db) {
+            Ok(api_response) => {
+                let mut guard = api_results.lock().unwrap();
+                *guard = api_response;
+            }
+            Err(err) => {
+                eprintln!("{err:?}");
             }
         });
     }
 
-    /// save the api response to the file as defined in self.filename.
+    /// save the api response to the file as defined in self.filename. Afterwards the saved files
+    /// `auto_delete` wants gone are deleted.
     #[cfg(not(target_arch = "wasm32"))]
-    pub fn save_to_file(&self) {
+    pub fn save_to_file(&self, auto_delete: AutoDeletePref) {
         // only relevant on native. WASM does not get to save old api responses
         let opt_output_string = serde_json::to_string(self);
         let opt_filename = self.filename.clone();
@@ -153,6 +262,7 @@ impl APIResponse {
                         match fs::write(filename, output_string) {
                             Ok(()) => {
                                 println!("successfully saved api response to file");
+                                storage::apply_auto_delete(auto_delete);
                             }
                             Err(err) => {
                                 eprintln!("{msg}\n{err:?}");
@@ -175,7 +285,8 @@ impl APIResponse {
 pub enum Model {
     Uninitialized(Arc<Mutex<APIResponse>>),
     Loaded {
-        db: database::DataTable,
+        db: Box<database::DataTable>,
+        report: Arc<ParseReport>,
         cache_strings: HashMap<StringCacheKey, (f32, Arc<Vec<String>>)>,
         cache_towns: HashMap<TownCacheKey, (f32, Arc<Vec<Town>>)>,
     },
@@ -342,4 +453,111 @@ impl Model {
             Model::Loaded { db, .. } => Arc::new(db.get_all_towns()),
         }
     }
+
+    /// Build the model of a complete api response, with the default settings. Safe to call on a
+    /// background thread, the result can be sent to the ui thread.
+    pub fn for_world(api_response: APIResponse) -> Self {
+        let (db, report) = database::DataTable::create_for_world(api_response);
+        return Model::Loaded {
+            db: Box::new(db),
+            report: Arc::new(report),
+            cache_strings: HashMap::default(),
+            cache_towns: HashMap::default(),
+        };
+    }
+
+    /// should towns with an approximate position be excluded from coordinate based constraints?
+    /// Clears the caches, because the results of the constraints may change.
+    pub fn set_exclude_approximate(&mut self, exclude: bool) {
+        match self {
+            Model::Uninitialized(_) => { /*do nothing*/ }
+            Model::Loaded {
+                db,
+                cache_strings,
+                cache_towns,
+                ..
+            } => {
+                db.exclude_approximate = exclude;
+                cache_strings.clear();
+                cache_towns.clear();
+            }
+        }
+    }
+
+    pub fn set_travel_settings(&mut self, travel: TravelSettings) {
+        match self {
+            Model::Uninitialized(_) => { /*do nothing*/ }
+            Model::Loaded {
+                db,
+                cache_strings,
+                cache_towns,
+                ..
+            } => {
+                db.travel = Arc::new(travel);
+                cache_strings.clear();
+                cache_towns.clear();
+            }
+        }
+    }
+
+    pub fn get_town_index(&self) -> Arc<SpatialIndex> {
+        match self {
+            Model::Uninitialized(_) => Arc::new(SpatialIndex::default()),
+            Model::Loaded { db, .. } => Arc::clone(&db.index),
+        }
+    }
+
+    pub fn get_town_details(&self, town_id: u32) -> Option<TownDetails> {
+        match self {
+            Model::Uninitialized(_) => None,
+            Model::Loaded { db, .. } => db.get_town_details(town_id),
+        }
+    }
+
+    /// use the given saved snapshots for constraints that compare with the past. Clears the
+    /// caches, because the results of these constraints may change.
+    pub fn set_history(&mut self, history: Arc<history::History>) {
+        match self {
+            Model::Uninitialized(_) => { /*do nothing*/ }
+            Model::Loaded {
+                db,
+                cache_strings,
+                cache_towns,
+                ..
+            } => {
+                db.history = history;
+                cache_strings.clear();
+                cache_towns.clear();
+            }
+        }
+    }
+
+    /// everything that changed between the loaded data and `other`. None if nothing is loaded.
+    pub fn get_changes(&self, other: &diff::WorldState) -> Option<diff::ChangeSet> {
+        match self {
+            Model::Uninitialized(_) => None,
+            Model::Loaded { db, .. } => Some(diff::changes_between(&db.as_ref().into(), other)),
+        }
+    }
+
+    pub fn get_inactive_players(&self) -> Vec<InactivePlayer> {
+        match self {
+            Model::Uninitialized(_) => Vec::new(),
+            Model::Loaded { db, .. } => db.get_inactive_players(),
+        }
+    }
+
+    pub fn get_parse_report(&self) -> Arc<ParseReport> {
+        match self {
+            Model::Uninitialized(_) => Arc::new(ParseReport::default()),
+            Model::Loaded { report, .. } => Arc::clone(report),
+        }
+    }
+
+    pub fn get_conquest_count(&self) -> usize {
+        match self {
+            Model::Uninitialized(_) => 0,
+            Model::Loaded { db, .. } => db.conquests.len(),
+        }
+    }
 }
@@ -123,5 +123,14 @@ pub fn sqlite_to_apiresponse(db: SavedDB) -> anyhow::Result<APIResponse> {
         alliances: Some(alliances_txt),
         towns: Some(towns_txt),
         islands: Some(islands_txt),
+        // the old sqlite databases never stored the conquest history or the kill rankings
+        conquers: Some(String::new()),
+        player_kills_all: Some(String::new()),
+        player_kills_att: Some(String::new()),
+        player_kills_def: Some(String::new()),
+        alliance_kills_all: Some(String::new()),
+        alliance_kills_att: Some(String::new()),
+        alliance_kills_def: Some(String::new()),
+        failed: Vec::new(),
     })
 }
@@ -0,0 +1,58 @@
+use super::source::WorldDataFile;
+
+/// A line of an api file that could not be parsed and was skipped
+#[derive(Debug, Clone)]
+pub struct BadLine {
+    pub file: WorldDataFile,
+    /// 1-based, like in a text editor
+    pub line_number: usize,
+    pub line: String,
+    pub error: String,
+}
+
+/// Why the position of a town is only approximate
+#[derive(Debug, Clone)]
+pub enum FallbackReason {
+    /// there is no island at the coordinates of the town
+    MissingIsland { x: u16, y: u16 },
+    /// the island type has no slot with the number of the town
+    MissingOffset { island_type: u8, slot_number: u8 },
+}
+
+/// A town that was parsed, but for which a lookup failed. It is placed in the centre of its island instead.
+#[derive(Debug, Clone)]
+pub struct Fallback {
+    pub town_id: u32,
+    pub town_name: String,
+    pub reason: FallbackReason,
+}
+
+/// Everything that went wrong while turning an api response into a `DataTable`. Nothing in here
+/// stopped us from loading the data, but it is visible on the map.
+#[derive(Debug, Clone, Default)]
+pub struct ParseReport {
+    pub bad_lines: Vec<BadLine>,
+    pub fallbacks: Vec<Fallback>,
+}
+
+impl ParseReport {
+    pub fn is_empty(&self) -> bool {
+        self.bad_lines.is_empty() && self.fallbacks.is_empty()
+    }
+
+    /// remember that line `index` (0-based) of `file` could not be parsed
+    pub fn add_bad_line(
+        &mut self,
+        file: WorldDataFile,
+        index: usize,
+        line: &str,
+        error: &anyhow::Error,
+    ) {
+        self.bad_lines.push(BadLine {
+            file,
+            line_number: index + 1,
+            line: line.to_string(),
+            error: format!("{error:#}"),
+        });
+    }
+}
@@ -0,0 +1,433 @@
+//! Where the world data comes from. By default we fetch the plain `.txt` files from the grepolis
+//! servers (or the reflector on wasm), but the app can also be pointed at mirrors, the compressed
+//! json endpoints or a local directory containing previously downloaded files.
+
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use strum_macros::{EnumCount, EnumIter};
+
+/// base url of the official world data api, `{server}` is replaced by the server id
+pub const GREPOLIS_URL: &str = "https://{server}.grepolis.com/data/";
+/// base url of the reflector, which adds the CORS headers required by the browser
+pub const REFLECTOR_URL: &str = "https://reflector.gmap.turun.de/{server}/";
+
+/// The files we need from the world data api to build a `DataTable`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, EnumIter, EnumCount)]
+pub enum WorldDataFile {
+    Players,
+    Alliances,
+    Towns,
+    Islands,
+    Conquers,
+    PlayerKillsAll,
+    PlayerKillsAtt,
+    PlayerKillsDef,
+    AllianceKillsAll,
+    AllianceKillsAtt,
+    AllianceKillsDef,
+}
+
+impl WorldDataFile {
+    /// the name of the file as used by the `.txt` endpoints, without the extension
+    pub fn txt_stem(self) -> &'static str {
+        match self {
+            WorldDataFile::Players => "players",
+            WorldDataFile::Alliances => "alliances",
+            WorldDataFile::Towns => "towns",
+            WorldDataFile::Islands => "islands",
+            WorldDataFile::Conquers => "conquers",
+            WorldDataFile::PlayerKillsAll => "player_kills_all",
+            WorldDataFile::PlayerKillsAtt => "player_kills_att",
+            WorldDataFile::PlayerKillsDef => "player_kills_def",
+            WorldDataFile::AllianceKillsAll => "alliance_kills_all",
+            WorldDataFile::AllianceKillsAtt => "alliance_kills_att",
+            WorldDataFile::AllianceKillsDef => "alliance_kills_def",
+        }
+    }
+
+    /// the name of the file as used by the `.json` endpoints, without the extension. The json
+    /// api bundles the three kill rankings into one file.
+    pub fn json_stem(self) -> &'static str {
+        match self {
+            WorldDataFile::PlayerKillsAll
+            | WorldDataFile::PlayerKillsAtt
+            | WorldDataFile::PlayerKillsDef => "player_kills",
+            WorldDataFile::AllianceKillsAll
+            | WorldDataFile::AllianceKillsAtt
+            | WorldDataFile::AllianceKillsDef => "alliance_kills",
+            _ => self.txt_stem(),
+        }
+    }
+
+    /// For the kill rankings: the key of the ranking inside a json record
+    fn json_kill_key(self) -> Option<&'static str> {
+        match self {
+            WorldDataFile::PlayerKillsAll | WorldDataFile::AllianceKillsAll => Some("all"),
+            WorldDataFile::PlayerKillsAtt | WorldDataFile::AllianceKillsAtt => Some("att"),
+            WorldDataFile::PlayerKillsDef | WorldDataFile::AllianceKillsDef => Some("def"),
+            _ => None,
+        }
+    }
+
+    /// the json keys in the order of the columns of the corresponding `.txt` file.
+    /// The api documentation does not mention the island ressources, their keys are our best guess.
+    fn json_columns(self) -> &'static [&'static str] {
+        match self {
+            WorldDataFile::Players => &["id", "name", "alliance_id", "points", "rank", "towns"],
+            WorldDataFile::Alliances => &["id", "name", "points", "towns", "members", "rank"],
+            WorldDataFile::Towns => &[
+                "id",
+                "player_id",
+                "name",
+                "island_x",
+                "island_y",
+                "number_on_island",
+                "points",
+            ],
+            WorldDataFile::Islands => &[
+                "id",
+                "x",
+                "y",
+                "island_id",
+                "available_towns",
+                "resource_plus",
+                "resource_minus",
+            ],
+            WorldDataFile::Conquers => &[
+                "town_id",
+                "time",
+                "new_player_id",
+                "old_player_id",
+                "new_ally_id",
+                "old_ally_id",
+                "town_points",
+            ],
+            WorldDataFile::PlayerKillsAll
+            | WorldDataFile::PlayerKillsAtt
+            | WorldDataFile::PlayerKillsDef => &["rank", "player_id", "points"],
+            WorldDataFile::AllianceKillsAll
+            | WorldDataFile::AllianceKillsAtt
+            | WorldDataFile::AllianceKillsDef => &["rank", "alliance_id", "points"],
+        }
+    }
+}
+
+/// Why a file could not be fetched
+#[derive(Debug, Clone)]
+pub enum FetchError {
+    /// we never got an answer, e.g. no internet connection or a timeout
+    Network(String),
+    /// the server answered, but with an error status code
+    Status { status: u16, status_text: String },
+    /// we got the file, but could not make sense of its content
+    Content(String),
+    /// the file could not be read from disk
+    #[cfg(not(target_arch = "wasm32"))]
+    Io(String),
+}
+
+impl FetchError {
+    /// is there any hope that trying again will help?
+    pub fn is_transient(&self) -> bool {
+        match self {
+            FetchError::Network(_) => true,
+            FetchError::Status { status, .. } => *status == 429 || *status >= 500,
+            FetchError::Content(_) => false,
+            #[cfg(not(target_arch = "wasm32"))]
+            FetchError::Io(_) => false,
+        }
+    }
+}
+
+impl std::fmt::Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FetchError::Network(reason) | FetchError::Content(reason) => write!(f, "{reason}"),
+            #[cfg(not(target_arch = "wasm32"))]
+            FetchError::Io(reason) => write!(f, "{reason}"),
+            FetchError::Status {
+                status,
+                status_text,
+            } => write!(f, "HTTP {status} {status_text}"),
+        }
+    }
+}
+
+/// Called exactly once per requested file, with the content of the file in the `.txt` format
+pub type OnFileFetched = Box<dyn FnOnce(Result<String, FetchError>) + Send>;
+
+/// A place we can get the world data from. Implementations must hand the content to `on_done`
+/// in the format of the `.txt` endpoints, that is what the parser in `download.rs` expects.
+pub trait WorldDataSource: Send + Sync {
+    fn fetch(&self, server_id: &str, file: WorldDataFile, on_done: OnFileFetched);
+}
+
+/// turn the result of an ehttp request into the body of the response, if the request was successful
+fn response_bytes(
+    url: &str,
+    response: Result<ehttp::Response, String>,
+) -> Result<Vec<u8>, FetchError> {
+    let response =
+        response.map_err(|err| FetchError::Network(format!("Failed to fetch {url}: {err}")))?;
+    if !response.ok {
+        return Err(FetchError::Status {
+            status: response.status,
+            status_text: response.status_text,
+        });
+    }
+    return Ok(response.bytes);
+}
+
+/// The plain `.txt` endpoints of the grepolis servers or any mirror with the same layout
+pub struct TxtEndpoint {
+    base_url: String,
+}
+
+impl TxtEndpoint {
+    /// `base_url` may contain `{server}`, which is replaced by the requested server id
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+        }
+    }
+}
+
+impl WorldDataSource for TxtEndpoint {
+    fn fetch(&self, server_id: &str, file: WorldDataFile, on_done: OnFileFetched) {
+        let url = format!(
+            "{}{}.txt",
+            self.base_url.replace("{server}", server_id),
+            file.txt_stem()
+        );
+        ehttp::fetch(ehttp::Request::get(&url), move |response| {
+            let text = response_bytes(&url, response).and_then(|bytes| {
+                String::from_utf8(bytes)
+                    .map_err(|err| FetchError::Content(format!("{url} is not valid utf8: {err}")))
+            });
+            on_done(text);
+        });
+    }
+}
+
+/// The reflector forwards the `.txt` endpoints and adds CORS headers, so it works in the browser
+pub struct Reflector;
+
+impl WorldDataSource for Reflector {
+    fn fetch(&self, server_id: &str, file: WorldDataFile, on_done: OnFileFetched) {
+        TxtEndpoint::new(REFLECTOR_URL).fetch(server_id, file, on_done);
+    }
+}
+
+/// the files waiting for the download of one `.json.gz` file, by url
+type PendingJsonDownloads = Arc<Mutex<HashMap<String, Vec<(WorldDataFile, OnFileFetched)>>>>;
+
+/// The compressed `.json.gz` endpoints of the grepolis servers or any mirror with the same layout.
+/// The json is converted into the `.txt` format after downloading.
+pub struct JsonGzEndpoint {
+    base_url: String,
+    /// The kill rankings share one json file. Files that ask for a json file that is already being
+    /// downloaded wait for that download instead of starting another one.
+    pending: PendingJsonDownloads,
+}
+
+impl JsonGzEndpoint {
+    /// `base_url` may contain `{server}`, which is replaced by the requested server id
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            base_url: base_url.to_string(),
+            pending: Arc::default(),
+        }
+    }
+}
+
+impl WorldDataSource for JsonGzEndpoint {
+    fn fetch(&self, server_id: &str, file: WorldDataFile, on_done: OnFileFetched) {
+        let url = format!(
+            "{}{}.json.gz",
+            self.base_url.replace("{server}", server_id),
+            file.json_stem()
+        );
+        {
+            let mut pending = self.pending.lock().unwrap();
+            if let Some(waiting) = pending.get_mut(&url) {
+                waiting.push((file, on_done));
+                return;
+            }
+            pending.insert(url.clone(), vec![(file, on_done)]);
+        }
+
+        let pending = Arc::clone(&self.pending);
+        ehttp::fetch(ehttp::Request::get(&url), move |response| {
+            let waiting = pending.lock().unwrap().remove(&url).unwrap_or_default();
+            let json = response_bytes(&url, response).and_then(|bytes| {
+                parse_json_gz(&bytes).map_err(|err| FetchError::Content(format!("{url}: {err:#}")))
+            });
+            for (file, on_done) in waiting {
+                let text = json.clone().and_then(|json| {
+                    json_to_txt(file, &json)
+                        .map_err(|err| FetchError::Content(format!("{url}: {err:#}")))
+                });
+                on_done(text);
+            }
+        });
+    }
+}
+
+/// A directory containing `.txt` files, e.g. a dump of a world or a test fixture
+#[cfg(not(target_arch = "wasm32"))]
+pub struct LocalDirectory {
+    path: PathBuf,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl LocalDirectory {
+    pub fn new(path: PathBuf) -> Self {
+        Self { path }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WorldDataSource for LocalDirectory {
+    fn fetch(&self, _server_id: &str, file: WorldDataFile, on_done: OnFileFetched) {
+        let path = self.path.join(format!("{}.txt", file.txt_stem()));
+        // read in a different thread, just like the network requests. Otherwise we hang the UI on slow disks.
+        let _handle = std::thread::spawn(move || {
+            let text = std::fs::read_to_string(&path)
+                .map_err(|err| FetchError::Io(format!("Failed to read {}: {err}", path.display())));
+            on_done(text);
+        });
+    }
+}
+
+/// decompress and parse a `.json.gz` file
+fn parse_json_gz(bytes: &[u8]) -> anyhow::Result<Arc<serde_json::Value>> {
+    let mut json_text = String::new();
+    let _bytes_read = flate2::read::GzDecoder::new(bytes)
+        .read_to_string(&mut json_text)
+        .context("Failed to decompress the response")?;
+    let json = serde_json::from_str(&json_text).context("Failed to parse the response as json")?;
+    return Ok(Arc::new(json));
+}
+
+/// convert the records of a `.json` file into lines of the `.txt` format of `file`
+fn json_to_txt(file: WorldDataFile, json: &serde_json::Value) -> anyhow::Result<String> {
+    let records: Vec<&serde_json::Value> = match json {
+        serde_json::Value::Array(records) => records.iter().collect(),
+        serde_json::Value::Object(records) => records.values().collect(),
+        _ => anyhow::bail!("Expected a list of records, got {json}"),
+    };
+
+    let mut lines = Vec::with_capacity(records.len());
+    for record in records {
+        // each record of the kill files contains all three rankings
+        let record = match file.json_kill_key() {
+            Some(key) => match record.get(key) {
+                Some(ranking) => ranking,
+                None => continue,
+            },
+            None => record,
+        };
+        let values: Vec<String> = file
+            .json_columns()
+            .iter()
+            .map(|column| match record.get(column) {
+                None | Some(serde_json::Value::Null) => String::new(),
+                // names are url encoded in the txt files
+                Some(serde_json::Value::String(text)) => {
+                    form_urlencoded::byte_serialize(text.as_bytes()).collect()
+                }
+                Some(value) => value.to_string(),
+            })
+            .collect();
+        lines.push(values.join(","));
+    }
+    return Ok(lines.join("\n"));
+}
+
+/// The user facing choice of data source. Saved as part of the app config.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DataSourceConfig {
+    /// `.txt` endpoints at the given base url
+    Txt(String),
+    /// `.json.gz` endpoints at the given base url. Not offered in the menu until the layout of the
+    /// json files is verified against a real response, see `WorldDataFile::json_columns`.
+    JsonGz(String),
+    Reflector,
+    #[cfg(not(target_arch = "wasm32"))]
+    LocalDirectory(PathBuf),
+}
+
+impl Default for DataSourceConfig {
+    fn default() -> Self {
+        // the browser does not allow us to fetch from the grepolis servers directly
+        #[cfg(target_arch = "wasm32")]
+        return DataSourceConfig::Reflector;
+        #[cfg(not(target_arch = "wasm32"))]
+        return DataSourceConfig::Txt(GREPOLIS_URL.to_string());
+    }
+}
+
+impl DataSourceConfig {
+    pub fn source(&self) -> Arc<dyn WorldDataSource> {
+        match self {
+            DataSourceConfig::Txt(base_url) => Arc::new(TxtEndpoint::new(base_url)),
+            DataSourceConfig::JsonGz(base_url) => Arc::new(JsonGzEndpoint::new(base_url)),
+            DataSourceConfig::Reflector => Arc::new(Reflector),
+            #[cfg(not(target_arch = "wasm32"))]
+            DataSourceConfig::LocalDirectory(path) => Arc::new(LocalDirectory::new(path.clone())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    fn gzip(text: &str) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(text.as_bytes()).unwrap();
+        return encoder.finish().unwrap();
+    }
+
+    fn json_gz_to_txt(file: WorldDataFile, bytes: &[u8]) -> anyhow::Result<String> {
+        let json = parse_json_gz(bytes)?;
+        return json_to_txt(file, &json);
+    }
+
+    #[test]
+    fn json_gz_to_txt_orders_columns_and_encodes_names() {
+        let json = r#"[
+            {"name": "Foo Bar", "id": 1, "alliance_id": null, "points": 10, "rank": 2, "towns": 1},
+            {"id": 2, "name": "a,b", "alliance_id": 5, "points": 20, "rank": 1, "towns": 3}
+        ]"#;
+        let text = json_gz_to_txt(WorldDataFile::Players, &gzip(json)).unwrap();
+        assert_eq!(text, "1,Foo+Bar,,10,2,1\n2,a%2Cb,5,20,1,3");
+    }
+
+    #[test]
+    fn json_gz_to_txt_picks_the_kill_ranking() {
+        let json = r#"{
+            "0": {"all": {"rank": 1, "player_id": 7, "points": 500}, "att": {"rank": 3, "player_id": 7, "points": 100}},
+            "1": {"all": {"rank": 2, "player_id": 8, "points": 300}}
+        }"#;
+        let bytes = gzip(json);
+        let all = json_gz_to_txt(WorldDataFile::PlayerKillsAll, &bytes).unwrap();
+        assert_eq!(all, "1,7,500\n2,8,300");
+        // players without attack points have no attack ranking
+        let att = json_gz_to_txt(WorldDataFile::PlayerKillsAtt, &bytes).unwrap();
+        assert_eq!(att, "3,7,100");
+    }
+
+    #[test]
+    fn json_gz_to_txt_rejects_bad_input() {
+        assert!(json_gz_to_txt(WorldDataFile::Players, b"not gzip").is_err());
+        assert!(json_gz_to_txt(WorldDataFile::Players, &gzip("not json")).is_err());
+        assert!(json_gz_to_txt(WorldDataFile::Players, &gzip("42")).is_err());
+    }
+}
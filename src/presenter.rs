@@ -1,13 +1,23 @@
 use anyhow::anyhow;
-use eframe::epaint::ahash::HashMap;
 
 use crate::emptyconstraint::EmptyConstraint;
 use crate::emptyselection::EmptyTownSelection;
 use crate::model::database::DataTable;
-use crate::model::{APIResponse, Model};
-use crate::town::Town;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::model::diff::{ChangeSet, WorldState};
+use crate::model::history::History;
+use crate::model::report::ParseReport;
+use crate::model::source::{DataSourceConfig, WorldDataFile};
+use crate::model::{APIResponse, Model, API_FILE_COUNT};
+use crate::spatial::SpatialIndex;
+use crate::town::{InactivePlayer, Town, TownDetails};
+use crate::travel::TravelSettings;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::view::preferences::AutoDeletePref;
 use crate::view::preferences::CacheSize;
 use std::sync::{Arc, Mutex};
+#[cfg(not(target_arch = "wasm32"))]
+use std::thread;
 
 #[cfg(not(target_arch = "wasm32"))]
 use crate::storage::{self, SavedDB};
@@ -17,11 +27,68 @@ pub enum PresenterReady {
     AlwaysHasBeen,
     WaitingForAPI,
     NewlyReady,
+    /// the saved snapshots of the world were read after the data was loaded. Constraints that
+    /// compare with the past may have a different result now.
+    HistoryLoaded,
+    /// some files could not be fetched, contains the files and the reason they failed
+    DownloadFailed(Vec<(WorldDataFile, String)>),
+}
+
+/// the snapshot to compare with, once it is read from disk and parsed
+#[cfg(not(target_arch = "wasm32"))]
+type ReadComparison = Arc<Mutex<Option<anyhow::Result<WorldState>>>>;
+
+/// most snapshots that are preloaded for a playback, each of them is a whole model
+#[cfg(not(target_arch = "wasm32"))]
+pub const MAX_PLAYBACK_SNAPSHOTS: usize = 30;
+
+/// a snapshot read from disk and turned into a model, with the time it was taken. None if the
+/// file could not be read.
+#[cfg(not(target_arch = "wasm32"))]
+type ReadSnapshot = (i64, Option<Model>);
+
+/// Snapshots of one server, turned into models ahead of time so they can be shown one after
+/// another without a loading screen.
+#[cfg(not(target_arch = "wasm32"))]
+struct Playback {
+    server: String,
+    /// number of snapshots to preload
+    total: usize,
+    /// the snapshots turned into models so far, filled in the background
+    read: Arc<Mutex<Vec<ReadSnapshot>>>,
+    /// how many snapshots were taken from `read`
+    converted: usize,
+    /// the snapshots that are ready to be shown, with the time they were taken
+    frames: Vec<(i64, Model)>,
+    /// The frame that is swapped into `Presenter::model`. The model it replaced waits in the slot
+    /// of the frame in the meantime.
+    shown: Option<usize>,
 }
 
 pub struct Presenter {
     model: Model,
     max_cache_size: CacheSize,
+    data_source: DataSourceConfig,
+    exclude_approximate: bool,
+    travel: TravelSettings,
+    #[cfg(not(target_arch = "wasm32"))]
+    save_api_responses: bool,
+    /// which saved api responses are deleted after saving a new one
+    #[cfg(not(target_arch = "wasm32"))]
+    auto_delete: AutoDeletePref,
+    /// the snapshot the loaded data is compared with, while it is being read from disk
+    #[cfg(not(target_arch = "wasm32"))]
+    comparison: Option<ReadComparison>,
+    /// the saved snapshots of the last loaded server, kept to not read them again on every load
+    history: Arc<History>,
+    /// the saved snapshots of the loaded server, while they are being read from disk
+    #[cfg(not(target_arch = "wasm32"))]
+    history_loading: Option<Arc<Mutex<Option<History>>>>,
+    /// the server whose saved snapshots are read the next time they are needed
+    #[cfg(not(target_arch = "wasm32"))]
+    history_outdated: Option<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    playback: Option<Playback>,
 }
 
 impl Default for Presenter {
@@ -29,19 +96,41 @@ impl Default for Presenter {
         Self {
             model: Model::Uninitialized(Arc::new(Mutex::new(APIResponse::new(String::new())))),
             max_cache_size: CacheSize::Normal,
+            data_source: DataSourceConfig::default(),
+            exclude_approximate: false,
+            travel: TravelSettings::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            save_api_responses: true,
+            #[cfg(not(target_arch = "wasm32"))]
+            auto_delete: AutoDeletePref::Eternity,
+            #[cfg(not(target_arch = "wasm32"))]
+            comparison: None,
+            history: Arc::new(History::default()),
+            #[cfg(not(target_arch = "wasm32"))]
+            history_loading: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            history_outdated: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            playback: None,
         }
     }
 }
 
 impl Presenter {
     /// Return all possible selection names that can be used in the `DropDownValues` for the
-    /// Constraint. Returns None if the Constraint is not a IN/NOT IN type of constraint. In which
-    /// case it is up to the caller to determine which drop down values are appropriate.
+    /// Constraint. Returns None if the Constraint does not reference another selection (IN/NOT IN or
+    /// distance to a selection). In which case it is up to the caller to determine which drop down
+    /// values are appropriate.
     fn possible_ddv_selections_or(
         constraint: &EmptyConstraint,
         selection: &EmptyTownSelection,
         all_selections: &[EmptyTownSelection],
     ) -> Option<Arc<Vec<String>>> {
+        // distance constraints have the radius in front of the selection name, keep it
+        let prefix = constraint
+            .distance_value()
+            .map(|(radius, _reference)| format!("{radius} "))
+            .unwrap_or_default();
         constraint
             .referenced_selection()
             .map(|_referenced_selection| {
@@ -59,6 +148,7 @@ impl Presenter {
                             });
                             !test_selection.contains_circular_reference(all_selections)
                         })
+                        .map(|name| format!("{prefix}{name}"))
                         .collect(),
                 )
             })
@@ -68,9 +158,13 @@ impl Presenter {
     /// This is deliberately its own method, because the self.model = `Model::Uninit` needs to be triggered before the
     /// normal message processing.
     pub fn load_server(&mut self, server: String) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.playback = None;
+        }
         let api_response = Arc::new(Mutex::new(APIResponse::new(server)));
         self.model = Model::Uninitialized(Arc::clone(&api_response));
-        DataTable::get_api_results(&Arc::clone(&api_response));
+        DataTable::get_api_results(&Arc::clone(&api_response), &self.data_source.source());
     }
 
     /// triggers the server loading, which is handled asynchronously
@@ -78,11 +172,59 @@ impl Presenter {
     /// normal message processing.
     #[cfg(not(target_arch = "wasm32"))]
     pub fn load_server_from_file(&mut self, saved_db: SavedDB) {
+        self.playback = None;
         let api_response = Arc::new(Mutex::new(APIResponse::new(String::new())));
         self.model = Model::Uninitialized(Arc::clone(&api_response));
         APIResponse::load_from_file(saved_db, api_response);
     }
 
+    /// load an api response that was already read, e.g. with `APIResponse::read_from_file`. It is
+    /// turned into a model on the next call to `ready_for_requests`.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn load_api_response(&mut self, api_response: APIResponse) {
+        self.playback = None;
+        self.model = Model::Uninitialized(Arc::new(Mutex::new(api_response)));
+    }
+
+    /// start reading and parsing a second snapshot in the background, to compare the loaded data
+    /// with it. Replaces any comparison that has not finished yet.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn compare_with_file(&mut self, saved_db: SavedDB) {
+        let result = Arc::new(Mutex::new(None));
+        self.comparison = Some(Arc::clone(&result));
+        thread::spawn(move || {
+            let state = APIResponse::read_from_file(&saved_db).and_then(|api_response| {
+                if !api_response.is_complete() {
+                    return Err(anyhow!(
+                        "The snapshot does not contain all files of the world data"
+                    ));
+                }
+                let (db, _report) = DataTable::create_for_world(api_response);
+                return Ok(WorldState::from(&db));
+            });
+            *result.lock().unwrap() = Some(state);
+        });
+    }
+
+    /// Returns the changes between the loaded data and the snapshot given to `compare_with_file`
+    /// once both are ready, or why the snapshot can not be compared with. Returns None while
+    /// either one is still loading or if there is no comparison going on.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn poll_comparison(&mut self) -> Option<anyhow::Result<Arc<ChangeSet>>> {
+        if matches!(self.model, Model::Uninitialized(_)) {
+            return None;
+        }
+        let read = self.comparison.as_ref()?.lock().unwrap().take()?;
+        self.comparison = None;
+        return match read {
+            Ok(other) => self
+                .model
+                .get_changes(&other)
+                .map(|changes| Ok(Arc::new(changes))),
+            Err(err) => Some(Err(err)),
+        };
+    }
+
     /// return a list of all towns in the current model with no constraints applied.
     pub fn get_all_towns(&mut self) -> Arc<Vec<Town>> {
         self.model.get_all_towns()
@@ -93,12 +235,48 @@ impl Presenter {
         self.model.get_ghost_towns()
     }
 
+    /// return the positions of all towns, in the same order as `get_all_towns`
+    pub fn get_town_index(&self) -> Arc<SpatialIndex> {
+        self.model.get_town_index()
+    }
+
+    /// return the town with the given id along with its island, player and alliance. None if
+    /// there is no such town in the current model.
+    pub fn get_town_details(&self, town_id: u32) -> Option<TownDetails> {
+        self.model.get_town_details(town_id)
+    }
+
+    /// Return every player with towns in the current model and how long they have been inactive.
+    /// Until the saved snapshots are read, nobody is known to be inactive.
+    pub fn get_inactive_players(&mut self) -> Vec<InactivePlayer> {
+        self.request_history();
+        self.model.get_inactive_players()
+    }
+
+    /// return everything that went wrong while parsing the data of the current model
+    pub fn get_parse_report(&self) -> Arc<ParseReport> {
+        self.model.get_parse_report()
+    }
+
+    /// return the number of conquests in the current model
+    pub fn get_conquest_count(&self) -> usize {
+        self.model.get_conquest_count()
+    }
+
     /// return a list of all the towns that match a given selection with all its constraints.
     pub fn towns_for_selection(
         &mut self,
         selection: &EmptyTownSelection,
         all_selections: &[EmptyTownSelection],
     ) -> anyhow::Result<Arc<Vec<Town>>> {
+        // a referenced selection may compare with the past as well
+        if all_selections
+            .iter()
+            .chain([selection])
+            .any(EmptyTownSelection::uses_history)
+        {
+            self.request_history();
+        }
         let filled_constraints: Vec<EmptyConstraint> = selection
             .constraints
             .iter()
@@ -119,6 +297,9 @@ impl Presenter {
         selection: &EmptyTownSelection,
         all_selections: &[EmptyTownSelection],
     ) -> anyhow::Result<Arc<Vec<String>>> {
+        if constraint.constraint_type.uses_history() {
+            self.request_history();
+        }
         // a list of filled constraints. For each one, filter the ddv list by all _other_ filled constratins
         let constraints_filled: Vec<EmptyConstraint> = selection
             .constraints
@@ -169,11 +350,13 @@ impl Presenter {
         }
     }
 
-    /// returns how many of the api requests already completed. i.e. 1/4 -> 0.25
+    /// returns how many of the api requests already completed. i.e. 1/5 -> 0.2
     /// if model is not in the loading state we return a flat 1.0
     pub fn loading_progress(&self) -> f32 {
         match &self.model {
-            Model::Uninitialized(arc) => f32::from(arc.lock().unwrap().count_completed()) / 4.0,
+            Model::Uninitialized(arc) => {
+                f32::from(arc.lock().unwrap().count_completed()) / f32::from(API_FILE_COUNT)
+            }
             Model::Loaded { .. } => 1.0,
         }
     }
@@ -186,30 +369,358 @@ impl Presenter {
             Model::Uninitialized(api_response) => {
                 let api_response = api_response.lock().unwrap().clone();
                 if !api_response.is_complete() {
+                    // only report failures once every file is either fetched or given up on.
+                    // Otherwise the user would see the list of failed files grow over time.
+                    let failures = api_response.failures();
+                    let settled = usize::from(api_response.count_completed()) + failures.len();
+                    if !failures.is_empty() && settled >= usize::from(API_FILE_COUNT) {
+                        return Ok(PresenterReady::DownloadFailed(failures.to_vec()));
+                    }
                     return Ok(PresenterReady::WaitingForAPI);
                 }
 
                 #[cfg(not(target_arch = "wasm32"))]
-                api_response.save_to_file();
+                if self.save_api_responses {
+                    api_response.save_to_file(self.auto_delete);
+                }
 
-                let db = DataTable::create_for_world(api_response);
-                self.model = Model::Loaded {
-                    db,
-                    cache_strings: HashMap::default(),
-                    cache_towns: HashMap::default(),
-                };
+                // new snapshots may have been saved since the history was read
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    self.history_outdated = Some(api_response.for_server.clone());
+                    // snapshots that are still being read may be of another server
+                    self.history_loading = None;
+                }
+
+                self.model = self.loaded_model(api_response);
                 return Ok(PresenterReady::NewlyReady);
             }
-            Model::Loaded { .. } => return Ok(PresenterReady::AlwaysHasBeen),
+            Model::Loaded { .. } => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if self.history_loaded() {
+                    return Ok(PresenterReady::HistoryLoaded);
+                }
+                return Ok(PresenterReady::AlwaysHasBeen);
+            }
+        }
+    }
+
+    /// turn a complete api response into a model with the current settings
+    fn loaded_model(&self, api_response: APIResponse) -> Model {
+        let server = api_response.for_server.clone();
+        let mut model = Model::for_world(api_response);
+        self.apply_settings(&mut model, &server);
+        return model;
+    }
+
+    /// hand the current settings to a model of `server` that was just built
+    fn apply_settings(&self, model: &mut Model, server: &str) {
+        model.set_exclude_approximate(self.exclude_approximate);
+        model.set_travel_settings(self.travel.clone());
+        // until the history is read anew, the snapshots we know of are better than nothing
+        if self.history.server == server {
+            model.set_history(Arc::clone(&self.history));
+        }
+    }
+
+    /// the model that is shown and the models of all preloaded snapshots
+    fn all_models(&mut self) -> impl Iterator<Item = &mut Model> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let frames = self
+            .playback
+            .iter_mut()
+            .flat_map(|playback| playback.frames.iter_mut().map(|(_time, model)| model));
+        #[cfg(target_arch = "wasm32")]
+        let frames = std::iter::empty();
+        return std::iter::once(&mut self.model).chain(frames);
+    }
+
+    /// Start reading `saved_dbs` in the background, to show them one after another with
+    /// `show_playback_frame`. Only the first `MAX_PLAYBACK_SNAPSHOTS` are read. Replaces any
+    /// previous playback.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn preload_playback(&mut self, mut saved_dbs: Vec<SavedDB>) {
+        self.stop_playback();
+        saved_dbs.truncate(MAX_PLAYBACK_SNAPSHOTS);
+        let read = Arc::new(Mutex::new(Vec::with_capacity(saved_dbs.len())));
+        self.playback = Some(Playback {
+            server: saved_dbs
+                .first()
+                .map(|saved_db| saved_db.server_str.clone())
+                .unwrap_or_default(),
+            total: saved_dbs.len(),
+            read: Arc::clone(&read),
+            converted: 0,
+            frames: Vec::new(),
+            shown: None,
+        });
+        thread::spawn(move || {
+            for saved_db in saved_dbs {
+                let model = match APIResponse::read_from_file(&saved_db) {
+                    Ok(api_response) => Some(Model::for_world(api_response)),
+                    Err(err) => {
+                        eprintln!("Skipping {saved_db} in the playback: {err:?}");
+                        None
+                    }
+                };
+                let time = saved_db.date.unix_timestamp();
+                read.lock().unwrap().push((time, model));
+            }
+        });
+    }
+
+    /// Take the snapshots that were prepared in the background for the playback. Returns the times
+    /// of the snapshots that can be shown, oldest first, and the number of snapshots that are
+    /// still being prepared. None if there is no playback.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn poll_playback(&mut self) -> Option<(Vec<i64>, usize)> {
+        let playback = self.playback.as_ref()?;
+        let ready: Vec<ReadSnapshot> = playback.read.lock().unwrap().drain(..).collect();
+        let handled = ready.len();
+        let server = playback.server.clone();
+        let mut frames = Vec::with_capacity(ready.len());
+        for (time, opt_model) in ready {
+            if let Some(mut model) = opt_model {
+                // the settings may have changed since the playback was started
+                self.apply_settings(&mut model, &server);
+                frames.push((time, model));
+            }
+        }
+
+        let playback = self.playback.as_mut()?;
+        playback.converted += handled;
+        playback.frames.append(&mut frames);
+        let times = playback.frames.iter().map(|(time, _model)| *time).collect();
+        return Some((times, playback.total - playback.converted));
+    }
+
+    /// Show the preloaded snapshot at `index` instead of the loaded data. Returns the time the
+    /// snapshot was taken, or None if there is no such snapshot.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn show_playback_frame(&mut self, index: usize) -> Option<i64> {
+        let playback = self.playback.as_mut()?;
+        if index >= playback.frames.len() {
+            return None;
+        }
+        // put the model that is shown back into its slot first, then swap in the new one
+        if let Some(shown) = playback.shown.take() {
+            std::mem::swap(&mut self.model, &mut playback.frames[shown].1);
+        }
+        std::mem::swap(&mut self.model, &mut playback.frames[index].1);
+        playback.shown = Some(index);
+        return Some(playback.frames[index].0);
+    }
+
+    /// show the loaded data again and forget the preloaded snapshots
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn stop_playback(&mut self) {
+        if let Some(mut playback) = self.playback.take() {
+            if let Some(shown) = playback.shown {
+                std::mem::swap(&mut self.model, &mut playback.frames[shown].1);
+            }
+        }
+    }
+
+    /// Start reading the saved snapshots of the loaded server, unless they are read already.
+    /// `PresenterReady::HistoryLoaded` tells when they are there.
+    pub fn request_history(&mut self) {
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(server) = self.history_outdated.take() {
+            self.load_history(server);
+        }
+    }
+
+    /// read the saved snapshots of `server` in the background
+    #[cfg(not(target_arch = "wasm32"))]
+    fn load_history(&mut self, server: String) {
+        let result = Arc::new(Mutex::new(None));
+        self.history_loading = Some(Arc::clone(&result));
+        let known = Arc::clone(&self.history);
+        thread::spawn(move || {
+            let history = History::load(&server, &known);
+            *result.lock().unwrap() = Some(history);
+        });
+    }
+
+    /// Hand the saved snapshots to the model once they are read. Returns true exactly once after
+    /// each load, even if the snapshots did not change.
+    #[cfg(not(target_arch = "wasm32"))]
+    fn history_loaded(&mut self) -> bool {
+        let Some(history) = self
+            .history_loading
+            .as_ref()
+            .and_then(|result| result.lock().unwrap().take())
+        else {
+            return false;
+        };
+        self.history_loading = None;
+        if !history.same_snapshots(&self.history) {
+            self.history = Arc::new(history);
+            let history = Arc::clone(&self.history);
+            for model in self.all_models() {
+                model.set_history(Arc::clone(&history));
+            }
         }
+        return true;
     }
 
     pub fn set_max_cache_size(&mut self, cache_size: CacheSize) {
         self.max_cache_size = cache_size;
     }
 
+    /// set where the world data is fetched from. Takes effect the next time a server is loaded.
+    pub fn set_data_source(&mut self, data_source: DataSourceConfig) {
+        self.data_source = data_source;
+    }
+
+    /// should freshly fetched api responses be saved to disk? Defaults to true.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_save_api_responses(&mut self, save: bool) {
+        self.save_api_responses = save;
+    }
+
+    /// how long saved api responses are kept. Applied whenever a new one is saved.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_auto_delete(&mut self, auto_delete: AutoDeletePref) {
+        self.auto_delete = auto_delete;
+    }
+
+    /// exclude towns with an approximate position from coordinate based constraints
+    pub fn set_exclude_approximate(&mut self, exclude: bool) {
+        self.exclude_approximate = exclude;
+        for model in self.all_models() {
+            model.set_exclude_approximate(exclude);
+        }
+    }
+
+    /// the unit and modifiers travel time constraints are evaluated with
+    pub fn set_travel_settings(&mut self, travel: TravelSettings) {
+        for model in self.all_models() {
+            model.set_travel_settings(travel.clone());
+        }
+        self.travel = travel;
+    }
+
     /// age the cache of the model by one, slowly forgetting the responses to old requests.
     pub fn age_cache(&mut self) {
         self.model.age_cache(self.max_cache_size.value());
     }
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use eframe::epaint::ahash::HashMap;
+
+    use super::*;
+    use crate::model::database::fixtures;
+
+    /// a loaded model without any towns, told apart by the time it was fetched
+    fn model(time: i64) -> Model {
+        Model::Loaded {
+            db: Box::new(DataTable {
+                reference_time: time,
+                ..fixtures::table(Vec::new(), Vec::new())
+            }),
+            report: Arc::new(ParseReport::default()),
+            cache_strings: HashMap::default(),
+            cache_towns: HashMap::default(),
+        }
+    }
+
+    fn time_of(model: &Model) -> Option<i64> {
+        match model {
+            Model::Uninitialized(_) => None,
+            Model::Loaded { db, .. } => Some(db.reference_time),
+        }
+    }
+
+    /// a presenter showing the data fetched at time 100, with a playback of the given snapshots
+    fn presenter_with_playback(times: &[i64]) -> Presenter {
+        return Presenter {
+            model: model(100),
+            playback: Some(Playback {
+                server: String::from("de99"),
+                total: times.len(),
+                read: Arc::new(Mutex::new(Vec::new())),
+                converted: times.len(),
+                frames: times.iter().map(|time| (*time, model(*time))).collect(),
+                shown: None,
+            }),
+            ..Default::default()
+        };
+    }
+
+    fn frame_times(presenter: &Presenter) -> Vec<Option<i64>> {
+        presenter
+            .playback
+            .iter()
+            .flat_map(|playback| playback.frames.iter().map(|(_time, model)| time_of(model)))
+            .collect()
+    }
+
+    #[test]
+    fn show_playback_frame_swaps_the_frames_in_and_out() {
+        let mut presenter = presenter_with_playback(&[1, 2, 3]);
+
+        assert_eq!(presenter.show_playback_frame(1), Some(2));
+        assert_eq!(time_of(&presenter.model), Some(2));
+        // the loaded data waits in the slot of the shown frame
+        assert_eq!(frame_times(&presenter), vec![Some(1), Some(100), Some(3)]);
+
+        assert_eq!(presenter.show_playback_frame(0), Some(1));
+        assert_eq!(time_of(&presenter.model), Some(1));
+        assert_eq!(frame_times(&presenter), vec![Some(100), Some(2), Some(3)]);
+
+        // showing the same frame again keeps it
+        assert_eq!(presenter.show_playback_frame(0), Some(1));
+        assert_eq!(time_of(&presenter.model), Some(1));
+        assert_eq!(frame_times(&presenter), vec![Some(100), Some(2), Some(3)]);
+
+        // there is no such frame, nothing changes
+        assert_eq!(presenter.show_playback_frame(3), None);
+        assert_eq!(time_of(&presenter.model), Some(1));
+    }
+
+    #[test]
+    fn stop_playback_shows_the_loaded_data_again() {
+        let mut presenter = presenter_with_playback(&[1, 2, 3]);
+        presenter.show_playback_frame(2);
+        presenter.show_playback_frame(1);
+
+        presenter.stop_playback();
+
+        assert_eq!(time_of(&presenter.model), Some(100));
+        assert!(presenter.playback.is_none());
+
+        // without a frame shown the loaded data stays
+        let mut presenter = presenter_with_playback(&[1, 2, 3]);
+        presenter.stop_playback();
+        assert_eq!(time_of(&presenter.model), Some(100));
+    }
+
+    #[test]
+    fn poll_playback_takes_the_prepared_snapshots() {
+        let mut presenter = presenter_with_playback(&[1]);
+        presenter.set_exclude_approximate(true);
+        if let Some(playback) = &mut presenter.playback {
+            playback.total = 4;
+            let mut read = playback.read.lock().unwrap();
+            read.push((2, Some(model(2))));
+            // could not be read
+            read.push((3, None));
+        }
+
+        assert_eq!(presenter.poll_playback(), Some((vec![1, 2], 1)));
+        assert_eq!(presenter.poll_playback(), Some((vec![1, 2], 1)));
+
+        // the settings apply to snapshots that were prepared in the meantime as well
+        let Some(Model::Loaded { db, .. }) = presenter
+            .playback
+            .as_ref()
+            .map(|playback| &playback.frames[1].1)
+        else {
+            panic!("the snapshot is not loaded");
+        };
+        assert!(db.exclude_approximate);
+    }
+}
@@ -0,0 +1,382 @@
+//! Draw the map without a window or GPU, e.g. to post an overview map in the alliance forum.
+//!
+//! The image is built as an SVG with the same layers as `View::ui_map`. For a PNG that SVG is
+//! rasterized on the CPU.
+
+use std::fmt::Write;
+use std::sync::Arc;
+
+use anyhow::Context;
+use base64::Engine;
+use rust_i18n::t;
+
+use crate::selection::MarkerStyle;
+use crate::town::{ocean_number, Town};
+
+/// towns of one colour, e.g. all ghost towns or the towns of one selection
+pub struct MapLayer {
+    pub name: String,
+    pub color: egui::Color32,
+    pub towns: Arc<Vec<Town>>,
+    /// radius in pixels that is added to the scaled town size, same as on the map
+    pub min_radius: f32,
+    pub marker: MarkerStyle,
+}
+
+pub struct MapImage {
+    /// the part of the world that is exported, in world coordinates
+    pub region: egui::Rect,
+    /// width of the image in pixels, the height follows from the aspect ratio of the region
+    pub width: u32,
+    pub background: egui::Color32,
+    pub text_color: egui::Color32,
+    /// drawn in order, the last layer ends up on top
+    pub layers: Vec<MapLayer>,
+    /// the territories of `territory::territory_image`, drawn behind all layers
+    pub territory: Option<Arc<egui::ColorImage>>,
+    /// name, colour and number of towns of each territory that is not one of the layers
+    pub territory_legend: Vec<(String, egui::Color32, usize)>,
+    /// where the top left corner of the legend goes, as a fraction of the image size. No legend if None
+    pub legend: Option<egui::Vec2>,
+}
+
+/// the colour as an svg attribute value and an opacity
+fn svg_color(color: egui::Color32) -> (String, f32) {
+    let [r, g, b, a] = color.to_srgba_unmultiplied();
+    return (format!("#{r:02x}{g:02x}{b:02x}"), f32::from(a) / 255.0);
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&apos;")
+}
+
+impl MapImage {
+    /// pixels per world unit
+    fn scale(&self) -> f32 {
+        #[allow(clippy::cast_precision_loss)] // images are never that large
+        return self.width as f32 / self.region.width();
+    }
+
+    pub fn height(&self) -> u32 {
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        return (self.region.height() * self.scale()).round().max(1.0) as u32;
+    }
+
+    fn world_to_image(&self, x: f32, y: f32) -> egui::Pos2 {
+        let scale = self.scale();
+        return egui::pos2(
+            (x - self.region.min.x) * scale,
+            (y - self.region.min.y) * scale,
+        );
+    }
+
+    fn write_grid(&self, svg: &mut String) {
+        let gray = svg_color(egui::Color32::DARK_GRAY).0;
+        let mut lines = |step: usize, stroke: &str| {
+            for i in (0..=1000u16).step_by(step).map(f32::from) {
+                let one = self.world_to_image(0.0, i);
+                let two = self.world_to_image(1000.0, i);
+                let three = self.world_to_image(i, 0.0);
+                let four = self.world_to_image(i, 1000.0);
+                let _ = writeln!(
+                    svg,
+                    r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{gray}" {stroke}/>"#,
+                    one.x, one.y, two.x, two.y
+                );
+                let _ = writeln!(
+                    svg,
+                    r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{gray}" {stroke}/>"#,
+                    three.x, three.y, four.x, four.y
+                );
+            }
+        };
+        lines(100, r#"stroke-width="2""#);
+        // the finer grid is only drawn on the map when zoomed in, so do the same here
+        if self.scale() > 5.0 {
+            lines(10, r#"stroke-width="1" stroke-dasharray="7 7""#);
+        }
+
+        // label each ocean in its top left corner, like on the map
+        if self.scale() * 100.0 > 40.0 {
+            for ocean_x in 0u16..10 {
+                for ocean_y in 0u16..10 {
+                    let corner =
+                        self.world_to_image(f32::from(ocean_x) * 100.0, f32::from(ocean_y) * 100.0);
+                    let _ = writeln!(
+                        svg,
+                        r#"<text x="{}" y="{}" font-family="sans-serif" font-size="16" fill="{gray}">{}</text>"#,
+                        corner.x + 4.0,
+                        corner.y + 20.0,
+                        ocean_number(ocean_x * 100, ocean_y * 100)
+                    );
+                }
+            }
+        }
+    }
+
+    /// where the territory image goes: from its pixels to the pixels of this image
+    fn territory_transform(&self, image: &resvg::tiny_skia::Pixmap) -> resvg::tiny_skia::Transform {
+        let min = self.world_to_image(0.0, 0.0);
+        let max = self.world_to_image(1000.0, 1000.0);
+        #[allow(clippy::cast_precision_loss)] // the territory image is only 500 pixels wide
+        return resvg::tiny_skia::Transform::from_row(
+            (max.x - min.x) / image.width() as f32,
+            0.0,
+            0.0,
+            (max.y - min.y) / image.height() as f32,
+            min.x,
+            min.y,
+        );
+    }
+
+    /// the territory image stretched over the whole world, embedded as a png
+    fn write_territory(
+        &self,
+        svg: &mut String,
+        image: &resvg::tiny_skia::Pixmap,
+    ) -> anyhow::Result<()> {
+        let png = base64::engine::general_purpose::STANDARD.encode(image.encode_png()?);
+        let min = self.world_to_image(0.0, 0.0);
+        let max = self.world_to_image(1000.0, 1000.0);
+        let _ = writeln!(
+            svg,
+            r#"<image x="{}" y="{}" width="{}" height="{}" preserveAspectRatio="none" href="data:image/png;base64,{png}"/>"#,
+            min.x,
+            min.y,
+            max.x - min.x,
+            max.y - min.y
+        );
+        return Ok(());
+    }
+
+    fn write_layer(&self, svg: &mut String, layer: &MapLayer) {
+        if layer.color.a() == 0 {
+            return;
+        }
+        let (color, opacity) = svg_color(layer.color);
+        let default_radius = layer.min_radius + self.scale() * 0.15;
+        let max_value = layer.marker.max_value(&layer.towns);
+        // scaled markers are at most twice as large
+        let visible = self.region.expand(2.0 * default_radius / self.scale());
+        let _ = writeln!(svg, r#"<g fill="{color}" fill-opacity="{opacity}">"#);
+        for town in layer
+            .towns
+            .iter()
+            .filter(|town| visible.contains(egui::pos2(town.x, town.y)))
+        {
+            let center = self.world_to_image(town.x, town.y);
+            let (radius, town_color) =
+                layer
+                    .marker
+                    .apply(town, max_value, default_radius, layer.color);
+            let (_, town_opacity) = svg_color(town_color);
+            // same as on the map, approximate positions are drawn as a ring
+            if town.approximate {
+                let _ = writeln!(
+                    svg,
+                    r#"<circle cx="{}" cy="{}" r="{radius}" fill="none" stroke="{color}" stroke-opacity="{town_opacity}" stroke-width="1"/>"#,
+                    center.x, center.y
+                );
+            } else if town_color == layer.color {
+                let _ = writeln!(
+                    svg,
+                    r#"<circle cx="{}" cy="{}" r="{radius}"/>"#,
+                    center.x, center.y
+                );
+            } else {
+                let _ = writeln!(
+                    svg,
+                    r#"<circle cx="{}" cy="{}" r="{radius}" fill-opacity="{town_opacity}"/>"#,
+                    center.x, center.y
+                );
+            }
+        }
+        svg.push_str("</g>\n");
+    }
+
+    fn write_legend(&self, svg: &mut String, position: egui::Vec2) {
+        let entries: Vec<(egui::Color32, String)> = self
+            .layers
+            .iter()
+            .filter(|layer| layer.color.a() > 0)
+            .map(|layer| (&layer.name, layer.color, layer.towns.len()))
+            .chain(
+                self.territory_legend
+                    .iter()
+                    .map(|(name, color, count)| (name, *color, *count)),
+            )
+            .map(|(name, color, count)| (color, t!("map.legend.entry", name = name, count = count)))
+            .collect();
+        if entries.is_empty() {
+            return;
+        }
+        let font_size = 14.0;
+        let row_height = font_size * 1.5;
+        let padding = 8.0;
+        // there is no text layout here, so guess the width from the longest name
+        #[allow(clippy::cast_precision_loss)]
+        let longest = entries
+            .iter()
+            .map(|(_, text)| text.chars().count())
+            .max()
+            .unwrap_or(0) as f32;
+        let width = 2.0 * padding + row_height + longest * font_size * 0.6;
+        #[allow(clippy::cast_precision_loss)]
+        let height = 2.0 * padding + entries.len() as f32 * row_height;
+
+        // same place as the overlay on the map, but never cut off at the edges
+        #[allow(clippy::cast_precision_loss)]
+        let (image_width, image_height) = (self.width as f32, self.height() as f32);
+        let left = (position.x * image_width).min(image_width - width).max(0.0);
+        let top = (position.y * image_height)
+            .min(image_height - height)
+            .max(0.0);
+
+        let (background, background_opacity) = svg_color(self.background);
+        let (text_color, _) = svg_color(self.text_color);
+        let _ = writeln!(
+            svg,
+            r#"<rect x="{left}" y="{top}" width="{width}" height="{height}" fill="{background}" fill-opacity="{}" stroke="{text_color}" stroke-width="1"/>"#,
+            background_opacity * 0.8
+        );
+        for (index, (color, text)) in entries.iter().enumerate() {
+            #[allow(clippy::cast_precision_loss)]
+            let row_center = top + padding + (index as f32 + 0.5) * row_height;
+            let (color, opacity) = svg_color(*color);
+            let _ = writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{row_center}" r="{}" fill="{color}" fill-opacity="{opacity}"/>"#,
+                left + padding + row_height / 2.0,
+                font_size / 3.0
+            );
+            let _ = writeln!(
+                svg,
+                r#"<text x="{}" y="{}" font-family="sans-serif" font-size="{font_size}" fill="{text_color}">{}</text>"#,
+                left + padding + row_height,
+                row_center + font_size / 3.0,
+                xml_escape(text)
+            );
+        }
+    }
+
+    /// the territory image as something that can be drawn and encoded
+    fn territory_pixmap(&self) -> anyhow::Result<Option<resvg::tiny_skia::Pixmap>> {
+        let Some(image) = &self.territory else {
+            return Ok(None);
+        };
+        let [width, height] = image.size;
+        let size =
+            resvg::tiny_skia::IntSize::from_wh(u32::try_from(width)?, u32::try_from(height)?)
+                .context("The territory image is empty")?;
+        // both use premultiplied rgba
+        let data = image
+            .pixels
+            .iter()
+            .flat_map(egui::Color32::to_array)
+            .collect();
+        let pixmap = resvg::tiny_skia::Pixmap::from_vec(data, size)
+            .context("The territory image does not fit its size")?;
+        return Ok(Some(pixmap));
+    }
+
+    fn svg_document(&self, body: &str) -> String {
+        let width = self.width;
+        let height = self.height();
+        return format!(
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">
+{body}</svg>
+"#
+        );
+    }
+
+    /// the background and the grid, everything below the territories
+    fn svg_below_territory(&self) -> String {
+        let (background, _) = svg_color(self.background);
+        let mut svg = format!(
+            r#"<rect width="{}" height="{}" fill="{background}"/>
+"#,
+            self.width,
+            self.height()
+        );
+        self.write_grid(&mut svg);
+        return svg;
+    }
+
+    /// the towns and the legend, everything above the territories
+    fn svg_above_territory(&self) -> String {
+        let mut svg = String::new();
+        for layer in &self.layers {
+            self.write_layer(&mut svg, layer);
+        }
+        if let Some(position) = self.legend {
+            self.write_legend(&mut svg, position);
+        }
+        return svg;
+    }
+
+    pub fn to_svg(&self) -> anyhow::Result<String> {
+        let mut body = self.svg_below_territory();
+        if let Some(territory) = self.territory_pixmap()? {
+            self.write_territory(&mut body, &territory)?;
+        }
+        body.push_str(&self.svg_above_territory());
+        return Ok(self.svg_document(&body));
+    }
+
+    /// rasterize the svg with the fonts the app uses, so names render the same way as in the ui
+    pub fn to_png(&self) -> anyhow::Result<Vec<u8>> {
+        let mut options = resvg::usvg::Options::default();
+        let fontdb = options.fontdb_mut();
+        // load the proportional fonts first, so the first face is the default font of the ui
+        let definitions = egui::FontDefinitions::default();
+        for name in &definitions.families[&egui::FontFamily::Proportional] {
+            if let Some(font) = definitions.font_data.get(name) {
+                fontdb.load_font_data(font.font.to_vec());
+            }
+        }
+        let default_family = fontdb
+            .faces()
+            .find_map(|face| face.families.first().map(|(name, _)| name.clone()));
+        fontdb.load_font_data(crate::view::UNICODE_FONT.to_vec());
+        if let Some(family) = default_family {
+            fontdb.set_sans_serif_family(family);
+        }
+
+        let mut pixmap = resvg::tiny_skia::Pixmap::new(self.width, self.height())
+            .context("The image is too large")?;
+        let render = |pixmap: &mut resvg::tiny_skia::Pixmap, body: &str| -> anyhow::Result<()> {
+            let tree = resvg::usvg::Tree::from_str(&self.svg_document(body), &options)
+                .context("Failed to parse the generated svg")?;
+            resvg::render(
+                &tree,
+                resvg::tiny_skia::Transform::default(),
+                &mut pixmap.as_mut(),
+            );
+            return Ok(());
+        };
+        render(&mut pixmap, &self.svg_below_territory())?;
+        // resvg is built without support for embedded raster images, so the territories are drawn
+        // in between the two halves of the svg
+        if let Some(territory) = self.territory_pixmap()? {
+            let paint = resvg::tiny_skia::PixmapPaint {
+                quality: resvg::tiny_skia::FilterQuality::Bilinear,
+                ..resvg::tiny_skia::PixmapPaint::default()
+            };
+            pixmap.draw_pixmap(
+                0,
+                0,
+                territory.as_ref(),
+                &paint,
+                self.territory_transform(&territory),
+                None,
+            );
+        }
+        render(&mut pixmap, &self.svg_above_territory())?;
+        let re = pixmap.encode_png()?;
+        return Ok(re);
+    }
+}
@@ -4,6 +4,8 @@ use std::default::Default;
 use std::sync::Arc;
 
 use serde::{Deserialize, Serialize};
+use strum::IntoEnumIterator;
+use strum_macros::EnumIter;
 
 use crate::constraint::Constraint;
 use crate::emptyconstraint::EmptyConstraint;
@@ -39,6 +41,76 @@ impl AndOr {
     }
 }
 
+/// which value of a town decides how prominent its marker on the map is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumIter)]
+pub enum MarkerScale {
+    /// all markers look the same
+    #[default]
+    Fixed,
+    TownPoints,
+    PlayerPoints,
+    AlliancePoints,
+}
+
+/// how a larger value shows on the marker
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumIter)]
+pub enum MarkerEffect {
+    #[default]
+    Size,
+    Opacity,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub struct MarkerStyle {
+    #[serde(default)]
+    pub scale_by: MarkerScale,
+    #[serde(default)]
+    pub effect: MarkerEffect,
+}
+
+impl MarkerStyle {
+    fn value(self, town: &Town) -> f32 {
+        #[allow(clippy::cast_precision_loss)] // the exact value does not matter for drawing
+        match self.scale_by {
+            MarkerScale::Fixed => 1.0,
+            MarkerScale::TownPoints => f32::from(town.points),
+            MarkerScale::PlayerPoints => town.player_points.unwrap_or(0) as f32,
+            MarkerScale::AlliancePoints => town.alliance_points.unwrap_or(0) as f32,
+        }
+    }
+
+    /// the largest value among `towns`, markers are scaled relative to it
+    pub fn max_value(self, towns: &[Town]) -> f32 {
+        if self.scale_by == MarkerScale::Fixed {
+            return 1.0;
+        }
+        return towns
+            .iter()
+            .map(|town| self.value(town))
+            .fold(0.0, f32::max);
+    }
+
+    /// Radius and colour of the marker of `town`. The marker of the town with `max_value` is
+    /// twice as large as an unscaled one (or as opaque as `color`), towns without points are
+    /// drawn at a fifth of that. Scaling by the square root makes the area follow the value.
+    pub fn apply(
+        self,
+        town: &Town,
+        max_value: f32,
+        radius: f32,
+        color: egui::Color32,
+    ) -> (f32, egui::Color32) {
+        if self.scale_by == MarkerScale::Fixed || max_value <= 0.0 {
+            return (radius, color);
+        }
+        let share = (self.value(town) / max_value).clamp(0.0, 1.0).sqrt();
+        match self.effect {
+            MarkerEffect::Size => (radius * (0.4 + 1.6 * share), color),
+            MarkerEffect::Opacity => (radius, color.gamma_multiply(0.2 + 0.8 * share)),
+        }
+    }
+}
+
 #[allow(clippy::module_name_repetitions)]
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(from = "EmptyTownSelection", into = "EmptyTownSelection")] // TOOD: make this extra, so we can preserve collapsed state across app restarts
@@ -49,6 +121,7 @@ pub struct TownSelection {
     pub constraints: Vec<Constraint>,
     pub constraint_join_mode: AndOr,
     pub color: egui::Color32,
+    pub marker: MarkerStyle,
     pub towns: Arc<Vec<Town>>,
 }
 
@@ -120,6 +193,7 @@ impl TownSelection {
                 .collect(),
             constraint_join_mode: self.constraint_join_mode,
             color: self.color, // implements copy
+            marker: self.marker,
         }
     }
 
@@ -253,6 +327,40 @@ impl TownSelection {
                     refresh_action = Refresh::InSitu(HashSet::new());
                 }
 
+                ui.menu_button("◎", |ui| {
+                    ui.label(t!("selection.marker.scale_by"));
+                    for scale_by in MarkerScale::iter() {
+                        let text = match scale_by {
+                            MarkerScale::Fixed => t!("selection.marker.fixed"),
+                            MarkerScale::TownPoints => t!("selection.marker.town_points"),
+                            MarkerScale::PlayerPoints => t!("selection.marker.player_points"),
+                            MarkerScale::AlliancePoints => t!("selection.marker.alliance_points"),
+                        };
+                        if ui
+                            .selectable_label(self.marker.scale_by == scale_by, text)
+                            .clicked()
+                        {
+                            self.marker.scale_by = scale_by;
+                        }
+                    }
+                    ui.separator();
+                    ui.label(t!("selection.marker.effect"));
+                    for effect in MarkerEffect::iter() {
+                        let text = match effect {
+                            MarkerEffect::Size => t!("selection.marker.size"),
+                            MarkerEffect::Opacity => t!("selection.marker.opacity"),
+                        };
+                        if ui
+                            .selectable_label(self.marker.effect == effect, text)
+                            .clicked()
+                        {
+                            self.marker.effect = effect;
+                        }
+                    }
+                })
+                .response
+                .on_hover_text(t!("selection.marker.title"));
+
                 if self.is_hidden() {
                     ui.label(t!("selection.hidden"));
                 } else {
@@ -0,0 +1,268 @@
+//! Find towns by their position without looking at every town of the world.
+//!
+//! The world is split into square cells and each cell remembers which points lie in it. A query
+//! only looks at the cells it overlaps, which matters on worlds with tens of thousands of towns.
+
+const CELL_SIZE: f32 = 10.0;
+/// the world is 1000x1000 fields. Points outside of it end up in the cells at the border.
+const CELLS_PER_SIDE: usize = 100;
+
+#[derive(Debug, Clone, Default)]
+pub struct SpatialIndex {
+    positions: Vec<egui::Pos2>,
+    /// indices into `positions`, one list per cell, row by row
+    cells: Vec<Vec<usize>>,
+}
+
+/// Is `point` inside the polygon with the given corners? The polygon is closed automatically
+/// and may cross itself, like a lasso drawn by hand. Then the even-odd rule applies.
+pub fn polygon_contains(polygon: &[egui::Pos2], point: egui::Pos2) -> bool {
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(corner) => *corner,
+        None => return false,
+    };
+    for &corner in polygon {
+        // does a ray from the point to the right cross this edge?
+        if (corner.y > point.y) != (previous.y > point.y) {
+            let crossing_x =
+                corner.x + (point.y - corner.y) / (previous.y - corner.y) * (previous.x - corner.x);
+            if point.x < crossing_x {
+                inside = !inside;
+            }
+        }
+        previous = corner;
+    }
+    return inside;
+}
+
+/// the cell a coordinate falls into
+fn cell_coordinate(value: f32) -> usize {
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_sign_loss,
+        clippy::cast_precision_loss
+    )]
+    // clamped to the size of the grid, so this fits
+    return (value / CELL_SIZE)
+        .floor()
+        .clamp(0.0, (CELLS_PER_SIDE - 1) as f32) as usize;
+}
+
+/// The cells of the grid on the border of the square that reaches `ring` cells from the center
+/// cell in every direction. Ring 0 is the center cell itself.
+fn ring_cells(center_x: usize, center_y: usize, ring: usize) -> Vec<(usize, usize)> {
+    if ring == 0 {
+        return vec![(center_x, center_y)];
+    }
+    let inside = |coordinate: usize| (coordinate < CELLS_PER_SIDE).then_some(coordinate);
+    let x_range = center_x.saturating_sub(ring)..=(center_x + ring).min(CELLS_PER_SIDE - 1);
+    // the corners belong to the rows
+    let y_range = center_y.saturating_sub(ring - 1)..=(center_y + ring - 1).min(CELLS_PER_SIDE - 1);
+
+    let mut re = Vec::with_capacity(8 * ring);
+    for y in [center_y.checked_sub(ring), inside(center_y + ring)]
+        .into_iter()
+        .flatten()
+    {
+        re.extend(x_range.clone().map(|x| (x, y)));
+    }
+    for x in [center_x.checked_sub(ring), inside(center_x + ring)]
+        .into_iter()
+        .flatten()
+    {
+        re.extend(y_range.clone().map(|y| (x, y)));
+    }
+    return re;
+}
+
+impl SpatialIndex {
+    /// The index refers to points by their position in `positions`, so build it from a list
+    /// that is never reordered afterwards.
+    pub fn new(positions: Vec<egui::Pos2>) -> Self {
+        let mut cells = vec![Vec::new(); CELLS_PER_SIDE * CELLS_PER_SIDE];
+        for (index, position) in positions.iter().enumerate() {
+            let cell = cell_coordinate(position.y) * CELLS_PER_SIDE + cell_coordinate(position.x);
+            cells[cell].push(index);
+        }
+        Self { positions, cells }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.positions.is_empty()
+    }
+
+    fn cell(&self, x: usize, y: usize) -> &[usize] {
+        &self.cells[y * CELLS_PER_SIDE + x]
+    }
+
+    /// indices of all points inside `rect` (in world coordinates)
+    pub fn in_rect(&self, rect: egui::Rect) -> Vec<usize> {
+        if self.is_empty() || !rect.is_positive() {
+            return Vec::new();
+        }
+        let mut re = Vec::new();
+        for y in cell_coordinate(rect.min.y)..=cell_coordinate(rect.max.y) {
+            for x in cell_coordinate(rect.min.x)..=cell_coordinate(rect.max.x) {
+                re.extend(
+                    self.cell(x, y)
+                        .iter()
+                        .filter(|&&index| rect.contains(self.positions[index])),
+                );
+            }
+        }
+        return re;
+    }
+
+    /// The index of the point closest to `position` and its distance. None if there is no point
+    /// closer than `max_distance`.
+    pub fn nearest(&self, position: egui::Pos2, max_distance: f32) -> Option<(usize, f32)> {
+        let center_x = cell_coordinate(position.x);
+        let center_y = cell_coordinate(position.y);
+        let mut best: Option<(usize, f32)> = None;
+
+        // look at rings of cells around the cell of the position, growing outwards
+        for ring in 0..CELLS_PER_SIDE {
+            #[allow(clippy::cast_precision_loss)]
+            // anything in this ring or further out is at least this far away
+            let min_distance = ring.saturating_sub(1) as f32 * CELL_SIZE;
+            if min_distance >= max_distance || best.is_some_and(|(_, d)| d <= min_distance) {
+                break;
+            }
+
+            for (x, y) in ring_cells(center_x, center_y, ring) {
+                for &index in self.cell(x, y) {
+                    let distance = self.positions[index].distance(position);
+                    if distance < max_distance && best.is_none_or(|(_, d)| distance < d) {
+                        best = Some((index, distance));
+                    }
+                }
+            }
+        }
+        return best;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// points spread over the world (and a bit beyond), the same every run
+    fn scattered_points(count: usize) -> Vec<egui::Pos2> {
+        let mut state: u32 = 12345;
+        let mut next = || {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            f32::from(u16::try_from(state >> 16).unwrap() % 1100) - 50.0
+        };
+        return (0..count).map(|_| egui::pos2(next(), next())).collect();
+    }
+
+    #[test]
+    fn in_rect_finds_the_same_points_as_looking_at_all() {
+        let points = scattered_points(2000);
+        let index = SpatialIndex::new(points.clone());
+        for rect in [
+            egui::Rect::from_min_max(egui::pos2(100.0, 200.0), egui::pos2(350.5, 260.0)),
+            egui::Rect::from_min_max(egui::pos2(-100.0, -100.0), egui::pos2(5.0, 1100.0)),
+            egui::Rect::from_min_max(egui::pos2(990.0, 990.0), egui::pos2(2000.0, 2000.0)),
+        ] {
+            let mut found = index.in_rect(rect);
+            found.sort_unstable();
+            let expected: Vec<usize> = (0..points.len())
+                .filter(|&i| rect.contains(points[i]))
+                .collect();
+            assert_eq!(found, expected, "{rect:?}");
+        }
+        let empty = egui::Rect::from_min_max(egui::pos2(10.0, 10.0), egui::pos2(5.0, 20.0));
+        assert!(index.in_rect(empty).is_empty());
+    }
+
+    #[test]
+    fn nearest_finds_the_closest_point_within_the_distance() {
+        let points = scattered_points(500);
+        let index = SpatialIndex::new(points.clone());
+        for position in [
+            egui::pos2(500.0, 500.0),
+            egui::pos2(0.0, 0.0),
+            egui::pos2(999.0, 3.0),
+            egui::pos2(-30.0, 1040.0),
+        ] {
+            let expected = points
+                .iter()
+                .map(|point| point.distance(position))
+                .min_by(f32::total_cmp)
+                .unwrap();
+            let (found, distance) = index.nearest(position, f32::INFINITY).unwrap();
+            assert!((distance - expected).abs() < 1e-4, "{position:?}");
+            assert!((points[found].distance(position) - expected).abs() < 1e-4);
+            // nothing closer than the closest point
+            assert!(index.nearest(position, expected * 0.99).is_none());
+        }
+        assert!(SpatialIndex::new(Vec::new())
+            .nearest(egui::pos2(1.0, 1.0), 100.0)
+            .is_none());
+    }
+
+    #[test]
+    fn ring_cells_are_the_border_of_the_square_inside_the_grid() {
+        assert_eq!(ring_cells(50, 50, 0), vec![(50, 50)]);
+
+        let mut ring = ring_cells(50, 50, 3);
+        assert_eq!(ring.len(), 24);
+        assert!(ring
+            .iter()
+            .all(|&(x, y)| x.abs_diff(50).max(y.abs_diff(50)) == 3));
+        ring.sort_unstable();
+        ring.dedup();
+        assert_eq!(ring.len(), 24);
+
+        // at the corner of the grid only a quarter of the ring exists
+        let mut corner = ring_cells(0, 0, 2);
+        corner.sort_unstable();
+        assert_eq!(corner, vec![(0, 2), (1, 2), (2, 0), (2, 1), (2, 2)]);
+        assert!(ring_cells(0, 0, CELLS_PER_SIDE).is_empty());
+    }
+
+    #[test]
+    fn polygon_contains_closes_the_polygon_and_uses_even_odd() {
+        // a triangle, the last corner connects back to the first one
+        let triangle = [
+            egui::pos2(0.0, 0.0),
+            egui::pos2(10.0, 0.0),
+            egui::pos2(0.0, 10.0),
+        ];
+        assert!(polygon_contains(&triangle, egui::pos2(2.0, 2.0)));
+        assert!(!polygon_contains(&triangle, egui::pos2(8.0, 8.0)));
+        assert!(!polygon_contains(&triangle, egui::pos2(-1.0, 5.0)));
+
+        // a bow tie crossing itself in the middle
+        let bow_tie = [
+            egui::pos2(0.0, 0.0),
+            egui::pos2(10.0, 10.0),
+            egui::pos2(10.0, 0.0),
+            egui::pos2(0.0, 10.0),
+        ];
+        assert!(polygon_contains(&bow_tie, egui::pos2(1.0, 5.0)));
+        assert!(polygon_contains(&bow_tie, egui::pos2(9.0, 5.0)));
+        assert!(!polygon_contains(&bow_tie, egui::pos2(5.0, 1.0)));
+
+        // a lasso that loops around the same area twice leaves it out
+        let twice = [
+            egui::pos2(0.0, 0.0),
+            egui::pos2(10.0, 0.0),
+            egui::pos2(10.0, 10.0),
+            egui::pos2(0.0, 10.0),
+            egui::pos2(0.0, 0.0),
+            egui::pos2(10.0, 0.0),
+            egui::pos2(10.0, 10.0),
+            egui::pos2(0.0, 10.0),
+        ];
+        assert!(!polygon_contains(&twice, egui::pos2(5.0, 5.0)));
+
+        assert!(!polygon_contains(&[], egui::pos2(0.0, 0.0)));
+        assert!(!polygon_contains(
+            &[egui::pos2(1.0, 1.0)],
+            egui::pos2(1.0, 1.0)
+        ));
+    }
+}
@@ -1,6 +1,6 @@
 use anyhow::Context;
 use directories_next::ProjectDirs;
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashSet};
 use std::ffi::OsStr;
 use std::ffi::OsString;
 use std::fmt::Display;
@@ -9,9 +9,12 @@ use std::path::{Path, PathBuf};
 use time::format_description::FormatItem;
 use time::macros::format_description;
 use time::macros::offset;
+use time::Duration;
 use time::OffsetDateTime;
 use time::UtcOffset;
 
+use crate::view::preferences::AutoDeletePref;
+
 const DEFAULT_FILENAME: &str = "de99-1970-01-01-00-00-00T00-00-00";
 const FORMAT_FILENAME: &[FormatItem<'_>] = format_description!(
     "[year]-[month]-[day]-[hour]-[minute]-[second]T[offset_hour]-[offset_minute]-[offset_second]"
@@ -35,9 +38,8 @@ impl From<PathBuf> for SavedDB {
             .unwrap_or(&default_filename)
             .to_str()
             .unwrap_or(DEFAULT_FILENAME);
-        let splits: Vec<&str> = filename.splitn(2, '-').collect();
-        let server_str = splits[0];
-        let date_str = splits[1];
+        // files that were not saved by us may not follow the naming scheme
+        let (server_str, date_str) = filename.split_once('-').unwrap_or((filename, ""));
         let date =
             OffsetDateTime::parse(date_str, &FORMAT_FILENAME).unwrap_or(OffsetDateTime::UNIX_EPOCH);
 
@@ -94,6 +96,15 @@ impl Display for SavedDB {
     }
 }
 
+/// a unix timestamp in the local time zone, in the same format as the dates of saved files
+pub fn format_timestamp(timestamp: i64) -> String {
+    let local_offset = UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC);
+    return OffsetDateTime::from_unix_timestamp(timestamp)
+        .ok()
+        .and_then(|date| date.to_offset(local_offset).format(&FORMAT_DISPLAY).ok())
+        .unwrap_or_else(|| timestamp.to_string());
+}
+
 /// returns a path to a not yet existing apiresponse file. If the
 /// function returns `Some(path)`, the parent directory is
 /// guaranteed to exist.
@@ -172,6 +183,72 @@ pub fn remove_all() {
     }
 }
 
+/// The saved files `pref` wants gone as of `now`, oldest first for each server. The newest file of
+/// each server is always kept, so there is something to open. So are files without a date in
+/// their name, because their age is unknown.
+pub fn snapshots_to_delete(
+    saved_dbs: &BTreeMap<String, Vec<SavedDB>>,
+    pref: AutoDeletePref,
+    now: OffsetDateTime,
+) -> Vec<SavedDB> {
+    let max_age = match pref {
+        AutoDeletePref::NoTime => Some(Duration::ZERO),
+        AutoDeletePref::OneDay => Some(Duration::DAY),
+        AutoDeletePref::OneWeek => Some(Duration::WEEK),
+        AutoDeletePref::OneMonth => Some(Duration::days(30)),
+        AutoDeletePref::Thinned | AutoDeletePref::Eternity => None,
+    };
+
+    let mut re = Vec::new();
+    for list_of_dbs in saved_dbs.values() {
+        let mut dated: Vec<&SavedDB> = list_of_dbs
+            .iter()
+            .filter(|saved_db| saved_db.date != OffsetDateTime::UNIX_EPOCH)
+            .collect();
+        dated.sort_by_key(|saved_db| saved_db.date);
+        // the newest file
+        dated.pop();
+
+        if let Some(max_age) = max_age {
+            re.extend(
+                dated
+                    .into_iter()
+                    .filter(|saved_db| now - saved_db.date > max_age)
+                    .cloned(),
+            );
+        } else if pref == AutoDeletePref::Thinned {
+            // The oldest file of each day or week is kept. That way the file that is kept for a
+            // week is one of the files that were kept for their day.
+            let mut kept = HashSet::new();
+            for saved_db in dated {
+                let age = now - saved_db.date;
+                let period = if age <= Duration::WEEK {
+                    continue;
+                } else if age <= Duration::days(30) {
+                    (saved_db.date.year(), saved_db.date.ordinal(), false)
+                } else {
+                    let (year, week, _weekday) = saved_db.date.to_iso_week_date();
+                    (year, u16::from(week), true)
+                };
+                if !kept.insert(period) {
+                    re.push(saved_db.clone());
+                }
+            }
+        }
+    }
+    return re;
+}
+
+/// delete the saved files `pref` wants gone, see `snapshots_to_delete`
+pub fn apply_auto_delete(pref: AutoDeletePref) {
+    let to_delete = snapshots_to_delete(&get_list_of_saved_dbs(), pref, OffsetDateTime::now_utc());
+    for saved_db in to_delete {
+        if let Err(err) = remove_db(&saved_db.path) {
+            eprintln!("{err:?}");
+        }
+    }
+}
+
 // utility functions
 
 fn my_project_dir() -> Option<ProjectDirs> {
@@ -189,3 +266,113 @@ fn ensure_storage_location_exists() -> bool {
         false
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use time::macros::datetime;
+
+    use super::*;
+
+    /// 2024-06-15 is a saturday
+    const NOW: OffsetDateTime = datetime!(2024-06-15 12:00 UTC);
+
+    fn saved_db(server: &str, date: OffsetDateTime) -> SavedDB {
+        let date_str = date.format(&FORMAT_FILENAME).unwrap();
+        SavedDB::from(PathBuf::from(format!("{server}-{date_str}.apiresponse")))
+    }
+
+    fn saved_dbs(dbs: &[SavedDB]) -> BTreeMap<String, Vec<SavedDB>> {
+        let mut re: BTreeMap<String, Vec<SavedDB>> = BTreeMap::new();
+        for saved_db in dbs {
+            re.entry(saved_db.server_str.clone())
+                .or_default()
+                .push(saved_db.clone());
+        }
+        return re;
+    }
+
+    fn days_ago(days: f64) -> OffsetDateTime {
+        NOW - Duration::seconds_f64(days * 86400.0)
+    }
+
+    #[test]
+    fn snapshots_to_delete_by_age() {
+        let dbs: Vec<SavedDB> = [0.5, 2.0, 10.0, 40.0]
+            .into_iter()
+            .map(|days| saved_db("de99", days_ago(days)))
+            .collect();
+        // the newest file is the one of half a day ago and is always kept
+        let cases = [
+            (AutoDeletePref::NoTime, vec![3, 2, 1]),
+            (AutoDeletePref::OneDay, vec![3, 2, 1]),
+            (AutoDeletePref::OneWeek, vec![3, 2]),
+            (AutoDeletePref::OneMonth, vec![3]),
+            (AutoDeletePref::Eternity, vec![]),
+        ];
+
+        for (pref, expected) in cases {
+            let to_delete = snapshots_to_delete(&saved_dbs(&dbs), pref, NOW);
+            let expected: Vec<SavedDB> = expected.into_iter().map(|i| dbs[i].clone()).collect();
+            assert_eq!(to_delete, expected, "{pref:?}");
+        }
+    }
+
+    #[test]
+    fn snapshots_to_delete_keeps_the_newest_file_of_each_server() {
+        let dbs = [
+            saved_db("de99", days_ago(100.0)),
+            saved_db("de99", days_ago(50.0)),
+            saved_db("en12", days_ago(80.0)),
+        ];
+
+        let to_delete = snapshots_to_delete(&saved_dbs(&dbs), AutoDeletePref::NoTime, NOW);
+
+        assert_eq!(to_delete, vec![dbs[0].clone()]);
+    }
+
+    #[test]
+    fn snapshots_to_delete_keeps_files_without_a_date() {
+        let undated = SavedDB::from(PathBuf::from("de99-copy.apiresponse"));
+        assert_eq!(undated.date, OffsetDateTime::UNIX_EPOCH);
+        let dbs = [
+            undated,
+            saved_db("de99", days_ago(20.0)),
+            saved_db("de99", days_ago(1.0)),
+        ];
+
+        for pref in [AutoDeletePref::NoTime, AutoDeletePref::Thinned] {
+            let to_delete = snapshots_to_delete(&saved_dbs(&dbs), pref, NOW);
+            assert!(!to_delete.contains(&dbs[0]), "{pref:?}");
+        }
+    }
+
+    #[test]
+    fn snapshots_to_delete_thinned_keeps_one_file_per_day_and_week() {
+        let dbs = [
+            // the last week is kept completely
+            saved_db("de99", days_ago(1.0)),
+            saved_db("de99", days_ago(1.01)),
+            saved_db("de99", days_ago(6.0)),
+            saved_db("de99", days_ago(6.01)),
+            // two files of the same day, the older one is kept
+            saved_db("de99", days_ago(10.0)),
+            saved_db("de99", days_ago(10.01)),
+            // different days
+            saved_db("de99", days_ago(12.0)),
+            saved_db("de99", days_ago(13.0)),
+            // 2024-04-15 is a monday, so these are in the same week and only the first is kept
+            saved_db("de99", datetime!(2024-04-15 09:00 UTC)),
+            saved_db("de99", datetime!(2024-04-17 09:00 UTC)),
+            saved_db("de99", datetime!(2024-04-21 09:00 UTC)),
+            // the next week
+            saved_db("de99", datetime!(2024-04-22 09:00 UTC)),
+        ];
+
+        let to_delete = snapshots_to_delete(&saved_dbs(&dbs), AutoDeletePref::Thinned, NOW);
+
+        assert_eq!(
+            to_delete,
+            vec![dbs[9].clone(), dbs[10].clone(), dbs[4].clone()]
+        );
+    }
+}
@@ -0,0 +1,83 @@
+//! Which group of towns dominates which part of the map, to make front lines visible when zoomed
+//! out far enough that single towns are just specks.
+//!
+//! Every town spreads influence over the fields around it, fading out with the distance. Each part
+//! of the map belongs to the group with the most influence there. The more influence, the more
+//! opaque the colour, so the core of a territory stands out from its thinly settled edges.
+
+use crate::town::Town;
+
+/// fields per pixel of the territory image
+const CELL_SIZE: f32 = 2.0;
+/// pixels per side of the territory image, covering the whole 1000x1000 world
+const CELLS_PER_SIDE: usize = 500;
+/// how far the influence of a town reaches, in fields
+const RADIUS: f32 = 12.0;
+/// influence at which a territory is fully coloured, roughly a handful of towns close by
+const FULL_INFLUENCE: f32 = 3.0;
+/// opacity of a fully coloured territory, so the towns on top stay visible
+const MAX_OPACITY: f32 = 0.5;
+
+/// towns that belong together, e.g. the towns of one alliance or one selection
+pub struct TerritoryGroup<'a> {
+    pub color: egui::Color32,
+    pub towns: Vec<&'a Town>,
+}
+
+/// Colour every part of the world by the group with the most influence there. If two groups are
+/// equally strong the later one wins, like the town dots of later selections are drawn on top.
+pub fn territory_image(groups: &[TerritoryGroup]) -> egui::ColorImage {
+    let cell_count = CELLS_PER_SIDE * CELLS_PER_SIDE;
+    let mut best: Vec<(f32, Option<usize>)> = vec![(0.0, None); cell_count];
+    let mut influence = vec![0.0f32; cell_count];
+
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let reach = (RADIUS / CELL_SIZE).ceil() as isize;
+    for (group_index, group) in groups.iter().enumerate() {
+        influence.fill(0.0);
+        for town in &group.towns {
+            #[allow(clippy::cast_possible_truncation)]
+            let (center_x, center_y) = (
+                (town.x / CELL_SIZE).floor() as isize,
+                (town.y / CELL_SIZE).floor() as isize,
+            );
+            for y in (center_y - reach)..=(center_y + reach) {
+                for x in (center_x - reach)..=(center_x + reach) {
+                    let (Ok(cell_x), Ok(cell_y)) = (usize::try_from(x), usize::try_from(y)) else {
+                        continue;
+                    };
+                    if cell_x >= CELLS_PER_SIDE || cell_y >= CELLS_PER_SIDE {
+                        continue;
+                    }
+                    // measure from the centre of the cell
+                    #[allow(clippy::cast_precision_loss)]
+                    let distance = egui::pos2(
+                        (cell_x as f32 + 0.5) * CELL_SIZE,
+                        (cell_y as f32 + 0.5) * CELL_SIZE,
+                    )
+                    .distance(egui::pos2(town.x, town.y));
+                    if distance < RADIUS {
+                        influence[cell_y * CELLS_PER_SIDE + cell_x] += 1.0 - distance / RADIUS;
+                    }
+                }
+            }
+        }
+        for (cell, value) in best.iter_mut().zip(&influence) {
+            if *value > 0.0 && *value >= cell.0 {
+                *cell = (*value, Some(group_index));
+            }
+        }
+    }
+
+    let mut image =
+        egui::ColorImage::new([CELLS_PER_SIDE, CELLS_PER_SIDE], egui::Color32::TRANSPARENT);
+    for (pixel, (value, owner)) in image.pixels.iter_mut().zip(best) {
+        if let Some(group_index) = owner {
+            let [r, g, b, _a] = groups[group_index].color.to_srgba_unmultiplied();
+            #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+            let alpha = ((value / FULL_INFLUENCE).min(1.0) * MAX_OPACITY * 255.0) as u8;
+            *pixel = egui::Color32::from_rgba_unmultiplied(r, g, b, alpha);
+        }
+    }
+    return image;
+}
@@ -4,9 +4,78 @@ pub struct Town {
     pub player_id: Option<u32>,
     pub player_name: Option<String>,
     pub alliance_name: Option<String>,
+    pub player_points: Option<u32>,
+    pub alliance_points: Option<u32>,
     pub name: String,
     pub x: f32,
     pub y: f32,
+    pub island_x: u16,
+    pub island_y: u16,
+    /// None if there is no island at `island_x`/`island_y` in the data
+    pub island_id: Option<u32>,
+    /// e.g. 54 for a town on the island at 512|473
+    pub ocean: u8,
     pub slot_number: u8,
     pub points: u16,
+    /// the island or the slot of the town is unknown, x and y are just the centre of the island
+    pub approximate: bool,
+}
+
+/// the number of the ocean (the 100x100 squares of the map) the coordinates are in, e.g. 54 for
+/// 512|473. The first digit is the x coordinate, the second one the y coordinate.
+pub fn ocean_number(x: u16, y: u16) -> u8 {
+    #[allow(clippy::cast_possible_truncation)]
+    // coordinates are below 1000, so this is at most 99
+    return ((x / 100) * 10 + y / 100) as u8;
+}
+
+/// everything we know about a town and its owner, for the detail panel of a clicked town
+#[derive(Debug, Clone)]
+pub struct TownDetails {
+    pub town: Town,
+    pub island: Option<IslandDetails>,
+    pub player: Option<PlayerDetails>,
+    pub alliance: Option<AllianceDetails>,
+    /// how often the town changed hands
+    pub conquests: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct IslandDetails {
+    pub typ: u8,
+    /// number of town slots on the island
+    pub towns: u8,
+    pub ressource_plus: String,
+    pub ressource_minus: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct PlayerDetails {
+    pub name: String,
+    pub points: u32,
+    pub rank: u16,
+    pub towns: u16,
+}
+
+/// a player and how long they have not grown, for the list of inactive players
+#[derive(Debug, Clone)]
+pub struct InactivePlayer {
+    pub name: String,
+    pub alliance_name: Option<String>,
+    pub points: u32,
+    pub towns: u16,
+    pub inactive_days: f64,
+    /// number of saved snapshots the inactivity is based on
+    pub snapshots: usize,
+    /// where the map jumps to when the player is clicked
+    pub first_town: Option<Town>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AllianceDetails {
+    pub name: String,
+    pub points: u32,
+    pub rank: u16,
+    pub towns: u32,
+    pub members: u16,
 }
@@ -0,0 +1,266 @@
+//! Estimate how long units need from one town to another.
+//!
+//! The game measures distances in pixels of the island map, 128 pixels per field of our
+//! coordinates. A unit with speed 1 needs 50 seconds per pixel on a world with speed 1. The result
+//! is an estimate, the game itself rounds and may apply bonuses we do not know about.
+
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+const PIXELS_PER_FIELD: f32 = 128.0;
+const SECONDS_PER_PIXEL: f32 = 50.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Unit {
+    /// the id the game uses, e.g. `bireme`
+    pub id: String,
+    pub name: String,
+    pub speed: f32,
+    pub naval: bool,
+}
+
+/// speeds of the units on a world without special settings
+const DEFAULT_UNITS: [(&str, &str, f32, bool); 25] = [
+    ("sword", "Swordsman", 8.0, false),
+    ("slinger", "Slinger", 14.0, false),
+    ("archer", "Archer", 12.0, false),
+    ("hoplite", "Hoplite", 6.0, false),
+    ("rider", "Horseman", 22.0, false),
+    ("chariot", "Chariot", 18.0, false),
+    ("catapult", "Catapult", 2.0, false),
+    ("minotaur", "Minotaur", 10.0, false),
+    ("manticore", "Manticore", 22.0, false),
+    ("zyklop", "Cyclops", 8.0, false),
+    ("harpy", "Harpy", 28.0, false),
+    ("medusa", "Medusa", 6.0, false),
+    ("centaur", "Centaur", 18.0, false),
+    ("pegasus", "Pegasus", 35.0, false),
+    ("cerberus", "Cerberus", 4.0, false),
+    ("fury", "Erinys", 10.0, false),
+    ("griffin", "Griffin", 18.0, false),
+    ("calydonian_boar", "Calydonian Boar", 16.0, false),
+    ("big_transporter", "Transport Boat", 8.0, true),
+    ("small_transporter", "Fast Transport Ship", 15.0, true),
+    ("bireme", "Bireme", 15.0, true),
+    ("attack_ship", "Light Ship", 13.0, true),
+    ("demolition_ship", "Fire Ship", 5.0, true),
+    ("trireme", "Trireme", 15.0, true),
+    ("colonize_ship", "Colony Ship", 3.0, true),
+];
+
+pub fn default_units() -> Vec<Unit> {
+    DEFAULT_UNITS
+        .iter()
+        .map(|(id, name, speed, naval)| Unit {
+            id: (*id).to_string(),
+            name: (*name).to_string(),
+            speed: *speed,
+            naval: *naval,
+        })
+        .collect()
+}
+
+/// the fields of a unit in the `units.json` of the game that we care about
+#[derive(Deserialize)]
+struct JsonUnit {
+    name: String,
+    speed: f32,
+    #[serde(default)]
+    is_naval: bool,
+}
+
+/// Read the unit list the game uses, e.g. `GameData.units` copied from the browser console. It
+/// maps the id of each unit to its properties. Units that do not move (militia) are skipped.
+pub fn parse_units_json(text: &str) -> anyhow::Result<Vec<Unit>> {
+    let units: BTreeMap<String, JsonUnit> = serde_json::from_str(text)?;
+    let re = units
+        .into_iter()
+        .filter(|(_id, unit)| unit.speed > 0.0)
+        .map(|(id, unit)| Unit {
+            id,
+            name: unit.name,
+            speed: unit.speed,
+            naval: unit.is_naval,
+        })
+        .collect();
+    return Ok(re);
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TravelSettings {
+    #[serde(default = "default_units")]
+    pub units: Vec<Unit>,
+    /// id of the selected unit in `units`
+    pub unit: String,
+    pub world_speed: f32,
+    /// +10% speed for land units
+    #[serde(default)]
+    pub meteorology: bool,
+    /// +10% speed for ships
+    #[serde(default)]
+    pub cartography: bool,
+    /// +15% speed for ships
+    #[serde(default)]
+    pub lighthouse: bool,
+    /// any other bonus, e.g. from heroes or spells
+    #[serde(default)]
+    pub bonus_percent: f32,
+}
+
+impl Default for TravelSettings {
+    fn default() -> Self {
+        Self {
+            units: default_units(),
+            unit: String::from("bireme"),
+            world_speed: 1.0,
+            meteorology: false,
+            cartography: false,
+            lighthouse: false,
+            bonus_percent: 0.0,
+        }
+    }
+}
+
+impl TravelSettings {
+    pub fn selected_unit(&self) -> Option<&Unit> {
+        self.units.iter().find(|unit| unit.id == self.unit)
+    }
+
+    /// speed of the selected unit with all modifiers applied
+    fn effective_speed(&self) -> Option<f32> {
+        let unit = self.selected_unit()?;
+        let mut bonus = self.bonus_percent / 100.0;
+        if unit.naval {
+            if self.cartography {
+                bonus += 0.1;
+            }
+            if self.lighthouse {
+                bonus += 0.15;
+            }
+        } else if self.meteorology {
+            bonus += 0.1;
+        }
+        let speed = unit.speed * (1.0 + bonus) * self.world_speed;
+        return (speed > 0.0).then_some(speed);
+    }
+
+    /// seconds the selected unit needs for `distance` fields. None if no valid unit is selected.
+    pub fn travel_seconds(&self, distance: f32) -> Option<f32> {
+        let speed = self.effective_speed()?;
+        return Some(distance * PIXELS_PER_FIELD * SECONDS_PER_PIXEL / speed);
+    }
+
+    /// fields the selected unit gets far in `seconds`, the inverse of `travel_seconds`
+    pub fn travel_distance(&self, seconds: f32) -> Option<f32> {
+        let speed = self.effective_speed()?;
+        return Some(seconds * speed / (PIXELS_PER_FIELD * SECONDS_PER_PIXEL));
+    }
+}
+
+/// e.g. `1:05:09`
+pub fn format_duration(seconds: f32) -> String {
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    let seconds = seconds.round().max(0.0) as u64;
+    return format!(
+        "{}:{:02}:{:02}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(unit: &str, world_speed: f32) -> TravelSettings {
+        TravelSettings {
+            unit: unit.to_string(),
+            world_speed,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn travel_seconds_of_a_bireme_on_a_speed_2_world() {
+        // 10 fields are 1280 pixels, at 50 seconds per pixel for speed 1. A bireme has speed 15,
+        // so on a world with speed 2 it needs 1280 * 50 / 30 seconds.
+        let travel = settings("bireme", 2.0);
+        let seconds = travel.travel_seconds(10.0).unwrap();
+        assert!((seconds - 2133.333).abs() < 0.01, "{seconds}");
+        assert_eq!(format_duration(seconds), "0:35:33");
+
+        // a slow unit on a world with speed 1 takes hours
+        let seconds = settings("colonize_ship", 1.0).travel_seconds(10.0).unwrap();
+        assert_eq!(format_duration(seconds), "5:55:33");
+    }
+
+    #[test]
+    fn bonuses_apply_only_to_their_kind_of_unit() {
+        let base = settings("bireme", 1.0).travel_seconds(10.0).unwrap();
+
+        // meteorology is for land units only
+        let mut ship = settings("bireme", 1.0);
+        ship.meteorology = true;
+        assert!((ship.travel_seconds(10.0).unwrap() - base).abs() < 0.01);
+
+        // cartography and lighthouse add up to +25% speed
+        ship.cartography = true;
+        ship.lighthouse = true;
+        assert!((ship.travel_seconds(10.0).unwrap() - base / 1.25).abs() < 0.01);
+
+        let mut sword = settings("sword", 1.0);
+        let sword_base = sword.travel_seconds(10.0).unwrap();
+        sword.cartography = true;
+        sword.lighthouse = true;
+        assert!((sword.travel_seconds(10.0).unwrap() - sword_base).abs() < 0.01);
+        sword.meteorology = true;
+        sword.bonus_percent = 20.0;
+        assert!((sword.travel_seconds(10.0).unwrap() - sword_base / 1.3).abs() < 0.01);
+    }
+
+    #[test]
+    fn travel_seconds_without_a_known_unit() {
+        assert_eq!(settings("unknown", 1.0).travel_seconds(10.0), None);
+        assert_eq!(settings("bireme", 0.0).travel_seconds(10.0), None);
+        assert_eq!(settings("unknown", 1.0).travel_distance(600.0), None);
+    }
+
+    #[test]
+    fn travel_distance_is_the_inverse_of_travel_seconds() {
+        let mut travel = settings("bireme", 2.0);
+        travel.lighthouse = true;
+        let seconds = travel.travel_seconds(10.0).unwrap();
+        let distance = travel.travel_distance(seconds).unwrap();
+        assert!((distance - 10.0).abs() < 0.001, "{distance}");
+    }
+
+    #[test]
+    fn parse_units_json_skips_units_that_do_not_move() {
+        let text = r#"{
+            "bireme": {"name": "Bireme", "speed": 15, "is_naval": true},
+            "sword": {"name": "Swordsman", "speed": 8},
+            "militia": {"name": "Militia", "speed": 0}
+        }"#;
+        let units = parse_units_json(text).unwrap();
+        assert_eq!(
+            units,
+            vec![
+                Unit {
+                    id: String::from("bireme"),
+                    name: String::from("Bireme"),
+                    speed: 15.0,
+                    naval: true,
+                },
+                Unit {
+                    id: String::from("sword"),
+                    name: String::from("Swordsman"),
+                    speed: 8.0,
+                    naval: false,
+                },
+            ]
+        );
+        assert!(parse_units_json("not json").is_err());
+    }
+}
@@ -0,0 +1,57 @@
+use crate::emptyconstraint::EmptyConstraint;
+use crate::selection::TownSelection;
+use crate::spatial;
+
+use super::View;
+
+impl View {
+    /// ask what to do with the towns inside the rectangle or lasso that was drawn on the map
+    pub(crate) fn ui_area(&mut self, ctx: &egui::Context) {
+        let Some(area) = self.ui_data.area.as_ref().filter(|area| area.finished) else {
+            return;
+        };
+        let corners = area.corners();
+        let count = self
+            .ui_data
+            .town_index
+            .in_rect(egui::Rect::from_points(&corners))
+            .into_iter()
+            .filter_map(|index| self.ui_data.all_towns.get(index))
+            .filter(|town| spatial::polygon_contains(&corners, egui::pos2(town.x, town.y)))
+            .count();
+
+        let mut open = true;
+        let mut new_selection = false;
+        let mut target = None;
+        egui::Window::new(t!("map.area.title"))
+            .collapsible(false)
+            .resizable(false)
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(t!("map.area.towns", count = count));
+                if ui.button(t!("map.area.new_selection")).clicked() {
+                    new_selection = true;
+                }
+                ui.menu_button(t!("map.area.add_to_selection"), |ui| {
+                    for (index, selection) in self.ui_data.selections.iter().enumerate() {
+                        if ui.button(&selection.name).clicked() {
+                            target = Some(index);
+                            ui.close_menu();
+                        }
+                    }
+                });
+            });
+
+        if new_selection {
+            self.ui_data.selections.push(TownSelection::default());
+            target = Some(self.ui_data.selections.len() - 1);
+        }
+        if let Some(index) = target {
+            self.add_constraint_to_selection(index, &EmptyConstraint::in_area(&corners));
+            open = false;
+        }
+        if !open {
+            self.ui_data.area = None;
+        }
+    }
+}
@@ -0,0 +1,71 @@
+use strum::IntoEnumIterator;
+use time::OffsetDateTime;
+
+use crate::storage;
+use crate::view::preferences::AutoDeletePref;
+
+use super::View;
+
+impl View {
+    /// window to choose how long saved files are kept, with a preview of the files that would be
+    /// deleted right away
+    pub(crate) fn ui_auto_delete(&mut self, ctx: &egui::Context) {
+        let Some(mut choice) = self.ui_data.auto_delete_choice else {
+            return;
+        };
+        let to_delete =
+            storage::snapshots_to_delete(&self.ui_data.saved_db, choice, OffsetDateTime::now_utc());
+        let file_count: usize = self.ui_data.saved_db.values().map(Vec::len).sum();
+
+        let mut open = true;
+        let mut apply = false;
+        let mut cancel = false;
+        egui::Window::new(t!("menu.auto_delete.title"))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                for pref in AutoDeletePref::iter() {
+                    ui.radio_value(&mut choice, pref, pref.to_string());
+                }
+
+                ui.separator();
+                if to_delete.is_empty() {
+                    ui.label(t!("menu.auto_delete.nothing", total = file_count));
+                } else {
+                    ui.label(t!(
+                        "menu.auto_delete.preview",
+                        count = to_delete.len(),
+                        total = file_count
+                    ));
+                    // some people keep a file of every hour, so only render the visible rows
+                    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                    egui::ScrollArea::vertical()
+                        .id_source("auto delete preview")
+                        .max_height(300.0)
+                        .show_rows(ui, row_height, to_delete.len(), |ui, range| {
+                            for saved_db in &to_delete[range] {
+                                ui.label(t!(
+                                    "sidepanel.header.saved_file_entry",
+                                    server_id = saved_db.server_str,
+                                    db = saved_db
+                                ));
+                            }
+                        });
+                }
+
+                ui.separator();
+                ui.horizontal(|ui| {
+                    apply = ui.button(t!("menu.auto_delete.apply")).clicked();
+                    cancel = ui.button(t!("menu.auto_delete.cancel")).clicked();
+                });
+            });
+
+        if apply {
+            self.ui_data.preferences.auto_delete = choice;
+            self.ui_data.preferences.auto_delete_confirmed = true;
+            self.presenter.set_auto_delete(choice);
+            storage::apply_auto_delete(choice);
+            self.ui_data.saved_db = storage::get_list_of_saved_dbs();
+        }
+        self.ui_data.auto_delete_choice = (open && !apply && !cancel).then_some(choice);
+    }
+}
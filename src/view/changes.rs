@@ -0,0 +1,105 @@
+use std::sync::Arc;
+
+use strum::IntoEnumIterator;
+
+use crate::model::diff::{ChangeKind, ChangeRecord};
+use crate::storage;
+
+use super::View;
+
+/// the ring a change is marked with on the map
+fn ring(ui: &mut egui::Ui, color: egui::Color32) {
+    let size = ui.text_style_height(&egui::TextStyle::Body);
+    let (rect, _) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::hover());
+    ui.painter()
+        .circle_stroke(rect.center(), size / 3.0, egui::Stroke::new(1.5, color));
+}
+
+impl View {
+    /// window listing what changed between the compared snapshot and the loaded data
+    pub(crate) fn ui_changes(&mut self, ctx: &egui::Context) {
+        let Some(changes) = self.ui_data.changes.as_ref().map(Arc::clone) else {
+            return;
+        };
+
+        let mut open = true;
+        let mut clicked_record: Option<&ChangeRecord> = None;
+        egui::Window::new(t!("map.changes.title"))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.label(t!(
+                    "map.changes.between",
+                    older = storage::format_timestamp(changes.older_time),
+                    newer = storage::format_timestamp(changes.newer_time)
+                ));
+
+                // which kinds of changes to show, with their colour on the map
+                for kind in ChangeKind::iter() {
+                    ui.horizontal(|ui| {
+                        ring(ui, kind.color());
+                        let mut shown = !self.ui_data.hidden_changes.contains(&kind);
+                        let text = t!(
+                            "map.changes.kind_entry",
+                            kind = kind.to_string(),
+                            count = changes.count(kind)
+                        );
+                        if ui.checkbox(&mut shown, text).changed() {
+                            if shown {
+                                self.ui_data.hidden_changes.remove(&kind);
+                            } else {
+                                self.ui_data.hidden_changes.insert(kind);
+                            }
+                        }
+                    });
+                }
+
+                ui.separator();
+                let records: Vec<&ChangeRecord> = changes
+                    .records
+                    .iter()
+                    .filter(|record| !self.ui_data.hidden_changes.contains(&record.kind))
+                    .collect();
+                if records.is_empty() {
+                    ui.label(t!("map.changes.nothing"));
+                    return;
+                }
+
+                // there can be a change for almost every town, so only render the visible rows
+                let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                egui::ScrollArea::both()
+                    .id_source("changes list")
+                    .max_height(300.0)
+                    .show_rows(ui, row_height, records.len(), |ui, range| {
+                        for record in &records[range] {
+                            ui.horizontal(|ui| {
+                                ring(ui, record.kind.color());
+                                if ui.link(&record.subject).clicked() {
+                                    clicked_record = Some(record);
+                                }
+                                ui.add(
+                                    egui::Label::new(t!(
+                                        "map.changes.record",
+                                        kind = record.kind.to_string(),
+                                        before = record.before.as_deref().unwrap_or("-"),
+                                        after = record.after.as_deref().unwrap_or("-")
+                                    ))
+                                    .wrap(false),
+                                );
+                            });
+                        }
+                    });
+            });
+
+        // jump to the town, or the first town of the player
+        if let Some(town) = clicked_record.and_then(|record| record.towns.first()) {
+            if let Some(canvas) = &mut self.ui_data.canvas {
+                canvas.center_on(egui::pos2(town.x, town.y));
+            }
+            self.ui_data.selected_town = self.presenter.get_town_details(town.id);
+        }
+        if !open {
+            self.ui_data.compare_with = None;
+            self.ui_data.changes = None;
+        }
+    }
+}
@@ -1,15 +1,29 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Mutex;
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
+    fmt,
     ops::{Add, Div, Mul, Sub},
     sync::Arc,
 };
 
 use serde::{Deserialize, Serialize};
+use strum_macros::EnumIter;
 
+use crate::export::BBCodeOptions;
+use crate::model::diff::{ChangeKind, ChangeSet};
+use crate::model::report::ParseReport;
+use crate::model::source::DataSourceConfig;
 use crate::selection::TownSelection;
+use crate::spatial::SpatialIndex;
 #[cfg(not(target_arch = "wasm32"))]
 use crate::storage::SavedDB;
-use crate::town::Town;
+use crate::town::{InactivePlayer, Town, TownDetails};
+use crate::travel::TravelSettings;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::view::preferences::AutoDeletePref;
 use crate::view::preferences::{DarkModePref, Preferences};
 
 pub const ALL_TOWNS_DARK: egui::Color32 = egui::Color32::from_gray(60);
@@ -22,6 +36,234 @@ pub struct DefaultTownGroup {
     pub color: egui::Color32,
 }
 
+/// which part of the world ends up in an exported image
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumIter)]
+#[cfg(not(target_arch = "wasm32"))]
+pub enum ImageRegion {
+    #[default]
+    World,
+    CurrentView,
+    /// the `ImageExportOptions::coordinates`
+    Coordinates,
+}
+
+/// a rectangle of the world, in world coordinates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg(not(target_arch = "wasm32"))]
+pub struct WorldRange {
+    pub min_x: u16,
+    pub min_y: u16,
+    pub max_x: u16,
+    pub max_y: u16,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for WorldRange {
+    fn default() -> Self {
+        Self {
+            min_x: 0,
+            min_y: 0,
+            max_x: 1000,
+            max_y: 1000,
+        }
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl WorldRange {
+    pub fn rect(self) -> egui::Rect {
+        egui::Rect::from_min_max(
+            egui::pos2(f32::from(self.min_x), f32::from(self.min_y)),
+            egui::pos2(f32::from(self.max_x), f32::from(self.max_y)),
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg(not(target_arch = "wasm32"))]
+pub struct ImageExportOptions {
+    #[serde(default)]
+    pub region: ImageRegion,
+    /// the region for `ImageRegion::Coordinates`
+    #[serde(default)]
+    pub coordinates: WorldRange,
+    /// in pixels
+    pub width: u32,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for ImageExportOptions {
+    fn default() -> Self {
+        Self {
+            region: ImageRegion::World,
+            coordinates: WorldRange::default(),
+            width: 2000,
+        }
+    }
+}
+
+/// how far an image export that runs in the background got
+#[derive(Debug, Clone)]
+#[cfg(not(target_arch = "wasm32"))]
+pub enum ImageExportProgress {
+    /// drawing the map, with the size of the image in pixels
+    Rendering(u32, u32),
+    Writing,
+    Done(PathBuf),
+    Failed(String),
+}
+
+/// how the preloaded snapshots of a server are played back
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[cfg(not(target_arch = "wasm32"))]
+pub struct PlaybackOptions {
+    pub seconds_per_snapshot: f32,
+    /// start over with the first snapshot after the last one
+    pub repeat: bool,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl Default for PlaybackOptions {
+    fn default() -> Self {
+        Self {
+            seconds_per_snapshot: 1.0,
+            repeat: false,
+        }
+    }
+}
+
+/// stepping through preloaded snapshots of the loaded server, see `View::ui_playback`
+#[derive(Debug, Clone, Default)]
+#[cfg(not(target_arch = "wasm32"))]
+pub struct PlaybackState {
+    /// first and last snapshot to preload, as indices into the chronologically sorted saved
+    /// snapshots of the server. None for all of them.
+    pub range: Option<(usize, usize)>,
+    /// the snapshots are being preloaded or played back
+    pub active: bool,
+    /// unix timestamps of the preloaded snapshots, oldest first
+    pub times: Vec<i64>,
+    /// number of snapshots that are still being preloaded
+    pub remaining: usize,
+    /// index into `times` of the snapshot on the map. None while the loaded data is shown.
+    pub frame: Option<usize>,
+    pub playing: bool,
+    /// `egui::InputState::time` when `frame` was shown, to know when to show the next one
+    pub frame_shown_at: f64,
+}
+
+/// what the coloured territories behind the towns show
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumIter)]
+pub enum TerritoryMode {
+    #[default]
+    Off,
+    /// one territory per visible selection, in its colour
+    Selections,
+    /// one territory for each of the alliances with the most towns
+    Alliances,
+}
+
+/// which towns and colours a territory image was computed from, to know when it is outdated
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TerritoryKey {
+    pub mode: TerritoryMode,
+    /// address and length of each list of towns, with the colour it was drawn in
+    pub groups: Vec<(usize, usize, egui::Color32)>,
+}
+
+/// the territory image on the gpu, drawn behind the towns
+#[derive(Clone)]
+pub struct Territory {
+    pub key: TerritoryKey,
+    pub texture: egui::TextureHandle,
+    /// the pixels of the texture, to draw them into exported map images as well
+    #[cfg(not(target_arch = "wasm32"))]
+    pub image: Arc<egui::ColorImage>,
+    /// name, colour and number of towns of each territory that is not a selection
+    pub legend: Vec<(String, egui::Color32, usize)>,
+}
+
+impl fmt::Debug for Territory {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Territory")
+            .field("key", &self.key)
+            .field("legend", &self.legend)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AreaShape {
+    /// drawn by dragging with shift held down
+    Rectangle,
+    /// drawn by dragging with ctrl (cmd on mac) held down
+    Lasso,
+}
+
+/// an outline drawn on the map to select the towns inside of it
+#[derive(Debug, Clone)]
+pub struct AreaOutline {
+    pub shape: AreaShape,
+    /// in world coordinates. For a rectangle only the first and the last point matter.
+    pub points: Vec<egui::Pos2>,
+    /// the mouse was released, now we wait for the user to decide what to do with the towns
+    pub finished: bool,
+}
+
+impl AreaOutline {
+    /// the corners of the outline. Empty if it does not enclose anything yet.
+    pub fn corners(&self) -> Vec<egui::Pos2> {
+        match self.shape {
+            AreaShape::Rectangle => {
+                let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+                    return Vec::new();
+                };
+                let rect = egui::Rect::from_two_pos(*first, *last);
+                if !rect.is_positive() {
+                    return Vec::new();
+                }
+                vec![
+                    rect.left_top(),
+                    rect.right_top(),
+                    rect.right_bottom(),
+                    rect.left_bottom(),
+                ]
+            }
+            AreaShape::Lasso if self.points.len() >= 3 => self.points.clone(),
+            AreaShape::Lasso => Vec::new(),
+        }
+    }
+}
+/// what the list of inactive players can be sorted by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize, EnumIter)]
+pub enum InactiveColumn {
+    Name,
+    Alliance,
+    Points,
+    Towns,
+    #[default]
+    Days,
+    Snapshots,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct InactiveListOptions {
+    pub sort_by: InactiveColumn,
+    pub descending: bool,
+    /// only players that have been inactive for more days than this are listed
+    pub min_days: f64,
+}
+
+impl Default for InactiveListOptions {
+    fn default() -> Self {
+        Self {
+            sort_by: InactiveColumn::Days,
+            descending: true,
+            min_days: 3.0,
+        }
+    }
+}
+
 /// contains all the data required to draw the ui.
 #[derive(Clone, Serialize, Deserialize, Debug)]
 #[serde(deny_unknown_fields)]
@@ -40,6 +282,34 @@ pub struct Data {
     pub all_towns: Arc<Vec<Town>>,
     #[serde(skip)]
     pub ghost_towns: Arc<Vec<Town>>,
+    /// positions of `all_towns`, to find the towns in a part of the map quickly
+    #[serde(skip)]
+    pub town_index: Arc<SpatialIndex>,
+
+    /// bad lines and misplaced towns of the currently loaded data
+    #[serde(skip)]
+    pub parse_report: Arc<ParseReport>,
+    #[serde(skip)]
+    pub show_diagnostics: bool,
+
+    #[serde(default)]
+    pub territory_mode: TerritoryMode,
+    #[serde(skip)]
+    pub territory: Option<Territory>,
+
+    /// the rectangle or lasso that is drawn on the map
+    #[serde(skip)]
+    pub area: Option<AreaOutline>,
+
+    /// the town that was clicked on the map, shown in the detail panel
+    #[serde(skip)]
+    pub selected_town: Option<TownDetails>,
+
+    #[serde(default)]
+    pub show_legend: bool,
+    /// top left corner of the legend relative to the map, as a fraction of the map size
+    #[serde(skip)]
+    pub legend_position: egui::Vec2,
 
     #[serde(skip)]
     #[cfg(not(target_arch = "wasm32"))]
@@ -51,11 +321,71 @@ pub struct Data {
     #[cfg(not(target_arch = "wasm32"))]
     pub history_index: Option<usize>,
 
+    /// the saved snapshot the loaded data is compared with
+    #[serde(skip)]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub compare_with: Option<SavedDB>,
+    /// why the last snapshot chosen to compare with could not be read
+    #[serde(skip)]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub comparison_error: Option<String>,
+    /// what changed between `compare_with` and the loaded data
+    #[serde(skip)]
+    pub changes: Option<Arc<ChangeSet>>,
+    /// kinds of changes that are neither drawn on the map nor listed
+    #[serde(default)]
+    pub hidden_changes: BTreeSet<ChangeKind>,
+
+    #[serde(skip)]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub playback: PlaybackState,
+    #[serde(default)]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub playback_options: PlaybackOptions,
+
+    /// every player with towns and how long they have been inactive, sorted as in
+    /// `inactive_list`. None until the list is shown.
+    #[serde(skip)]
+    pub inactive_players: Option<Vec<InactivePlayer>>,
+    #[serde(skip)]
+    pub show_inactive_players: bool,
+    #[serde(default)]
+    pub inactive_list: InactiveListOptions,
+
     #[serde(skip)]
     #[cfg(target_arch = "wasm32")]
     pub url: Option<String>,
 
     pub preferences: Preferences,
+    /// the setting picked in the window for deleting saved files automatically, not applied yet.
+    /// None while the window is closed.
+    #[serde(skip)]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub auto_delete_choice: Option<AutoDeletePref>,
+
+    #[serde(default)]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub image_export: ImageExportOptions,
+    /// the image export that is running in the background or finished last. None once the user
+    /// closed the window showing it.
+    #[serde(skip)]
+    #[cfg(not(target_arch = "wasm32"))]
+    pub image_export_progress: Option<Arc<Mutex<ImageExportProgress>>>,
+
+    /// unit and modifiers for travel times in the hover popup and travel time constraints
+    #[serde(default)]
+    pub travel: TravelSettings,
+    /// id of the town travel times in the hover popup are measured from
+    #[serde(default)]
+    pub travel_origin: Option<u32>,
+
+    /// how the towns of a selection are laid out when copied as `BBCode`
+    #[serde(default)]
+    pub bbcode_options: BBCodeOptions,
+
+    /// where the world data is fetched from
+    #[serde(default)]
+    pub download_source: DataSourceConfig,
 }
 
 impl Default for Data {
@@ -65,6 +395,15 @@ impl Default for Data {
             canvas: None,
             all_towns: Arc::new(Vec::new()),
             ghost_towns: Arc::new(Vec::new()),
+            town_index: Arc::new(SpatialIndex::default()),
+            parse_report: Arc::new(ParseReport::default()),
+            show_diagnostics: false,
+            territory_mode: TerritoryMode::Off,
+            territory: None,
+            area: None,
+            selected_town: None,
+            show_legend: false,
+            legend_position: egui::Vec2::ZERO,
             selections: vec![TownSelection::default()],
             settings_ghosts: DefaultTownGroup {
                 enabled: true,
@@ -78,9 +417,32 @@ impl Default for Data {
             saved_db: BTreeMap::new(),
             #[cfg(not(target_arch = "wasm32"))]
             history_index: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            compare_with: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            comparison_error: None,
+            changes: None,
+            hidden_changes: BTreeSet::new(),
+            #[cfg(not(target_arch = "wasm32"))]
+            playback: PlaybackState::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            playback_options: PlaybackOptions::default(),
+            inactive_players: None,
+            show_inactive_players: false,
+            inactive_list: InactiveListOptions::default(),
             #[cfg(target_arch = "wasm32")]
             url: None,
             preferences: Preferences::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            auto_delete_choice: None,
+            #[cfg(not(target_arch = "wasm32"))]
+            image_export: ImageExportOptions::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            image_export_progress: None,
+            travel: TravelSettings::default(),
+            travel_origin: None,
+            bbcode_options: BBCodeOptions::default(),
+            download_source: DataSourceConfig::default(),
         }
     }
 }
@@ -121,16 +483,25 @@ pub struct CanvasData {
     /// distance from top left of canvas to top left of grepolis coordinates
     pub world_offset_px: egui::Vec2,
     pub zoom: f32,
+    /// the part of the screen the map was drawn in during the last frame
+    pub screen_rect: egui::Rect,
 }
 
 impl CanvasData {
-    pub fn new(top_left: egui::Vec2) -> Self {
+    pub fn new(top_left: egui::Vec2, screen_rect: egui::Rect) -> Self {
         Self {
             world_offset_px: top_left,
             zoom: 1.0,
+            screen_rect,
         }
     }
 
+    /// move the map so `world` ends up in the centre of the map
+    pub fn center_on(&mut self, world: egui::Pos2) {
+        self.world_offset_px =
+            world.to_vec2() - self.scale_screen_to_world(self.screen_rect.center().to_vec2());
+    }
+
     pub fn world_to_screen<T>(&self, world: T) -> T
     where
         T: Mul<f32, Output = T>,
@@ -182,6 +553,14 @@ impl ViewPortFilter {
         }
     }
 
+    /// the visible part of the world
+    pub fn world_rect(&self) -> egui::Rect {
+        egui::Rect::from_min_max(
+            egui::pos2(self.world_l, self.world_t),
+            egui::pos2(self.world_r, self.world_b),
+        )
+    }
+
     pub fn town_in_viewport(&self, town: &Town) -> bool {
         self.world_l < town.x
             && town.x < self.world_r
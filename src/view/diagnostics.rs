@@ -0,0 +1,76 @@
+use std::sync::Arc;
+
+use crate::model::report::FallbackReason;
+
+use super::View;
+
+impl View {
+    /// window listing everything that went wrong while parsing the currently loaded data
+    pub(crate) fn ui_diagnostics(&mut self, ctx: &egui::Context) {
+        let report = Arc::clone(&self.ui_data.parse_report);
+        egui::Window::new(t!("menu.diagnostics.title"))
+            .open(&mut self.ui_data.show_diagnostics)
+            .show(ctx, |ui| {
+                if report.is_empty() {
+                    ui.label(t!("menu.diagnostics.all_good"));
+                    return;
+                }
+
+                // the lists can contain thousands of entries, so only render the visible rows
+                let row_height = ui.text_style_height(&egui::TextStyle::Body);
+                ui.collapsing(
+                    t!("menu.diagnostics.bad_lines", count = report.bad_lines.len()),
+                    |ui| {
+                        egui::ScrollArea::both()
+                            .id_source("diagnostics bad lines")
+                            .max_height(300.0)
+                            .show_rows(ui, row_height, report.bad_lines.len(), |ui, range| {
+                                for bad_line in &report.bad_lines[range] {
+                                    ui.add(
+                                        egui::Label::new(t!(
+                                            "menu.diagnostics.bad_line",
+                                            file = bad_line.file.txt_stem(),
+                                            number = bad_line.line_number,
+                                            error = bad_line.error
+                                        ))
+                                        .wrap(false),
+                                    );
+                                }
+                            });
+                    },
+                );
+                ui.collapsing(
+                    t!("menu.diagnostics.fallbacks", count = report.fallbacks.len()),
+                    |ui| {
+                        egui::ScrollArea::both()
+                            .id_source("diagnostics fallbacks")
+                            .max_height(300.0)
+                            .show_rows(ui, row_height, report.fallbacks.len(), |ui, range| {
+                                for fallback in &report.fallbacks[range] {
+                                    let text = match fallback.reason {
+                                        FallbackReason::MissingIsland { x, y } => t!(
+                                            "menu.diagnostics.missing_island",
+                                            town = fallback.town_name,
+                                            id = fallback.town_id,
+                                            x = x,
+                                            y = y
+                                        ),
+                                        FallbackReason::MissingOffset {
+                                            island_type,
+                                            slot_number,
+                                        } => t!(
+                                            "menu.diagnostics.missing_offset",
+                                            town = fallback.town_name,
+                                            id = fallback.town_id,
+                                            typ = island_type,
+                                            slot = slot_number
+                                        ),
+                                    };
+                                    ui.add(egui::Label::new(text).wrap(false));
+                                }
+                            });
+                    },
+                );
+            });
+    }
+}
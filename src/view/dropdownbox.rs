@@ -10,6 +10,8 @@ pub struct DropDownBox<'a> {
     buf: &'a mut String,
     popup_id: Id,
     opt_it: Option<&'a Arc<Vec<String>>>,
+    /// how the values are listed, if not as they are
+    label_of: Option<fn(&str) -> String>,
 }
 
 impl<'a> DropDownBox<'a> {
@@ -23,8 +25,16 @@ impl<'a> DropDownBox<'a> {
             popup_id: Id::new(id_source),
             opt_it,
             buf,
+            label_of: None,
         }
     }
+
+    /// List each value as returned by `label_of`, e.g. a timestamp as a date. Picking an entry
+    /// still puts the value itself into the text field.
+    pub fn with_labels(mut self, label_of: Option<fn(&str) -> String>) -> Self {
+        self.label_of = label_of;
+        self
+    }
 }
 
 impl Widget for DropDownBox<'_> {
@@ -33,6 +43,7 @@ impl Widget for DropDownBox<'_> {
             popup_id,
             buf,
             opt_it,
+            label_of,
         } = self;
 
         let mut r = ui.text_edit_singleline(buf);
@@ -55,11 +66,14 @@ impl Widget for DropDownBox<'_> {
                 };
 
                 for var in &**it {
-                    let s = var.as_ref();
-                    if buf.is_empty() {
+                    let label = label_of.map(|label_of| label_of(var));
+                    let s = label.as_deref().unwrap_or(var);
+                    // the text field contains the value, which is not what is listed
+                    let value_matches = label.is_some() && var.contains(&*buf);
+                    if buf.is_empty() || value_matches {
                         let mut job = LayoutJob::default();
                         job.append(s, 0.0, TextFormat::default());
-                        first.push((s.to_string(), job));
+                        first.push((var.clone(), job));
                         continue;
                     }
 
@@ -88,7 +102,7 @@ impl Widget for DropDownBox<'_> {
                             job.append(&s[*index..cursor], 0.0, emphasize.clone());
                         }
                         job.append(&s[cursor..], 0.0, TextFormat::default());
-                        first.push((s.to_string(), job));
+                        first.push((var.clone(), job));
                     } else {
                         // matching somewhere in the string, but not the start
                         let mut cursor = 0;
@@ -99,7 +113,7 @@ impl Widget for DropDownBox<'_> {
                             job.append(&s[*index..cursor], 0.0, emphasize.clone());
                         }
                         job.append(&s[cursor..], 0.0, TextFormat::default());
-                        second.push((s.to_string(), job));
+                        second.push((var.clone(), job));
                     }
                 }
                 let combined = [first, second].concat();
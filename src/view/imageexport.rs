@@ -0,0 +1,102 @@
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use super::data::ImageExportProgress;
+use super::View;
+
+impl View {
+    /// Render the map as svg or png, depending on `extension`, into `file_path` in the background.
+    /// Rasterizing a large png can take a while. The progress is shown by `ui_image_export_progress`.
+    pub(crate) fn start_image_export(
+        &mut self,
+        ctx: &egui::Context,
+        file_path: PathBuf,
+        extension: &str,
+    ) {
+        let image = self.map_image(ctx);
+        let progress = Arc::new(Mutex::new(ImageExportProgress::Rendering(
+            image.width,
+            image.height(),
+        )));
+        self.ui_data.image_export_progress = Some(Arc::clone(&progress));
+
+        let is_svg = extension == "svg";
+        let _handle = std::thread::spawn(move || {
+            let bytes = if is_svg {
+                image.to_svg().map(String::into_bytes)
+            } else {
+                image.to_png()
+            };
+            let result = match bytes {
+                Ok(bytes) => {
+                    *progress.lock().unwrap() = ImageExportProgress::Writing;
+                    match std::fs::write(&file_path, bytes) {
+                        Ok(()) => ImageExportProgress::Done(file_path),
+                        Err(err) => {
+                            eprintln!(
+                                "Failed to write the image to file ({}) Error: {err:?}",
+                                file_path.display()
+                            );
+                            ImageExportProgress::Failed(format!("{}: {err}", file_path.display()))
+                        }
+                    }
+                }
+                Err(err) => {
+                    eprintln!("Failed to render the map: {err:?}");
+                    ImageExportProgress::Failed(format!("{err:#}"))
+                }
+            };
+            *progress.lock().unwrap() = result;
+        });
+    }
+
+    /// small window with the state of the running image export, or how the last one ended
+    pub(crate) fn ui_image_export_progress(&mut self, ctx: &egui::Context) {
+        let Some(progress) = &self.ui_data.image_export_progress else {
+            return;
+        };
+        let progress = progress.lock().unwrap().clone();
+
+        let mut open = true;
+        egui::Window::new(t!("menu.export_image.progress_title"))
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| match &progress {
+                ImageExportProgress::Rendering(width, height) => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(t!(
+                            "menu.export_image.rendering",
+                            width = width,
+                            height = height
+                        ));
+                    });
+                }
+                ImageExportProgress::Writing => {
+                    ui.horizontal(|ui| {
+                        ui.spinner();
+                        ui.label(t!("menu.export_image.writing"));
+                    });
+                }
+                ImageExportProgress::Done(file_path) => {
+                    ui.label(t!("menu.export_image.done", path = file_path.display()));
+                }
+                ImageExportProgress::Failed(reason) => {
+                    ui.label(t!("menu.export_image.failed", reason = reason));
+                }
+            });
+
+        let finished = matches!(
+            progress,
+            ImageExportProgress::Done(_) | ImageExportProgress::Failed(_)
+        );
+        if !finished {
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
+        if !open {
+            // the export keeps running in the background, only the window is gone
+            self.ui_data.image_export_progress = None;
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::cmp::Ordering;
+
+use egui_extras::{Column, TableBuilder};
+use strum::IntoEnumIterator;
+
+use crate::constraint::{Comparator, ConstraintType};
+use crate::emptyconstraint::EmptyConstraint;
+use crate::town::{InactivePlayer, Town};
+
+use super::data::{InactiveColumn, InactiveListOptions};
+use super::View;
+
+impl InactiveColumn {
+    fn header(self) -> String {
+        match self {
+            InactiveColumn::Name => t!("menu.inactive.column.name"),
+            InactiveColumn::Alliance => t!("menu.inactive.column.alliance"),
+            InactiveColumn::Points => t!("menu.inactive.column.points"),
+            InactiveColumn::Towns => t!("menu.inactive.column.towns"),
+            InactiveColumn::Days => t!("menu.inactive.column.days"),
+            InactiveColumn::Snapshots => t!("menu.inactive.column.snapshots"),
+        }
+    }
+
+    /// the order a column is sorted in when it is clicked first. Names from a to z, numbers from
+    /// the largest to the smallest.
+    fn descending_by_default(self) -> bool {
+        !matches!(self, InactiveColumn::Name | InactiveColumn::Alliance)
+    }
+
+    fn compare(self, a: &InactivePlayer, b: &InactivePlayer) -> Ordering {
+        match self {
+            InactiveColumn::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+            InactiveColumn::Alliance => a
+                .alliance_name
+                .as_ref()
+                .map(|name| name.to_lowercase())
+                .cmp(&b.alliance_name.as_ref().map(|name| name.to_lowercase())),
+            InactiveColumn::Points => a.points.cmp(&b.points),
+            InactiveColumn::Towns => a.towns.cmp(&b.towns),
+            InactiveColumn::Days => a.inactive_days.total_cmp(&b.inactive_days),
+            InactiveColumn::Snapshots => a.snapshots.cmp(&b.snapshots),
+        }
+    }
+}
+
+fn sort_players(players: &mut [InactivePlayer], options: InactiveListOptions) {
+    players.sort_by(|a, b| {
+        let ordering = options.sort_by.compare(a, b);
+        if options.descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+}
+
+/// the players in a table with a header to sort by. Returns the clicked column header and the town
+/// of the clicked player, if any.
+fn players_table(
+    ui: &mut egui::Ui,
+    rows: &[&InactivePlayer],
+    options: &InactiveListOptions,
+) -> (Option<InactiveColumn>, Option<Town>) {
+    let mut clicked_column = None;
+    let mut clicked_player: Option<&InactivePlayer> = None;
+    // every player of the world may be listed, so only render the visible rows
+    let row_height = ui.text_style_height(&egui::TextStyle::Body);
+    let mut table = TableBuilder::new(ui)
+        .striped(true)
+        .max_scroll_height(300.0)
+        .cell_layout(egui::Layout::left_to_right(egui::Align::Center));
+    for _column in InactiveColumn::iter() {
+        table = table.column(Column::auto().at_least(50.0));
+    }
+    table
+        .header(row_height * 1.5, |mut header| {
+            for column in InactiveColumn::iter() {
+                header.col(|ui| {
+                    let sorted = options.sort_by == column;
+                    let text = match (sorted, options.descending) {
+                        (false, _) => column.header(),
+                        (true, true) => format!("{} ⬇", column.header()),
+                        (true, false) => format!("{} ⬆", column.header()),
+                    };
+                    if ui.selectable_label(sorted, text).clicked() {
+                        clicked_column = Some(column);
+                    }
+                });
+            }
+        })
+        .body(|body| {
+            body.rows(row_height, rows.len(), |mut row| {
+                let player = rows[row.index()];
+                row.col(|ui| {
+                    if ui.link(&player.name).clicked() {
+                        clicked_player = Some(player);
+                    }
+                });
+                row.col(|ui| {
+                    ui.label(player.alliance_name.as_deref().unwrap_or("-"));
+                });
+                row.col(|ui| {
+                    ui.label(player.points.to_string());
+                });
+                row.col(|ui| {
+                    ui.label(player.towns.to_string());
+                });
+                row.col(|ui| {
+                    ui.label(format!("{:.1}", player.inactive_days));
+                });
+                row.col(|ui| {
+                    ui.label(player.snapshots.to_string());
+                });
+            });
+        });
+    return (
+        clicked_column,
+        clicked_player.and_then(|player| player.first_town.clone()),
+    );
+}
+
+impl View {
+    /// window listing the players that did not grow for a while, e.g. to find towns to conquer
+    pub(crate) fn ui_inactive_players(&mut self, ctx: &egui::Context) {
+        if !self.ui_data.show_inactive_players {
+            return;
+        }
+        let mut players = self.ui_data.inactive_players.take().unwrap_or_else(|| {
+            let mut players = self.presenter.get_inactive_players();
+            sort_players(&mut players, self.ui_data.inactive_list);
+            players
+        });
+
+        let mut open = true;
+        let mut clicked_column = None;
+        let mut clicked_town: Option<Town> = None;
+        let mut target_selection = None;
+        let options = &mut self.ui_data.inactive_list;
+        let selections = &self.ui_data.selections;
+        egui::Window::new(t!("menu.inactive.title"))
+            .open(&mut open)
+            .show(ctx, |ui| {
+                ui.add(egui::Label::new(t!("menu.inactive.explanation")).wrap(true));
+                if players.iter().all(|player| player.snapshots == 0) {
+                    ui.label(t!("menu.inactive.no_history"));
+                }
+                ui.horizontal(|ui| {
+                    ui.label(t!("menu.inactive.min_days"));
+                    ui.add(egui::DragValue::new(&mut options.min_days).clamp_range(0.0..=365.0));
+                    ui.label(t!("menu.inactive.days"));
+                    ui.menu_button(t!("menu.inactive.add_to_selection"), |ui| {
+                        for (index, selection) in selections.iter().enumerate() {
+                            if ui.button(&selection.name).clicked() {
+                                target_selection = Some(index);
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                });
+
+                ui.separator();
+                let rows: Vec<&InactivePlayer> = players
+                    .iter()
+                    .filter(|player| player.inactive_days > options.min_days)
+                    .collect();
+                ui.label(t!("menu.inactive.count", count = rows.len()));
+                if rows.is_empty() {
+                    ui.label(t!("menu.inactive.nothing"));
+                    return;
+                }
+
+                (clicked_column, clicked_town) = players_table(ui, &rows, options);
+            });
+
+        // clicking the sorted column again reverses the order
+        if let Some(column) = clicked_column {
+            let options = &mut self.ui_data.inactive_list;
+            if options.sort_by == column {
+                options.descending = !options.descending;
+            } else {
+                options.sort_by = column;
+                options.descending = column.descending_by_default();
+            }
+            sort_players(&mut players, *options);
+        }
+        if let Some(index) = target_selection {
+            let constraint = EmptyConstraint {
+                constraint_type: ConstraintType::PlayerInactiveDays,
+                comparator: Comparator::GreaterThan,
+                value: self.ui_data.inactive_list.min_days.to_string(),
+            };
+            self.add_constraint_to_selection(index, &constraint);
+        }
+        // jump to the first town of the player
+        if let Some(town) = clicked_town {
+            if let Some(canvas) = &mut self.ui_data.canvas {
+                canvas.center_on(egui::pos2(town.x, town.y));
+            }
+            self.ui_data.selected_town = self.presenter.get_town_details(town.id);
+        }
+        self.ui_data.inactive_players = Some(players);
+        self.ui_data.show_inactive_players = open;
+    }
+}
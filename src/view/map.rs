@@ -1,15 +1,181 @@
 use egui::Shape;
+use strum::IntoEnumIterator;
 
-use crate::town::Town;
+use crate::model::diff::ChangeKind;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::render::{MapImage, MapLayer};
+#[cfg(not(target_arch = "wasm32"))]
+use crate::selection::MarkerStyle;
+use crate::town::{ocean_number, Town};
+use crate::travel;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
 
+#[cfg(not(target_arch = "wasm32"))]
+use super::data::ImageRegion;
 use super::{
-    data::{CanvasData, ViewPortFilter},
+    data::{AreaOutline, AreaShape, CanvasData, ViewPortFilter},
     View,
 };
 
+/// draw the marker of a single town. Towns with an approximate position are drawn as a ring
+/// instead of a dot, so it is visible that they are not exactly where they are drawn.
+fn draw_town(
+    painter: &egui::Painter,
+    center: egui::Pos2,
+    radius: f32,
+    color: egui::Color32,
+    approximate: bool,
+) {
+    if approximate {
+        painter.circle_stroke(center, radius, egui::Stroke::new(1.0, color));
+    } else {
+        painter.circle_filled(center, radius, color);
+    }
+}
+
 impl View {
+    /// the map with the same layers as `ui_map`, to be rendered into an image file
+    #[cfg(not(target_arch = "wasm32"))]
+    pub(crate) fn map_image(&self, ctx: &egui::Context) -> MapImage {
+        let options = self.ui_data.image_export;
+        let region = match (options.region, &self.ui_data.canvas) {
+            (ImageRegion::CurrentView, Some(canvas)) => egui::Rect::from_min_max(
+                canvas
+                    .screen_to_world(canvas.screen_rect.min.to_vec2())
+                    .to_pos2(),
+                canvas
+                    .screen_to_world(canvas.screen_rect.max.to_vec2())
+                    .to_pos2(),
+            ),
+            (ImageRegion::Coordinates, _) => options.coordinates.rect(),
+            _ => egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(1000.0, 1000.0)),
+        };
+
+        let mut layers = Vec::new();
+        if self.ui_data.settings_all.enabled {
+            layers.push(MapLayer {
+                name: t!("map.legend.all_towns"),
+                color: self.ui_data.settings_all.color,
+                towns: Arc::clone(&self.ui_data.all_towns),
+                min_radius: 1.0,
+                marker: MarkerStyle::default(),
+            });
+        }
+        if self.ui_data.settings_ghosts.enabled {
+            layers.push(MapLayer {
+                name: t!("map.legend.ghost_towns"),
+                color: self.ui_data.settings_ghosts.color,
+                towns: Arc::clone(&self.ui_data.ghost_towns),
+                min_radius: 2.0,
+                marker: MarkerStyle::default(),
+            });
+        }
+        for selection in self
+            .ui_data
+            .selections
+            .iter()
+            .filter(|selection| !selection.is_hidden())
+        {
+            layers.push(MapLayer {
+                name: selection.name.clone(),
+                color: selection.color,
+                towns: Arc::clone(&selection.towns),
+                min_radius: 1.0,
+                marker: selection.marker,
+            });
+        }
+
+        let visuals = ctx.style().visuals.clone();
+        return MapImage {
+            region,
+            width: options.width,
+            background: visuals.extreme_bg_color,
+            text_color: visuals.text_color(),
+            layers,
+            territory: self
+                .ui_data
+                .territory
+                .as_ref()
+                .map(|territory| Arc::clone(&territory.image)),
+            territory_legend: self
+                .ui_data
+                .territory
+                .as_ref()
+                .map(|territory| territory.legend.clone())
+                .unwrap_or_default(),
+            legend: self
+                .ui_data
+                .show_legend
+                .then_some(self.ui_data.legend_position),
+        };
+    }
+
+    /// the colours of everything that is drawn on the map, with the number of towns in each group
+    fn ui_legend(&mut self, ctx: &egui::Context, map_rect: egui::Rect) {
+        let mut entries = Vec::new();
+        if self.ui_data.settings_all.enabled {
+            entries.push((
+                t!("map.legend.all_towns"),
+                self.ui_data.settings_all.color,
+                self.ui_data.all_towns.len(),
+            ));
+        }
+        if self.ui_data.settings_ghosts.enabled {
+            entries.push((
+                t!("map.legend.ghost_towns"),
+                self.ui_data.settings_ghosts.color,
+                self.ui_data.ghost_towns.len(),
+            ));
+        }
+        for selection in &self.ui_data.selections {
+            if !selection.is_hidden() {
+                entries.push((
+                    selection.name.clone(),
+                    selection.color,
+                    selection.towns.len(),
+                ));
+            }
+        }
+        if let Some(territory) = &self.ui_data.territory {
+            entries.extend(territory.legend.iter().cloned());
+        }
+        if let Some(changes) = &self.ui_data.changes {
+            for kind in ChangeKind::iter() {
+                let count = changes.count(kind);
+                if count > 0 && !self.ui_data.hidden_changes.contains(&kind) {
+                    entries.push((kind.to_string(), kind.color(), count));
+                }
+            }
+        }
+
+        let response = egui::Area::new("map legend")
+            .movable(true)
+            .default_pos(map_rect.left_top() + egui::vec2(10.0, 10.0))
+            .constrain_to(map_rect)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    for (name, color, count) in entries {
+                        ui.horizontal(|ui| {
+                            let size = ui.text_style_height(&egui::TextStyle::Body);
+                            let (rect, _) = ui
+                                .allocate_exact_size(egui::vec2(size, size), egui::Sense::hover());
+                            ui.painter().circle_filled(rect.center(), size / 3.0, color);
+                            ui.label(t!("map.legend.entry", name = name, count = count));
+                        });
+                    }
+                });
+            })
+            .response;
+
+        // remember where the legend is, so image exports can put it in the same place
+        self.ui_data.legend_position =
+            (response.rect.left_top() - map_rect.left_top()) / map_rect.size();
+    }
+
     #[allow(clippy::too_many_lines)] // UI Code, am I right, hahah
     pub fn ui_map(&mut self, ctx: &egui::Context) {
+        self.update_territory(ctx);
         egui::CentralPanel::default().show(ctx, |ui| {
             egui::Frame::canvas(ui.style()).show(ui, |ui| {
                 let (mut response, painter) = ui.allocate_painter(
@@ -18,17 +184,66 @@ impl View {
                 );
 
                 if self.ui_data.canvas.is_none() {
-                    self.ui_data.canvas =
-                        Some(CanvasData::new(-response.rect.left_top().to_vec2()));
+                    self.ui_data.canvas = Some(CanvasData::new(
+                        -response.rect.left_top().to_vec2(),
+                        response.rect,
+                    ));
                 }
                 // we need to have this as an option so we are reminded when we have to
                 // reset it. The .unwrap here is fine, because if it is none we make it
                 // Some() just a line above this comment.
                 let canvas_data = self.ui_data.canvas.as_mut().unwrap();
+                canvas_data.screen_rect = response.rect;
 
-                //DRAG
-                canvas_data.world_offset_px -=
-                    canvas_data.scale_screen_to_world(response.drag_delta());
+                // DRAW AN AREA WHILE A MODIFIER IS HELD, OTHERWISE DRAG THE MAP
+                let pointer_position = response
+                    .interact_pointer_pos()
+                    .map(|position| canvas_data.screen_to_world(position.to_vec2()).to_pos2());
+                if response.drag_started() {
+                    let modifiers = ctx.input(|input| input.modifiers);
+                    let opt_shape = if modifiers.shift {
+                        Some(AreaShape::Rectangle)
+                    } else if modifiers.command {
+                        Some(AreaShape::Lasso)
+                    } else {
+                        None
+                    };
+                    if let (Some(shape), Some(position)) = (opt_shape, pointer_position) {
+                        self.ui_data.area = Some(AreaOutline {
+                            shape,
+                            points: vec![position],
+                            finished: false,
+                        });
+                    }
+                }
+                if let Some(area) = self.ui_data.area.as_mut().filter(|area| !area.finished) {
+                    if let Some(position) = pointer_position {
+                        match area.shape {
+                            AreaShape::Rectangle => {
+                                area.points.truncate(1);
+                                area.points.push(position);
+                            }
+                            AreaShape::Lasso => {
+                                // a point every few pixels is detailed enough and keeps the constraint short
+                                let far_enough = area.points.last().is_none_or(|last| {
+                                    canvas_data.scale_world_to_screen(last.distance(position)) > 5.0
+                                });
+                                if far_enough {
+                                    area.points.push(position);
+                                }
+                            }
+                        }
+                    }
+                    if response.drag_released() {
+                        area.finished = true;
+                        if area.corners().is_empty() {
+                            self.ui_data.area = None;
+                        }
+                    }
+                } else {
+                    canvas_data.world_offset_px -=
+                        canvas_data.scale_screen_to_world(response.drag_delta());
+                }
 
                 // ZOOM
                 // as per https://www.youtube.com/watch?v=ZQ8qtAizis4
@@ -63,18 +278,24 @@ impl View {
 
                 // filter everything that is not visible
                 let filter = ViewPortFilter::new(canvas_data, response.rect);
-                let visible_towns_all: Vec<&Town> = self
-                    .ui_data
-                    .all_towns
-                    .iter()
-                    .filter(|town| filter.town_in_viewport(town))
+                let all_towns = &self.ui_data.all_towns;
+                let town_index = &self.ui_data.town_index;
+                let visible_towns_all: Vec<&Town> = town_index
+                    .in_rect(filter.world_rect())
+                    .into_iter()
+                    .filter_map(|index| all_towns.get(index))
                     .collect();
-                let visible_ghost_towns: Vec<&Town> = self
-                    .ui_data
-                    .ghost_towns
+                let visible_ghost_towns: Vec<&Town> = visible_towns_all
                     .iter()
-                    .filter(|town| filter.town_in_viewport(town))
+                    .copied()
+                    .filter(|town| town.player_id.is_none())
                     .collect();
+                // the closest town to `position` (in world coordinates) within `max_distance`
+                let closest_town = |position: egui::Pos2, max_distance: f32| {
+                    town_index
+                        .nearest(position, max_distance)
+                        .and_then(|(index, _)| all_towns.get(index))
+                };
 
                 // DRAW GRID
                 for i in (0u16..=10).map(|i| f32::from(i) * 100.0) {
@@ -89,6 +310,29 @@ impl View {
                     painter
                         .line_segment([one, two], egui::Stroke::new(2.0, egui::Color32::DARK_GRAY));
                 }
+                // OCEAN NUMBERS, once the oceans are large enough on screen for a label
+                if canvas_data.scale_world_to_screen(100.0) > 40.0 {
+                    for ocean_x in 0u16..10 {
+                        for ocean_y in 0u16..10 {
+                            let corner = egui::vec2(f32::from(ocean_x), f32::from(ocean_y)) * 100.0;
+                            if !(filter.x_in_viewport(corner.x + 50.0)
+                                || filter.x_in_viewport(corner.x))
+                                || !(filter.y_in_viewport(corner.y + 50.0)
+                                    || filter.y_in_viewport(corner.y))
+                            {
+                                continue;
+                            }
+                            painter.text(
+                                canvas_data.world_to_screen(corner).to_pos2()
+                                    + egui::vec2(4.0, 4.0),
+                                egui::Align2::LEFT_TOP,
+                                ocean_number(ocean_x * 100, ocean_y * 100).to_string(),
+                                egui::FontId::proportional(16.0),
+                                egui::Color32::DARK_GRAY,
+                            );
+                        }
+                    }
+                }
                 if canvas_data.zoom > 5.0 {
                     for i in (0u16..=100)
                         .map(|i| f32::from(i) * 10.0)
@@ -115,16 +359,34 @@ impl View {
                     }
                 }
 
+                // TERRITORIES, behind all towns
+                if let Some(territory) = &self.ui_data.territory {
+                    let world = egui::Rect::from_min_max(
+                        canvas_data.world_to_screen(egui::vec2(0.0, 0.0)).to_pos2(),
+                        canvas_data
+                            .world_to_screen(egui::vec2(1000.0, 1000.0))
+                            .to_pos2(),
+                    );
+                    painter.image(
+                        territory.texture.id(),
+                        world,
+                        egui::Rect::from_min_max(egui::pos2(0.0, 0.0), egui::pos2(1.0, 1.0)),
+                        egui::Color32::WHITE,
+                    );
+                }
+
                 // DRAW ALL TOWNS
                 // towns have a diameter of .25 units, approximately
                 if self.ui_data.settings_all.enabled {
                     for town in &visible_towns_all {
-                        painter.circle_filled(
+                        draw_town(
+                            &painter,
                             canvas_data
                                 .world_to_screen(egui::vec2(town.x, town.y))
                                 .to_pos2(),
                             1.0 + canvas_data.scale_world_to_screen(0.15),
                             self.ui_data.settings_all.color,
+                            town.approximate,
                         );
                     }
                 }
@@ -132,12 +394,14 @@ impl View {
                 // DRAW GHOST TOWNS
                 if self.ui_data.settings_ghosts.enabled {
                     for town in &visible_ghost_towns {
-                        painter.circle_filled(
+                        draw_town(
+                            &painter,
                             canvas_data
                                 .world_to_screen(egui::vec2(town.x, town.y))
                                 .to_pos2(),
                             2.0 + canvas_data.scale_world_to_screen(0.15),
                             self.ui_data.settings_ghosts.color,
+                            town.approximate,
                         );
                     }
                 }
@@ -149,21 +413,97 @@ impl View {
                         continue;
                     }
 
+                    let max_value = selection.marker.max_value(&selection.towns);
                     for town in selection
                         .towns
                         .iter()
                         .filter(|t| filter.town_in_viewport(t))
                     {
-                        painter.circle_filled(
+                        let (radius, color) = selection.marker.apply(
+                            town,
+                            max_value,
+                            1.0 + canvas_data.scale_world_to_screen(0.15),
+                            selection.color,
+                        );
+                        draw_town(
+                            &painter,
                             canvas_data
                                 .world_to_screen(egui::vec2(town.x, town.y))
                                 .to_pos2(),
-                            1.0 + canvas_data.scale_world_to_screen(0.15),
-                            selection.color,
+                            radius,
+                            color,
+                            town.approximate,
                         );
                     }
                 }
 
+                // MARK WHAT CHANGED SINCE THE COMPARED SNAPSHOT, as rings around the towns
+                if let Some(changes) = &self.ui_data.changes {
+                    for record in changes
+                        .records
+                        .iter()
+                        .filter(|record| !self.ui_data.hidden_changes.contains(&record.kind))
+                    {
+                        let stroke = egui::Stroke::new(1.5, record.kind.color());
+                        for town in record.towns.iter().filter(|t| filter.town_in_viewport(t)) {
+                            painter.circle_stroke(
+                                canvas_data
+                                    .world_to_screen(egui::vec2(town.x, town.y))
+                                    .to_pos2(),
+                                3.0 + canvas_data.scale_world_to_screen(0.25),
+                                stroke,
+                            );
+                        }
+                    }
+                }
+
+                // MARK THE CLICKED TOWN
+                if let Some(details) = &self.ui_data.selected_town {
+                    let town = &details.town;
+                    if filter.town_in_viewport(town) {
+                        painter.circle_stroke(
+                            canvas_data
+                                .world_to_screen(egui::vec2(town.x, town.y))
+                                .to_pos2(),
+                            4.0 + canvas_data.scale_world_to_screen(0.3),
+                            egui::Stroke::new(2.0, ui.visuals().strong_text_color()),
+                        );
+                    }
+                }
+
+                // DRAW THE AREA
+                if let Some(area) = &self.ui_data.area {
+                    let stroke = egui::Stroke::new(2.0, ui.visuals().strong_text_color());
+                    let to_screen = |corner: &egui::Pos2| {
+                        canvas_data.world_to_screen(corner.to_vec2()).to_pos2()
+                    };
+                    if area.shape == AreaShape::Lasso && !area.finished {
+                        painter.add(Shape::line(
+                            area.points.iter().map(to_screen).collect(),
+                            stroke,
+                        ));
+                    } else {
+                        painter.add(Shape::closed_line(
+                            area.corners().iter().map(to_screen).collect(),
+                            stroke,
+                        ));
+                    }
+                }
+
+                // CLICK TO SHOW THE DETAILS OF A TOWN
+                if response.clicked() {
+                    if let Some(mouse_position) = response.interact_pointer_pos() {
+                        let position = canvas_data
+                            .screen_to_world(mouse_position.to_vec2())
+                            .to_pos2();
+                        // when zoomed out towns are tiny, so allow missing them by a few pixels
+                        let max_distance = f32::max(1.5, canvas_data.scale_screen_to_world(8.0));
+                        if let Some(town) = closest_town(position, max_distance) {
+                            self.ui_data.selected_town = self.presenter.get_town_details(town.id);
+                        }
+                    }
+                }
+
                 // POPUP WITH TOWN INFORMATION
                 if canvas_data.zoom > 10.0 {
                     let optional_mouse_position = response.hover_pos();
@@ -181,23 +521,9 @@ impl View {
                         };
                         ui.label(format!("{position:?}"));
 
-                        if visible_towns_all.is_empty() {
+                        let Some(closest_town) = closest_town(position, 1.5) else {
                             return;
-                        }
-                        let mut closest_town = visible_towns_all[0];
-                        let mut closest_distance =
-                            position.distance(egui::pos2(closest_town.x, closest_town.y));
-                        for town in &visible_towns_all {
-                            let distance = position.distance(egui::pos2(town.x, town.y));
-                            if distance < closest_distance {
-                                closest_distance = distance;
-                                closest_town = town;
-                            }
-                        }
-
-                        if closest_distance >= 1.5 {
-                            return;
-                        }
+                        };
                         ui.label(t!(
                             "map.hover",
                             name = closest_town.name,
@@ -205,9 +531,36 @@ impl View {
                             player = closest_town.player_name.as_deref().unwrap_or(""),
                             alliance = closest_town.alliance_name.as_deref().unwrap_or(""),
                         ));
+
+                        // TRAVEL TIME FROM THE CHOSEN TOWN
+                        let opt_origin = self.ui_data.travel_origin.and_then(|origin_id| {
+                            self.ui_data
+                                .all_towns
+                                .iter()
+                                .find(|town| town.id == origin_id)
+                        });
+                        if let Some(origin) = opt_origin {
+                            let distance = egui::pos2(origin.x, origin.y)
+                                .distance(egui::pos2(closest_town.x, closest_town.y));
+                            if let Some(seconds) = self.ui_data.travel.travel_seconds(distance) {
+                                ui.label(t!(
+                                    "map.travel_time",
+                                    origin = origin.name,
+                                    time = travel::format_duration(seconds),
+                                    distance = format!("{distance:.1}")
+                                ));
+                            }
+                        }
                     });
                 }
 
+                // LEGEND
+                if self.ui_data.show_legend {
+                    self.ui_legend(ctx, response.rect);
+                }
+                #[cfg(not(target_arch = "wasm32"))]
+                self.ui_playback_overlay(ctx, response.rect);
+
                 response
             })
         });
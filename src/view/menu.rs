@@ -1,16 +1,28 @@
+#[cfg(not(target_arch = "wasm32"))]
+use super::data::ImageRegion;
 use super::{
+    data::TerritoryMode,
     preferences::{CacheSize, DarkModePref, Language, Preferences, Telemetry},
     Progress, State, View,
 };
 use crate::emptyselection::EmptyTownSelection;
 #[cfg(not(target_arch = "wasm32"))]
+use crate::export::ExportFormat;
+use crate::export::{self, BBCodeGrouping, BBCodeSorting};
+use crate::model::source::{DataSourceConfig, GREPOLIS_URL};
+#[cfg(not(target_arch = "wasm32"))]
 use crate::storage;
 #[cfg(not(target_arch = "wasm32"))]
+use crate::town::Town;
+use crate::travel;
+#[cfg(not(target_arch = "wasm32"))]
 use arboard::Clipboard;
 #[cfg(not(target_arch = "wasm32"))]
 use native_dialog::FileDialog;
 use rust_i18n::t;
 use std::collections::BTreeMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::sync::Arc;
 use strum::IntoEnumIterator;
 
 impl View {
@@ -112,10 +124,72 @@ impl View {
                         ui.close_menu();
                     }
 
+                    ui.separator();
+
+                    if ui.checkbox(&mut self.ui_data.preferences.exclude_approximate_towns, t!("menu.preferences.exclude_approximate_towns")).changed() {
+                        self.presenter.set_exclude_approximate(self.ui_data.preferences.exclude_approximate_towns);
+                        self.refresh_all_selections();
+                    }
+                    ui.separator();
+
+                    ui.menu_button(t!("menu.preferences.data_source.title"), |ui| {
+                        let mut new_source = None;
+                        let current = &self.ui_data.download_source;
+                        if ui.selectable_label(matches!(current, DataSourceConfig::Txt(_)), t!("menu.preferences.data_source.txt")).clicked() {
+                            new_source = Some(DataSourceConfig::Txt(GREPOLIS_URL.to_string()));
+                        }
+                        if ui.selectable_label(matches!(current, DataSourceConfig::Reflector), t!("menu.preferences.data_source.reflector")).clicked() {
+                            new_source = Some(DataSourceConfig::Reflector);
+                        }
+                        #[cfg(not(target_arch="wasm32"))]
+                        if ui.selectable_label(matches!(current, DataSourceConfig::LocalDirectory(_)), t!("menu.preferences.data_source.local_directory")).clicked() {
+                            match FileDialog::new().show_open_single_dir() {
+                                Ok(Some(path)) => {
+                                    new_source = Some(DataSourceConfig::LocalDirectory(path));
+                                }
+                                Ok(None) => { /* ignore, the user knowingly clicked cancel*/ }
+                                Err(err) => {
+                                    eprintln!("Failed to open a directory chooser: {err:?}");
+                                }
+                            }
+                        }
+
+                        // mirrors have the same layout as the grepolis servers, only the base url differs
+                        let mut base_url_changed = false;
+                        match &mut self.ui_data.download_source {
+                            DataSourceConfig::Txt(base_url) | DataSourceConfig::JsonGz(base_url) => {
+                                ui.separator();
+                                ui.label(t!("menu.preferences.data_source.base_url"));
+                                base_url_changed = ui.text_edit_singleline(base_url).changed();
+                            }
+                            DataSourceConfig::Reflector => {}
+                            #[cfg(not(target_arch="wasm32"))]
+                            DataSourceConfig::LocalDirectory(path) => {
+                                ui.separator();
+                                ui.label(format!("{}", path.display()));
+                            }
+                        }
+
+                        if let Some(source) = new_source {
+                            self.ui_data.download_source = source;
+                            self.presenter.set_data_source(self.ui_data.download_source.clone());
+                        } else if base_url_changed {
+                            self.presenter.set_data_source(self.ui_data.download_source.clone());
+                        }
+                    });
+
                     #[cfg(not(target_arch="wasm32"))]
                     {
                         ui.separator();
 
+                        if ui.button(t!("menu.auto_delete.button")).clicked() {
+                            self.ui_data.auto_delete_choice = Some(self.ui_data.preferences.auto_delete);
+                            self.ui_data.saved_db = storage::get_list_of_saved_dbs();
+                            ui.close_menu();
+                        }
+
+                        ui.separator();
+
                         if ui.button(t!("menu.preferences.telemetry_all")).clicked() {
                             self.ui_data.preferences.telemetry = Telemetry::All;
                             ui.close_menu();
@@ -146,11 +220,110 @@ impl View {
                         self.ui_data.preferences = Preferences::default();
                         self.ui_data
                             .apply_darkmode(ctx, self.ui_data.preferences.darkmode);
+                        self.presenter.set_exclude_approximate(self.ui_data.preferences.exclude_approximate_towns);
+                        #[cfg(not(target_arch="wasm32"))]
+                        self.presenter.set_auto_delete(self.ui_data.preferences.effective_auto_delete());
+                        self.refresh_all_selections();
                         Self::reset_saved_preferences(frame);
                         ui.close_menu();
                     }
                 });
 
+                //////////////////////////////////////////////////////////////////////////////////
+                ui.menu_button(t!("menu.travel.title"), |ui| {
+                    ui.label(t!("menu.travel.origin"));
+                    let mut origin_text = self.ui_data.travel_origin.map(|id| id.to_string()).unwrap_or_default();
+                    if ui.text_edit_singleline(&mut origin_text).changed() {
+                        self.ui_data.travel_origin = origin_text.trim().parse().ok();
+                    }
+                    ui.separator();
+
+                    let mut changed = false;
+                    let travel = &mut self.ui_data.travel;
+                    let unit_text = travel.selected_unit().map_or_else(String::new, |unit| unit.name.clone());
+                    ui.menu_button(t!("menu.travel.unit", unit = unit_text), |ui| {
+                        for unit in &travel.units {
+                            let text = t!("menu.travel.unit_entry", name = unit.name, speed = unit.speed);
+                            if ui.selectable_label(travel.unit == unit.id, text).clicked() {
+                                travel.unit.clone_from(&unit.id);
+                                changed = true;
+                            }
+                        }
+                        ui.separator();
+                        #[cfg(not(target_arch="wasm32"))]
+                        if ui.button(t!("menu.travel.load_units")).clicked() {
+                            match FileDialog::new().add_filter("JSON", &["json"]).show_open_single_file() {
+                                Ok(Some(path)) => {
+                                    let result = std::fs::read_to_string(&path)
+                                        .map_err(anyhow::Error::from)
+                                        .and_then(|text| travel::parse_units_json(&text));
+                                    match result {
+                                        Ok(units) if !units.is_empty() => {
+                                            travel.units = units;
+                                            changed = true;
+                                        }
+                                        Ok(_) => {
+                                            eprintln!("No units with a speed in {}", path.display());
+                                        }
+                                        Err(err) => {
+                                            eprintln!("Failed to read units from {}: {err:?}", path.display());
+                                        }
+                                    }
+                                }
+                                Ok(None) => { /* ignore, the user knowingly clicked cancel*/ }
+                                Err(err) => {
+                                    eprintln!("Failed to open a file chooser: {err:?}");
+                                }
+                            }
+                        }
+                        if ui.button(t!("menu.travel.default_units")).clicked() {
+                            travel.units = travel::default_units();
+                            changed = true;
+                        }
+                    });
+                    ui.horizontal(|ui| {
+                        ui.label(t!("menu.travel.world_speed"));
+                        changed |= ui.add(egui::DragValue::new(&mut travel.world_speed).speed(0.1).clamp_range(0.1..=10.0)).changed();
+                    });
+                    changed |= ui.checkbox(&mut travel.meteorology, t!("menu.travel.meteorology")).changed();
+                    changed |= ui.checkbox(&mut travel.cartography, t!("menu.travel.cartography")).changed();
+                    changed |= ui.checkbox(&mut travel.lighthouse, t!("menu.travel.lighthouse")).changed();
+                    ui.horizontal(|ui| {
+                        ui.label(t!("menu.travel.bonus"));
+                        changed |= ui.add(egui::DragValue::new(&mut travel.bonus_percent).suffix(" %").clamp_range(0.0..=500.0)).changed();
+                    });
+
+                    if changed {
+                        self.presenter.set_travel_settings(self.ui_data.travel.clone());
+                        self.refresh_all_selections();
+                    }
+                });
+
+                //////////////////////////////////////////////////////////////////////////////////
+                let report = &self.ui_data.parse_report;
+                let problem_count = report.bad_lines.len() + report.fallbacks.len();
+                if ui.button(t!("menu.diagnostics.button", count = problem_count)).clicked() {
+                    self.ui_data.show_diagnostics = !self.ui_data.show_diagnostics;
+                }
+                ui.toggle_value(
+                    &mut self.ui_data.show_inactive_players,
+                    t!("menu.inactive.button"),
+                );
+                ui.toggle_value(&mut self.ui_data.show_legend, t!("menu.legend"));
+                ui.menu_button(t!("menu.territory.title"), |ui| {
+                    for mode in TerritoryMode::iter() {
+                        let text = match mode {
+                            TerritoryMode::Off => t!("menu.territory.off"),
+                            TerritoryMode::Selections => t!("menu.territory.selections"),
+                            TerritoryMode::Alliances => t!("menu.territory.alliances"),
+                        };
+                        if ui.selectable_label(self.ui_data.territory_mode == mode, text).clicked() {
+                            self.ui_data.territory_mode = mode;
+                            ui.close_menu();
+                        }
+                    }
+                });
+
                 //////////////////////////////////////////////////////////////////////////////////
                 #[cfg(not(target_arch="wasm32"))]
                 ui.menu_button(t!("menu.import.title"), |ui| {
@@ -254,7 +427,188 @@ impl View {
                         }
                     }
                 });
+
+                //////////////////////////////////////////////////////////////////////////////////
+                #[cfg(not(target_arch="wasm32"))]
+                ui.menu_button(t!("menu.export_towns.title"), |ui| {
+                    let all_selections: Vec<(String, Arc<Vec<Town>>)> = self
+                        .ui_data
+                        .selections
+                        .iter()
+                        .map(|selection| (selection.name.clone(), Arc::clone(&selection.towns)))
+                        .collect();
+                    ui.menu_button(t!("menu.export_towns.all_selections"), |ui| {
+                        Self::ui_export_towns(ui, &all_selections);
+                    });
+                    ui.separator();
+                    for selection in &all_selections {
+                        ui.menu_button(&selection.0, |ui| {
+                            Self::ui_export_towns(ui, std::slice::from_ref(selection));
+                        });
+                    }
+                });
+
+                //////////////////////////////////////////////////////////////////////////////////
+                #[cfg(not(target_arch="wasm32"))]
+                ui.menu_button(t!("menu.export_image.title"), |ui| {
+                    let options = &mut self.ui_data.image_export;
+                    for region in ImageRegion::iter() {
+                        let text = match region {
+                            ImageRegion::World => t!("menu.export_image.world"),
+                            ImageRegion::CurrentView => t!("menu.export_image.current_view"),
+                            ImageRegion::Coordinates => t!("menu.export_image.coordinates"),
+                        };
+                        if ui.selectable_label(options.region == region, text).clicked() {
+                            options.region = region;
+                        }
+                    }
+                    if options.region == ImageRegion::Coordinates {
+                        let range = &mut options.coordinates;
+                        egui::Grid::new("image export coordinates").show(ui, |ui| {
+                            ui.label("x");
+                            ui.add(egui::DragValue::new(&mut range.min_x).clamp_range(0..=999));
+                            ui.label("–");
+                            ui.add(egui::DragValue::new(&mut range.max_x).clamp_range(range.min_x + 1..=1000));
+                            ui.end_row();
+                            ui.label("y");
+                            ui.add(egui::DragValue::new(&mut range.min_y).clamp_range(0..=999));
+                            ui.label("–");
+                            ui.add(egui::DragValue::new(&mut range.max_y).clamp_range(range.min_y + 1..=1000));
+                            ui.end_row();
+                        });
+                        // the lower bound may have been moved past the upper one
+                        range.max_x = range.max_x.max(range.min_x + 1);
+                        range.max_y = range.max_y.max(range.min_y + 1);
+                    }
+                    ui.separator();
+                    ui.label(t!("menu.export_image.width"));
+                    ui.add(egui::DragValue::new(&mut options.width).clamp_range(100..=10000).suffix(" px"));
+                    ui.separator();
+
+                    for (extension, button_text) in [("svg", t!("menu.export_image.svg")), ("png", t!("menu.export_image.png"))] {
+                        if ui.button(button_text).clicked() {
+                            let file_res = FileDialog::new()
+                                .add_filter(&extension.to_uppercase(), &[extension])
+                                .show_save_single_file();
+                            match file_res {
+                                Ok(Some(file_path)) => self.start_image_export(ctx, file_path, extension),
+                                Ok(None) => { /* ignore, the user knowingly clicked cancel*/ }
+                                Err(err) => {
+                                    eprintln!("Failed to open a file chooser: {err:?}");
+                                }
+                            }
+                            ui.close_menu();
+                        }
+                    }
+                });
+
+                //////////////////////////////////////////////////////////////////////////////////
+                ui.menu_button(t!("menu.bbcode.title"), |ui| {
+                    let options = &mut self.ui_data.bbcode_options;
+                    ui.label(t!("menu.bbcode.group_by"));
+                    for grouping in BBCodeGrouping::iter() {
+                        let text = match grouping {
+                            BBCodeGrouping::Player => t!("menu.bbcode.group_by_player"),
+                            BBCodeGrouping::Alliance => t!("menu.bbcode.group_by_alliance"),
+                        };
+                        if ui.selectable_label(options.group_by == grouping, text).clicked() {
+                            options.group_by = grouping;
+                        }
+                    }
+                    ui.separator();
+
+                    // distances are measured from the same town as the travel times
+                    ui.label(t!("menu.bbcode.distance_from"));
+                    let mut origin_text = self.ui_data.travel_origin.map(|id| id.to_string()).unwrap_or_default();
+                    if ui.text_edit_singleline(&mut origin_text).changed() {
+                        self.ui_data.travel_origin = origin_text.trim().parse().ok();
+                    }
+                    let reference = self.ui_data.travel_origin.and_then(|origin_id| {
+                        self.ui_data.all_towns.iter().find(|town| town.id == origin_id)
+                    });
+
+                    ui.label(t!("menu.bbcode.sort_by"));
+                    let options = &mut self.ui_data.bbcode_options;
+                    for sorting in BBCodeSorting::iter() {
+                        let text = match (sorting, reference) {
+                            (BBCodeSorting::Points, _) => t!("menu.bbcode.sort_by_points"),
+                            (BBCodeSorting::Distance, Some(town)) => t!("menu.bbcode.sort_by_distance", town = town.name),
+                            (BBCodeSorting::Distance, None) => t!("menu.bbcode.sort_by_distance_no_town"),
+                        };
+                        let enabled = sorting != BBCodeSorting::Distance || reference.is_some();
+                        let response = ui
+                            .add_enabled(enabled, egui::SelectableLabel::new(options.sort_by == sorting, text))
+                            .on_disabled_hover_text(t!("menu.bbcode.no_distance_town"));
+                        if response.clicked() {
+                            options.sort_by = sorting;
+                        }
+                    }
+                    ui.separator();
+
+                    for selection in &self.ui_data.selections {
+                        if ui.button(t!("menu.bbcode.copy", selection = selection.name)).clicked() {
+                            let text = export::towns_to_bbcode(&selection.name, &selection.towns, self.ui_data.bbcode_options, reference, &self.ui_data.travel);
+                            ui.output_mut(|output| output.copied_text = text);
+                            ui.close_menu();
+                        }
+                    }
+                });
             });
         });
     }
+
+    /// buttons to export the towns of the given selections in every format, either to the clipboard or to a file
+    #[cfg(not(target_arch = "wasm32"))]
+    fn ui_export_towns(ui: &mut egui::Ui, selections: &[(String, Arc<Vec<Town>>)]) {
+        for format in ExportFormat::iter() {
+            let format_name = format.extension().to_uppercase();
+            if ui
+                .button(t!("menu.export_towns.to_clipboard", format = format_name))
+                .clicked()
+            {
+                match (export::export_towns(format, selections), Clipboard::new()) {
+                    (Ok(text), Ok(mut clipboard)) => {
+                        if let Err(err) = clipboard.set_text(text) {
+                            eprintln!("Failed to write {format_name} to clipboard: {err}");
+                        }
+                    }
+                    (Err(err), _) => {
+                        eprintln!("Failed to convert the towns into {format_name}: {err}");
+                    }
+                    (_, Err(err)) => {
+                        eprintln!("Did not get the clipboard: {err}");
+                    }
+                }
+                ui.close_menu();
+            }
+            if ui
+                .button(t!("menu.export_towns.to_file", format = format_name))
+                .clicked()
+            {
+                let file_res = FileDialog::new()
+                    .add_filter(&format_name, &[format.extension()])
+                    .show_save_single_file();
+                match file_res {
+                    Ok(Some(file_path)) => match export::export_towns(format, selections) {
+                        Ok(text) => {
+                            if let Err(err) = std::fs::write(&file_path, text) {
+                                eprintln!(
+                                    "Failed to write {format_name} to file ({}) Error: {err:?}",
+                                    file_path.display()
+                                );
+                            }
+                        }
+                        Err(err) => {
+                            eprintln!("Failed to convert the towns into {format_name}: {err}");
+                        }
+                    },
+                    Ok(None) => { /* ignore, the user knowingly clicked cancel*/ }
+                    Err(err) => {
+                        eprintln!("Failed to open a file chooser: {err:?}");
+                    }
+                }
+                ui.close_menu();
+            }
+        }
+    }
 }
@@ -1,24 +1,44 @@
+mod area;
+#[cfg(not(target_arch = "wasm32"))]
+mod autodelete;
+#[cfg(not(target_arch = "wasm32"))]
+mod changes;
 mod data;
+mod diagnostics;
 pub(crate) mod dropdownbox;
+#[cfg(not(target_arch = "wasm32"))]
+mod imageexport;
+mod inactive;
 mod map;
 mod menu;
+#[cfg(not(target_arch = "wasm32"))]
+mod playback;
 pub(crate) mod preferences;
 mod selectable_label;
 mod sidepanel;
+mod territory;
+mod towndetails;
 
+use crate::constraint::Comparator;
 use crate::emptyconstraint::EmptyConstraint;
 use crate::emptyselection::EmptyTownSelection;
+use crate::model::report::ParseReport;
+use crate::model::source::WorldDataFile;
 use crate::presenter::Presenter;
 use crate::presenter::PresenterReady;
-use crate::selection::TownSelection;
+use crate::selection::{AndOr, TownSelection};
 #[cfg(not(target_arch = "wasm32"))]
 use crate::storage;
 use crate::telemetry;
 use crate::view::data::Data;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::view::data::PlaybackState;
 #[cfg(target_arch = "wasm32")]
 use crate::wasm_utils;
 use eframe::Storage;
 use egui::{FontData, ProgressBar, RichText, Ui};
+#[cfg(not(target_arch = "wasm32"))]
+use preferences::AutoDeletePref;
 use preferences::Telemetry;
 use std::collections::HashSet;
 use std::sync::Arc;
@@ -27,6 +47,9 @@ use std::time::Duration;
 #[cfg(target_arch = "wasm32")]
 use log::info;
 
+/// the font for all the scripts the default egui fonts do not cover
+pub(crate) const UNICODE_FONT: &[u8] = include_bytes!("../../NotoSansJP-Regular.ttf");
+
 #[derive(Clone, Copy)]
 pub enum Change {
     Add,
@@ -50,6 +73,7 @@ pub enum Refresh {
 pub enum Progress {
     None,
     BackendCrashed(String),
+    DownloadFailed(Vec<(WorldDataFile, String)>),
     Fetching,
     LoadingFile,
 }
@@ -79,7 +103,7 @@ impl View {
         let mut fonts = egui::FontDefinitions::default();
         fonts.font_data.insert(
             String::from("Custom Font"),
-            FontData::from_static(include_bytes!("../../NotoSansJP-Regular.ttf")),
+            FontData::from_static(UNICODE_FONT),
         );
         fonts
             .families
@@ -107,6 +131,11 @@ impl View {
 
         re.presenter
             .set_max_cache_size(re.ui_data.preferences.cache_size);
+        re.presenter
+            .set_data_source(re.ui_data.download_source.clone());
+        re.presenter
+            .set_exclude_approximate(re.ui_data.preferences.exclude_approximate_towns);
+        re.presenter.set_travel_settings(re.ui_data.travel.clone());
 
         // start checking the latest version in the background. Will pop up a notification window if there is a newer version available
         // noop on wasm
@@ -117,10 +146,16 @@ impl View {
             Telemetry::Nothing => {}
         }
 
-        // TODO
-        // self.channel_presenter_tx
-        //     .send(MessageToModel::AutoDeleteTime(data.preferences.auto_delete_time))
-        //     .expect("Failed to send message to backend: Discover Saved Databases");
+        // delete the saved files that are older than the user wants to keep them. In a separate
+        // thread, listing and deleting hundreds of files must not delay the first frame.
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let auto_delete = re.ui_data.preferences.effective_auto_delete();
+            re.presenter.set_auto_delete(auto_delete);
+            if auto_delete != AutoDeletePref::Eternity {
+                let _handle = std::thread::spawn(move || storage::apply_auto_delete(auto_delete));
+            }
+        }
 
         // apply preferences before returning
         re.ui_data
@@ -241,11 +276,17 @@ impl View {
         self.ui_data = Data {
             all_towns: Arc::new(Vec::new()),
             ghost_towns: Arc::new(Vec::new()),
+            parse_report: Arc::new(ParseReport::default()),
             ..self.ui_data.clone()
         };
         #[cfg(not(target_arch = "wasm32"))]
         {
             self.ui_data.history_index = None;
+            self.ui_data.comparison_error = None;
+            self.ui_data.playback = PlaybackState {
+                range: self.ui_data.playback.range,
+                ..PlaybackState::default()
+            };
         }
 
         for selection in &mut self.ui_data.selections {
@@ -256,6 +297,107 @@ impl View {
         // the selections are invalidated after the backend sends "got server"
     }
 
+    /// fetch everything that is drawn from the presenter anew, after other data was loaded into it
+    fn refresh_shown_data(&mut self) {
+        self.ui_data.ghost_towns = self.presenter.get_ghost_towns();
+        self.ui_data.all_towns = self.presenter.get_all_towns();
+        self.ui_data.town_index = self.presenter.get_town_index();
+        self.ui_data.parse_report = self.presenter.get_parse_report();
+        self.ui_data.inactive_players = None;
+        // the clicked town may have changed hands or be gone in the new data
+        self.ui_data.selected_town = self
+            .ui_data
+            .selected_town
+            .as_ref()
+            .and_then(|details| self.presenter.get_town_details(details.town.id));
+
+        // ensure the towns in the selection are fetched anew after loading the data from the server.
+        // If we don't do this the selection may become stale and show towns from server ab12 on a
+        // map that is otherwise pulled from server cd34
+        self.refresh_all_selections();
+    }
+
+    /// drop the towns of all selections and fetch them anew from the presenter
+    fn refresh_all_selections(&mut self) {
+        let all_selections: Vec<EmptyTownSelection> = self
+            .ui_data
+            .selections
+            .iter()
+            .map(TownSelection::partial_clone)
+            .collect();
+        for selection in &mut self.ui_data.selections {
+            selection.towns = Arc::new(Vec::new());
+            let result =
+                selection.refresh_self(&mut self.presenter, &HashSet::new(), &all_selections);
+            if let Err(err) = result {
+                self.ui_state = State::Uninitialized(Progress::BackendCrashed(format!("{err:?}")));
+            }
+        }
+    }
+
+    /// Add the towns matching `constraint` to the selection at `index` and fetch its towns anew.
+    /// The selection keeps all towns it had before. If its constraints are joined with AND, they
+    /// are moved into a new hidden selection first, which is then OR-ed with `constraint`.
+    pub(crate) fn add_constraint_to_selection(
+        &mut self,
+        index: usize,
+        constraint: &EmptyConstraint,
+    ) {
+        let Some(selection) = self.ui_data.selections.get(index) else {
+            return;
+        };
+        if selection.constraints.iter().any(|c| c == constraint) {
+            return;
+        }
+
+        let has_constraints = selection.constraints.iter().any(|c| !c.value.is_empty());
+        if has_constraints && selection.constraint_join_mode == AndOr::And {
+            let wrapped = self.wrap_constraints_of_selection(index);
+            let selection = &mut self.ui_data.selections[index];
+            selection.constraints = vec![wrapped.fill()];
+            selection.constraint_join_mode = AndOr::Or;
+        }
+
+        // fill the empty constraint of a new selection instead of adding another one
+        let selection = &mut self.ui_data.selections[index];
+        if let Some(empty) = selection
+            .constraints
+            .iter_mut()
+            .find(|c| c.value.is_empty())
+        {
+            *empty = constraint.fill();
+        } else {
+            selection.constraints.push(constraint.fill());
+        }
+        self.refresh_all_selections();
+    }
+
+    /// Copy the constraints of the selection at `index` into a new hidden selection and return a
+    /// constraint that matches the towns of the new selection.
+    fn wrap_constraints_of_selection(&mut self, index: usize) -> EmptyConstraint {
+        let selection = &self.ui_data.selections[index];
+        // one of these names is free, there are not enough selections to take all of them
+        let name = (1..=self.ui_data.selections.len())
+            .map(|n| format!("{} {n}", selection.name))
+            .find(|name| self.ui_data.selections.iter().all(|s| &s.name != name))
+            .expect("one of the names is not taken");
+        let wrapper = TownSelection {
+            name: name.clone(),
+            constraints: selection.constraints.clone(),
+            constraint_join_mode: selection.constraint_join_mode,
+            color: egui::Color32::TRANSPARENT,
+            marker: selection.marker,
+            collapsed: true,
+            ..Default::default()
+        };
+        self.ui_data.selections.push(wrapper);
+        return EmptyConstraint {
+            comparator: Comparator::InSelection,
+            value: name,
+            ..Default::default()
+        };
+    }
+
     fn ui_server_input(&mut self, ui: &mut Ui) {
         let mut should_load_server = false;
         ui.horizontal(|ui| {
@@ -376,6 +518,9 @@ impl View {
                         self.ui_data.history_index = Some(index);
                     }
                 }
+
+                self.ui_compare_with(ui, &history_for_server);
+                self.ui_playback(ui, &history_for_server);
             }
         }
 
@@ -389,6 +534,56 @@ impl View {
         }
     }
 
+    /// choose a saved snapshot of the same server to compare the loaded data with
+    #[cfg(not(target_arch = "wasm32"))]
+    fn ui_compare_with(&mut self, ui: &mut Ui, history_for_server: &[storage::SavedDB]) {
+        let mut clicked_comparison = None;
+        ui.horizontal(|ui| {
+            ui.label(t!("sidepanel.header.compare_with"));
+            let selected_text = self.ui_data.compare_with.as_ref().map_or_else(
+                || t!("sidepanel.header.compare_nothing"),
+                ToString::to_string,
+            );
+            egui::ComboBox::from_id_source("compare_with_file")
+                .selected_text(selected_text)
+                .show_ui(ui, |ui| {
+                    let text = t!("sidepanel.header.compare_nothing");
+                    if ui
+                        .selectable_label(self.ui_data.compare_with.is_none(), text)
+                        .clicked()
+                    {
+                        clicked_comparison = Some(None);
+                    }
+                    for saved_db in history_for_server.iter().rev() {
+                        let selected = self.ui_data.compare_with.as_ref() == Some(saved_db);
+                        if ui
+                            .selectable_label(selected, saved_db.to_string())
+                            .clicked()
+                        {
+                            clicked_comparison = Some(Some(saved_db.clone()));
+                        }
+                    }
+                });
+            if self.ui_data.compare_with.is_some() && self.ui_data.changes.is_none() {
+                ui.spinner();
+            }
+        });
+        if let Some(reason) = &self.ui_data.comparison_error {
+            ui.label(
+                RichText::new(t!("sidepanel.header.compare_failed", reason = reason))
+                    .color(ui.style().visuals.warn_fg_color),
+            );
+        }
+        if let Some(comparison) = clicked_comparison {
+            if let Some(saved_db) = &comparison {
+                self.presenter.compare_with_file(saved_db.clone());
+            }
+            self.ui_data.compare_with = comparison;
+            self.ui_data.comparison_error = None;
+            self.ui_data.changes = None;
+        }
+    }
+
     fn ui_uninitialized(
         &mut self,
         ctx: &egui::Context,
@@ -396,6 +591,7 @@ impl View {
         progress: Progress,
     ) {
         self.ui_menu(ctx, frame);
+        let mut retry = false;
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.vertical(|ui| {
                 self.ui_server_input(ui);
@@ -410,6 +606,22 @@ impl View {
                             .color(ui.style().visuals.warn_fg_color),
                         );
                     }
+                    Progress::DownloadFailed(failures) => {
+                        ui.label(
+                            RichText::new(t!("sidepanel.loading.download_failed"))
+                                .color(ui.style().visuals.warn_fg_color),
+                        );
+                        for (file, reason) in failures {
+                            ui.label(t!(
+                                "sidepanel.loading.download_failed_file",
+                                file = file.txt_stem(),
+                                reason = reason
+                            ));
+                        }
+                        if ui.button(t!("sidepanel.loading.retry")).clicked() {
+                            retry = true;
+                        }
+                    }
                     Progress::Fetching => {
                         ui.add(
                             ProgressBar::new(self.presenter.loading_progress())
@@ -422,12 +634,29 @@ impl View {
                 }
             });
         });
+
+        // the files are fetched from scratch, a partial download is of no use
+        if retry {
+            self.reload_server();
+            self.presenter.load_server(self.ui_data.server_id.clone());
+            self.ui_state = State::Uninitialized(Progress::Fetching);
+        }
     }
 
     fn ui_init(&mut self, ctx: &egui::Context, frame: &mut eframe::Frame) {
         self.ui_menu(ctx, frame);
         self.ui_sidepanel(ctx);
+        self.ui_town_details(ctx);
         self.ui_map(ctx);
+        self.ui_area(ctx);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.ui_changes(ctx);
+        self.ui_diagnostics(ctx);
+        self.ui_inactive_players(ctx);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.ui_auto_delete(ctx);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.ui_image_export_progress(ctx);
     }
 }
 
@@ -448,33 +677,18 @@ impl eframe::App for View {
                 // still waiting for the API to respond. Make sure to check back in soon
                 ctx.request_repaint_after(Duration::from_millis(50));
             }
+            Ok(PresenterReady::HistoryLoaded) => {
+                // constraints that compare with older snapshots can only match now
+                self.refresh_all_selections();
+                self.ui_data.inactive_players = None;
+            }
+            Ok(PresenterReady::DownloadFailed(failures)) => {
+                self.ui_state = State::Uninitialized(Progress::DownloadFailed(failures.clone()));
+            }
             Ok(PresenterReady::NewlyReady) => {
                 // trigger all the data refreshes that are required when loading new data
                 self.ui_state = State::Show;
-                self.ui_data.ghost_towns = self.presenter.get_ghost_towns();
-                self.ui_data.all_towns = self.presenter.get_all_towns();
-
-                // ensure the towns in the selection are fetched anew after loading the data from the server.
-                // If we don't do this the selection may become stale and show towns from server ab12 on a
-                // map that is otherwise pulled from server cd34
-                let all_selections: Vec<EmptyTownSelection> = self
-                    .ui_data
-                    .selections
-                    .iter()
-                    .map(TownSelection::partial_clone)
-                    .collect();
-                for selection in &mut self.ui_data.selections {
-                    selection.towns = Arc::new(Vec::new());
-                    let result = selection.refresh_self(
-                        &mut self.presenter,
-                        &HashSet::new(),
-                        &all_selections,
-                    );
-                    if let Err(err) = result {
-                        self.ui_state =
-                            State::Uninitialized(Progress::BackendCrashed(format!("{err:?}")));
-                    }
-                }
+                self.refresh_shown_data();
 
                 // also refresh which SavedDBs are present. If we keep the *api response saving* in a
                 // separate thread this refresh will still miss the latest response (because it will
@@ -484,6 +698,21 @@ impl eframe::App for View {
                 {
                     self.ui_data.saved_db = storage::get_list_of_saved_dbs();
                 }
+
+                // the changes refer to the previously loaded data, compare anew. Snapshots of
+                // another server can not be compared with.
+                #[cfg(not(target_arch = "wasm32"))]
+                {
+                    self.ui_data.changes = None;
+                    self.ui_data.compare_with = self
+                        .ui_data
+                        .compare_with
+                        .take()
+                        .filter(|saved_db| saved_db.server_str == self.ui_data.server_id);
+                    if let Some(saved_db) = self.ui_data.compare_with.clone() {
+                        self.presenter.compare_with_file(saved_db);
+                    }
+                }
             }
             Err(err) => {
                 // crashed when trying to convert API Response into our backend data structure
@@ -492,6 +721,24 @@ impl eframe::App for View {
             }
         }
 
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.ui_data.compare_with.is_some() && self.ui_data.changes.is_none() {
+            match self.presenter.poll_comparison() {
+                Some(Ok(changes)) => self.ui_data.changes = Some(changes),
+                Some(Err(err)) => {
+                    eprintln!("Failed to compare with the snapshot: {err:?}");
+                    self.ui_data.compare_with = None;
+                    self.ui_data.comparison_error = Some(format!("{err:#}"));
+                }
+                None => ctx.request_repaint_after(Duration::from_millis(50)),
+            }
+        }
+
+        #[cfg(not(target_arch = "wasm32"))]
+        if self.ui_data.playback.active {
+            self.update_playback(ctx);
+        }
+
         // the above is book keeping. Now we call the rendering code.
         let state = self.ui_state.clone();
         match state {
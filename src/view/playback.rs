@@ -0,0 +1,233 @@
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+use crate::presenter::MAX_PLAYBACK_SNAPSHOTS;
+use crate::storage::{self, SavedDB};
+
+use super::data::PlaybackState;
+use super::{State, View};
+
+impl View {
+    /// preload a range of the saved snapshots of the loaded server and play them back on the map
+    pub(crate) fn ui_playback(&mut self, ui: &mut egui::Ui, history_for_server: &[SavedDB]) {
+        // the snapshots are shown in place of the loaded data, so there has to be some
+        if !matches!(self.ui_state, State::Show) {
+            return;
+        }
+        ui.collapsing(t!("sidepanel.playback.title"), |ui| {
+            if self.ui_data.playback.active {
+                self.ui_playback_controls(ui);
+            } else {
+                self.ui_playback_range(ui, history_for_server);
+            }
+        });
+    }
+
+    fn ui_playback_range(&mut self, ui: &mut egui::Ui, history_for_server: &[SavedDB]) {
+        let Some(max_index) = history_for_server.len().checked_sub(1) else {
+            return;
+        };
+        let (mut from, mut to) = self.ui_data.playback.range.unwrap_or((0, max_index));
+        (from, to) = (from.min(max_index), to.min(max_index));
+
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        let format_snapshot = |value: f64, _range: RangeInclusive<usize>| {
+            history_for_server
+                .get(value as usize)
+                .map_or_else(String::new, ToString::to_string)
+        };
+        // keep the range the right way round and not too long, whichever end is moved
+        let longest = MAX_PLAYBACK_SNAPSHOTS - 1;
+        ui.horizontal(|ui| {
+            ui.label(t!("sidepanel.playback.from"));
+            ui.add(egui::Slider::new(&mut from, 0..=max_index).custom_formatter(format_snapshot));
+        });
+        to = to.clamp(from, from + longest);
+        ui.horizontal(|ui| {
+            ui.label(t!("sidepanel.playback.to"));
+            ui.add(egui::Slider::new(&mut to, 0..=max_index).custom_formatter(format_snapshot));
+        });
+        from = from.clamp(to.saturating_sub(longest), to);
+        self.ui_data.playback.range = Some((from, to));
+
+        let text = t!("sidepanel.playback.preload", count = to - from + 1);
+        let button = ui.button(text).on_hover_text(t!(
+            "sidepanel.playback.max_snapshots",
+            count = MAX_PLAYBACK_SNAPSHOTS
+        ));
+        if button.clicked() {
+            self.presenter
+                .preload_playback(history_for_server[from..=to].to_vec());
+            self.ui_data.playback.active = true;
+        }
+    }
+
+    fn ui_playback_controls(&mut self, ui: &mut egui::Ui) {
+        let playback = &self.ui_data.playback;
+        if playback.remaining > 0 {
+            let total = playback.times.len() + playback.remaining;
+            #[allow(clippy::cast_precision_loss)] // there are never that many snapshots
+            let progress = playback.times.len() as f32 / total as f32;
+            ui.add(egui::ProgressBar::new(progress).text(t!(
+                "sidepanel.playback.preparing",
+                done = playback.times.len(),
+                total = total
+            )));
+        }
+
+        let mut goto = None;
+        let mut toggle_playing = false;
+        if let Some(last) = playback.times.len().checked_sub(1) {
+            let current = playback.frame;
+            ui.horizontal(|ui| {
+                if ui.button("⏮").clicked() {
+                    goto = Some(0);
+                }
+                let has_previous = current.is_some_and(|frame| frame > 0);
+                if ui
+                    .add_enabled(has_previous, egui::Button::new("⏴"))
+                    .clicked()
+                {
+                    goto = current.map(|frame| frame - 1);
+                }
+                let text = if playback.playing {
+                    t!("sidepanel.playback.pause")
+                } else {
+                    t!("sidepanel.playback.play")
+                };
+                if ui.button(text).clicked() {
+                    toggle_playing = true;
+                    // playing from the end starts over
+                    if !playback.playing && current == Some(last) {
+                        goto = Some(0);
+                    }
+                }
+                let has_next = current.is_none_or(|frame| frame < last);
+                if ui.add_enabled(has_next, egui::Button::new("⏵")).clicked() {
+                    goto = Some(current.map_or(0, |frame| frame + 1));
+                }
+                if ui.button("⏭").clicked() {
+                    goto = Some(last);
+                }
+            });
+
+            let times = &playback.times;
+            let mut index = current.unwrap_or(0);
+            let response = ui.add(egui::Slider::new(&mut index, 0..=last).custom_formatter(
+                #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+                |value, _range| {
+                    times
+                        .get(value as usize)
+                        .map_or_else(String::new, |time| storage::format_timestamp(*time))
+                },
+            ));
+            if response.changed() {
+                goto = Some(index);
+            }
+        }
+
+        ui.horizontal(|ui| {
+            ui.label(t!("sidepanel.playback.seconds_per_snapshot"));
+            ui.add(
+                egui::DragValue::new(&mut self.ui_data.playback_options.seconds_per_snapshot)
+                    .speed(0.1)
+                    .clamp_range(0.1..=60.0),
+            );
+            ui.checkbox(
+                &mut self.ui_data.playback_options.repeat,
+                t!("sidepanel.playback.repeat"),
+            );
+        });
+        let close = ui.button(t!("sidepanel.playback.close")).clicked();
+
+        if toggle_playing {
+            self.ui_data.playback.playing = !self.ui_data.playback.playing;
+        }
+        if let Some(index) = goto {
+            let now = ui.input(|input| input.time);
+            self.show_playback_frame(index, now);
+        }
+        if close {
+            self.stop_playback();
+        }
+    }
+
+    fn show_playback_frame(&mut self, index: usize, now: f64) {
+        if self.presenter.show_playback_frame(index).is_some() {
+            self.ui_data.playback.frame = Some(index);
+            self.ui_data.playback.frame_shown_at = now;
+            self.refresh_shown_data();
+        }
+    }
+
+    /// show the loaded data again, the range to preload is kept for next time
+    fn stop_playback(&mut self) {
+        self.presenter.stop_playback();
+        let was_shown = self.ui_data.playback.frame.is_some();
+        self.ui_data.playback = PlaybackState {
+            range: self.ui_data.playback.range,
+            ..PlaybackState::default()
+        };
+        if was_shown {
+            self.refresh_shown_data();
+        }
+    }
+
+    /// Prepare the preloaded snapshots and show the next one when it is time. Playing on waits for
+    /// snapshots that are still being prepared.
+    pub(crate) fn update_playback(&mut self, ctx: &egui::Context) {
+        let Some((times, remaining)) = self.presenter.poll_playback() else {
+            // other data was loaded in the meantime
+            self.stop_playback();
+            return;
+        };
+        let playback = &mut self.ui_data.playback;
+        playback.times = times;
+        playback.remaining = remaining;
+        if remaining > 0 {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+        if !playback.playing {
+            return;
+        }
+        let Some(last) = playback.times.len().checked_sub(1) else {
+            return;
+        };
+
+        let now = ctx.input(|input| input.time);
+        let seconds = f64::from(self.ui_data.playback_options.seconds_per_snapshot);
+        let next = match playback.frame {
+            None => Some(0),
+            Some(_) if now < playback.frame_shown_at + seconds => None,
+            Some(frame) if frame < last => Some(frame + 1),
+            Some(_) if remaining > 0 => None,
+            Some(_) if self.ui_data.playback_options.repeat => Some(0),
+            Some(_) => {
+                playback.playing = false;
+                return;
+            }
+        };
+        if let Some(index) = next {
+            self.show_playback_frame(index, now);
+        }
+        let wait = self.ui_data.playback.frame_shown_at + seconds - now;
+        ctx.request_repaint_after(Duration::from_secs_f64(wait.max(0.0)));
+    }
+
+    /// the date of the snapshot on the map in large letters on top of it
+    pub(crate) fn ui_playback_overlay(&self, ctx: &egui::Context, map_rect: egui::Rect) {
+        let playback = &self.ui_data.playback;
+        let Some(time) = playback.frame.and_then(|frame| playback.times.get(frame)) else {
+            return;
+        };
+        egui::Area::new("playback time")
+            .fixed_pos(map_rect.center_top() + egui::vec2(0.0, 10.0))
+            .pivot(egui::Align2::CENTER_TOP)
+            .interactable(false)
+            .show(ctx, |ui| {
+                egui::Frame::popup(ui.style()).show(ui, |ui| {
+                    ui.heading(storage::format_timestamp(*time));
+                });
+            });
+    }
+}
@@ -17,16 +17,33 @@ pub enum DarkModePref {
     Light,
 }
 
-#[derive(Clone, Copy, Serialize, Deserialize, Default, Debug)]
+/// how long saved api responses are kept, see `storage::snapshots_to_delete`
+#[derive(Clone, Copy, Serialize, Deserialize, Default, Debug, PartialEq, Eq, EnumIter)]
 pub enum AutoDeletePref {
+    /// only the newest file of each server is kept
     NoTime,
     OneDay,
-    #[default]
     OneWeek,
     OneMonth,
+    /// everything of the last week, then one file per day, after a month one file per week
+    Thinned,
+    #[default]
     Eternity,
 }
 
+impl ToString for AutoDeletePref {
+    fn to_string(&self) -> String {
+        match self {
+            AutoDeletePref::NoTime => t!("menu.auto_delete.no_time"),
+            AutoDeletePref::OneDay => t!("menu.auto_delete.one_day"),
+            AutoDeletePref::OneWeek => t!("menu.auto_delete.one_week"),
+            AutoDeletePref::OneMonth => t!("menu.auto_delete.one_month"),
+            AutoDeletePref::Thinned => t!("menu.auto_delete.thinned"),
+            AutoDeletePref::Eternity => t!("menu.auto_delete.eternity"),
+        }
+    }
+}
+
 #[derive(Clone, Copy, Serialize, Deserialize, Default, Debug)]
 pub enum CacheSize {
     None,
@@ -89,12 +106,30 @@ pub struct Preferences {
     pub darkmode: DarkModePref,
     #[serde(default)]
     pub auto_delete: AutoDeletePref,
+    /// `auto_delete` was applied in the window that previews what it deletes. Older versions
+    /// saved the setting without ever deleting anything, so it only takes effect once confirmed.
+    #[serde(default)]
+    pub auto_delete_confirmed: bool,
     #[serde(default)]
     pub cache_size: CacheSize,
     #[serde(default)]
     pub language: Language,
     #[serde(default)]
     pub telemetry: Telemetry,
+    /// towns whose island or slot is unknown do not match coordinate based constraints
+    #[serde(default)]
+    pub exclude_approximate_towns: bool,
+}
+
+impl Preferences {
+    /// the setting for deleting saved files that is actually applied, see `auto_delete_confirmed`
+    pub fn effective_auto_delete(self) -> AutoDeletePref {
+        if self.auto_delete_confirmed {
+            self.auto_delete
+        } else {
+            AutoDeletePref::Eternity
+        }
+    }
 }
 
 impl Default for Preferences {
@@ -102,9 +137,11 @@ impl Default for Preferences {
         Self {
             darkmode: DarkModePref::FollowSystem,
             auto_delete: AutoDeletePref::Eternity,
+            auto_delete_confirmed: false,
             cache_size: CacheSize::Normal,
             language: Language::EN,
             telemetry: Telemetry::All,
+            exclude_approximate_towns: false,
         }
     }
 }
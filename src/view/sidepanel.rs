@@ -22,6 +22,10 @@ impl View {
                     "sidepanel.town_stats.ghosts",
                     count = self.ui_data.ghost_towns.len()
                 ));
+                ui.label(t!(
+                    "sidepanel.town_stats.conquests",
+                    count = self.presenter.get_conquest_count()
+                ));
                 ui.separator();
 
                 ui.horizontal(|ui| {
@@ -0,0 +1,125 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use crate::territory::{self, TerritoryGroup};
+use crate::town::Town;
+
+use super::data::{Territory, TerritoryKey, TerritoryMode};
+use super::View;
+
+/// how many alliances get a territory in `TerritoryMode::Alliances`
+const ALLIANCE_COUNT: usize = 10;
+
+/// colours that are easy to tell apart, one for each alliance with a territory
+const ALLIANCE_COLORS: [egui::Color32; ALLIANCE_COUNT] = [
+    egui::Color32::from_rgb(230, 25, 75),
+    egui::Color32::from_rgb(60, 180, 75),
+    egui::Color32::from_rgb(255, 225, 25),
+    egui::Color32::from_rgb(0, 130, 200),
+    egui::Color32::from_rgb(245, 130, 48),
+    egui::Color32::from_rgb(145, 30, 180),
+    egui::Color32::from_rgb(70, 240, 240),
+    egui::Color32::from_rgb(240, 50, 230),
+    egui::Color32::from_rgb(210, 245, 60),
+    egui::Color32::from_rgb(250, 190, 212),
+];
+
+/// the towns of the alliances with the most towns, largest first
+fn largest_alliances(towns: &[Town]) -> Vec<(&str, Vec<&Town>)> {
+    let mut alliances: HashMap<&str, Vec<&Town>> = HashMap::new();
+    for town in towns {
+        if let Some(name) = &town.alliance_name {
+            alliances.entry(name).or_default().push(town);
+        }
+    }
+    let mut re: Vec<(&str, Vec<&Town>)> = alliances.into_iter().collect();
+    re.sort_by(|(a_name, a_towns), (b_name, b_towns)| {
+        b_towns.len().cmp(&a_towns.len()).then(a_name.cmp(b_name))
+    });
+    re.truncate(ALLIANCE_COUNT);
+    return re;
+}
+
+impl View {
+    /// which towns and colours the territories would currently be computed from
+    fn territory_key(&self) -> TerritoryKey {
+        let groups = match self.ui_data.territory_mode {
+            TerritoryMode::Off => Vec::new(),
+            TerritoryMode::Selections => self
+                .ui_data
+                .selections
+                .iter()
+                .filter(|selection| !selection.is_hidden())
+                .map(|selection| {
+                    (
+                        Arc::as_ptr(&selection.towns) as usize,
+                        selection.towns.len(),
+                        selection.color,
+                    )
+                })
+                .collect(),
+            TerritoryMode::Alliances => vec![(
+                Arc::as_ptr(&self.ui_data.all_towns) as usize,
+                self.ui_data.all_towns.len(),
+                egui::Color32::TRANSPARENT,
+            )],
+        };
+        return TerritoryKey {
+            mode: self.ui_data.territory_mode,
+            groups,
+        };
+    }
+
+    /// compute the territories anew if the towns or colours they are based on changed
+    pub(crate) fn update_territory(&mut self, ctx: &egui::Context) {
+        if self.ui_data.territory_mode == TerritoryMode::Off {
+            self.ui_data.territory = None;
+            return;
+        }
+        let key = self.territory_key();
+        if self
+            .ui_data
+            .territory
+            .as_ref()
+            .is_some_and(|territory| territory.key == key)
+        {
+            return;
+        }
+
+        let mut legend = Vec::new();
+        let groups: Vec<TerritoryGroup> = match self.ui_data.territory_mode {
+            TerritoryMode::Off => Vec::new(),
+            TerritoryMode::Selections => self
+                .ui_data
+                .selections
+                .iter()
+                .filter(|selection| !selection.is_hidden())
+                .map(|selection| TerritoryGroup {
+                    color: selection.color,
+                    towns: selection.towns.iter().collect(),
+                })
+                .collect(),
+            TerritoryMode::Alliances => largest_alliances(&self.ui_data.all_towns)
+                .into_iter()
+                .zip(ALLIANCE_COLORS)
+                // the largest alliance is drawn last, so it wins ties
+                .rev()
+                .map(|((name, towns), color)| {
+                    legend.push((name.to_string(), color, towns.len()));
+                    TerritoryGroup { color, towns }
+                })
+                .collect(),
+        };
+        legend.reverse();
+
+        let image = territory::territory_image(&groups);
+        let texture = ctx.load_texture("territory", image.clone(), egui::TextureOptions::LINEAR);
+        self.ui_data.territory = Some(Territory {
+            key,
+            texture,
+            #[cfg(not(target_arch = "wasm32"))]
+            image: Arc::new(image),
+            legend,
+        });
+    }
+}
@@ -0,0 +1,175 @@
+use crate::constraint::{Comparator, ConstraintType};
+use crate::emptyconstraint::EmptyConstraint;
+use crate::town::TownDetails;
+
+use super::View;
+
+impl View {
+    /// panel with everything we know about the town that was last clicked on the map
+    #[allow(clippy::too_many_lines)] // one row per property
+    pub(crate) fn ui_town_details(&mut self, ctx: &egui::Context) {
+        let Some(details) = self.ui_data.selected_town.clone() else {
+            return;
+        };
+        let town = &details.town;
+
+        egui::SidePanel::right("town details").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.heading(&town.name);
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    if ui.button("✖").clicked() {
+                        self.ui_data.selected_town = None;
+                    }
+                });
+            });
+
+            egui::Grid::new("town details grid")
+                .num_columns(2)
+                .show(ui, |ui| {
+                    ui.label(t!("map.town_details.id"));
+                    ui.label(town.id.to_string());
+                    ui.end_row();
+                    ui.label(t!("map.town_details.points"));
+                    ui.label(town.points.to_string());
+                    ui.end_row();
+                    ui.label(t!("map.town_details.position"));
+                    ui.label(format!("{:.3}|{:.3}", town.x, town.y));
+                    ui.end_row();
+                    ui.label(t!("map.town_details.ocean"));
+                    ui.label(town.ocean.to_string());
+                    ui.end_row();
+                    ui.label(t!("map.town_details.conquests"));
+                    ui.label(details.conquests.to_string());
+                    ui.end_row();
+                });
+            if town.approximate {
+                ui.label(t!("map.town_details.approximate"));
+            }
+
+            ui.separator();
+            ui.strong(t!("map.town_details.island"));
+            if let Some(island) = &details.island {
+                egui::Grid::new("town details island grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label(t!("map.town_details.position"));
+                        ui.label(format!("{}|{}", town.island_x, town.island_y));
+                        ui.end_row();
+                        ui.label(t!("map.town_details.island_type"));
+                        ui.label(island.typ.to_string());
+                        ui.end_row();
+                        ui.label(t!("map.town_details.island_slots"));
+                        ui.label(island.towns.to_string());
+                        ui.end_row();
+                        ui.label(t!("map.town_details.ressource_plus"));
+                        ui.label(&island.ressource_plus);
+                        ui.end_row();
+                        ui.label(t!("map.town_details.ressource_minus"));
+                        ui.label(&island.ressource_minus);
+                        ui.end_row();
+                    });
+            } else {
+                ui.label(t!("map.town_details.unknown_island"));
+            }
+
+            ui.separator();
+            ui.strong(t!("map.town_details.player"));
+            if let Some(player) = &details.player {
+                egui::Grid::new("town details player grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label(t!("map.town_details.name"));
+                        ui.label(&player.name);
+                        ui.end_row();
+                        ui.label(t!("map.town_details.points"));
+                        ui.label(player.points.to_string());
+                        ui.end_row();
+                        ui.label(t!("map.town_details.rank"));
+                        ui.label(player.rank.to_string());
+                        ui.end_row();
+                        ui.label(t!("map.town_details.towns"));
+                        ui.label(player.towns.to_string());
+                        ui.end_row();
+                    });
+            } else {
+                ui.label(t!("map.town_details.ghost_town"));
+            }
+
+            if let Some(alliance) = &details.alliance {
+                ui.separator();
+                ui.strong(t!("map.town_details.alliance"));
+                egui::Grid::new("town details alliance grid")
+                    .num_columns(2)
+                    .show(ui, |ui| {
+                        ui.label(t!("map.town_details.name"));
+                        ui.label(&alliance.name);
+                        ui.end_row();
+                        ui.label(t!("map.town_details.points"));
+                        ui.label(alliance.points.to_string());
+                        ui.end_row();
+                        ui.label(t!("map.town_details.rank"));
+                        ui.label(alliance.rank.to_string());
+                        ui.end_row();
+                        ui.label(t!("map.town_details.towns"));
+                        ui.label(alliance.towns.to_string());
+                        ui.end_row();
+                        ui.label(t!("map.town_details.members"));
+                        ui.label(alliance.members.to_string());
+                        ui.end_row();
+                    });
+            }
+
+            ui.separator();
+            if let Some(player) = &details.player {
+                let mut target = None;
+                ui.menu_button(t!("map.town_details.add_player"), |ui| {
+                    for (index, selection) in self.ui_data.selections.iter().enumerate() {
+                        if ui.button(&selection.name).clicked() {
+                            target = Some(index);
+                            ui.close_menu();
+                        }
+                    }
+                });
+                if let Some(index) = target {
+                    let constraint = EmptyConstraint {
+                        constraint_type: ConstraintType::PlayerName,
+                        comparator: Comparator::Equal,
+                        value: player.name.clone(),
+                    };
+                    self.add_constraint_to_selection(index, &constraint);
+                }
+            }
+            if ui.button(t!("map.town_details.copy_bbcode")).clicked() {
+                let text = town_bbcode(&details);
+                ui.output_mut(|o| o.copied_text = text);
+            }
+            if ui.button(t!("map.town_details.center")).clicked() {
+                if let Some(canvas) = &mut self.ui_data.canvas {
+                    canvas.center_on(egui::pos2(town.x, town.y));
+                }
+            }
+            let is_origin = self.ui_data.travel_origin == Some(town.id);
+            if ui
+                .add_enabled(
+                    !is_origin,
+                    egui::Button::new(t!("map.town_details.travel_origin")),
+                )
+                .clicked()
+            {
+                self.ui_data.travel_origin = Some(town.id);
+            }
+        });
+    }
+}
+
+/// the town with its owner as `BBCode`, the game turns the tags into links
+fn town_bbcode(details: &TownDetails) -> String {
+    let mut re = format!("[town]{}[/town]", details.town.id);
+    if let Some(player) = &details.player {
+        re = format!("{re} [player]{}[/player]", player.name);
+    }
+    if let Some(alliance) = &details.alliance {
+        re = format!("{re} [ally]{}[/ally]", alliance.name);
+    }
+    return re;
+}
@@ -1,5 +1,6 @@
 use base64::Engine;
 use log::debug;
+use std::time::Duration;
 use wasm_bindgen::JsCast;
 use wasm_bindgen::{closure::Closure, JsValue};
 /// some utility functions that are required for some features on wasm
@@ -60,6 +61,15 @@ pub fn set_current_url(text: &str) {
     // res.expect("failed to update history state");
 }
 
+/// call `callback` once after `delay`, without blocking the thread in the meantime
+pub fn run_after(delay: Duration, callback: impl FnOnce() + 'static) {
+    let window = web_sys::window().expect("no global `window`");
+    let callback = Closure::once_into_js(callback);
+    let millis = i32::try_from(delay.as_millis()).unwrap_or(i32::MAX);
+    let _res = window
+        .set_timeout_with_callback_and_timeout_and_arguments_0(callback.unchecked_ref(), millis);
+}
+
 /// Convert the given arguments into a urlencoded string. e.g. /?server=de99&selections=base64encodedSelections
 pub fn state_to_url_string(
     server_id: Option<&str>,